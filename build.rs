@@ -1,17 +1,123 @@
 extern crate cc;
 extern crate pkg_config;
+#[cfg(feature = "bindgen")]
+extern crate bindgen;
 
 use pkg_config::Library;
 use std::env;
 use std::fs::File;
 use std::io::Write;
-use std::path::Path;
-use std::process::Command;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// How the helper programs run at build time find the curses headers and
+/// library, whether from the system or from a vendored build.
+struct Headers {
+    include_paths: Vec<PathBuf>,
+    /// Macros the library was built with that its headers depend on.
+    defines: Vec<String>,
+    link_args: Vec<String>,
+}
+
+/// The curses implementations the crate can be built against, chosen with
+/// `NCURSES_RS_BACKEND`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Backend {
+    Ncurses,
+    /// NetBSD's libcurses, as packaged for Linux by netbsd-curses.
+    NetBsd,
+    /// PDCursesMod's VT port, which draws with escape sequences itself.
+    PdCursesModVt,
+}
+
+impl Backend {
+    fn from_env() -> Backend {
+        println!("cargo:rerun-if-env-changed=NCURSES_RS_BACKEND");
+        match env::var("NCURSES_RS_BACKEND").as_ref().map(|s| s.as_str()) {
+            Err(_) | Ok("") | Ok("ncurses") => Backend::Ncurses,
+            Ok("netbsd") => Backend::NetBsd,
+            Ok("pdcursesmod-vt") => Backend::PdCursesModVt,
+            Ok(other) => panic!("unknown NCURSES_RS_BACKEND `{}` \
+                                 (expected ncurses, netbsd or pdcursesmod-vt)", other),
+        }
+    }
+
+    /// The value of `cfg(curses_backend)`.
+    fn cfg(self) -> &'static str {
+        match self {
+            Backend::Ncurses => "ncurses",
+            Backend::NetBsd => "netbsd",
+            Backend::PdCursesModVt => "pdcursesmod_vt",
+        }
+    }
+}
+
+/// Optional groups of curses functions. Each is probed by linking a
+/// program against all of its symbols, and enables `cfg(<name>)` if that
+/// works.
+const SYMBOL_GROUPS: &[(&str, &[&str])] = &[
+    ("ncurses_escdelay", &["set_escdelay"]),
+    ("ncurses_window_queries", &[
+        "is_cleared", "is_idcok", "is_idlok", "is_immedok", "is_keypad", "is_leaveok",
+        "is_nodelay", "is_notimeout", "is_scrollok", "is_syncok", "wgetparent", "wgetscrreg",
+    ]),
+    ("ncurses_wgetdelay", &["wgetdelay"]),
+    ("ncurses_extended_colors", &[
+        "init_extended_color", "init_extended_pair", "extended_color_content", "extended_pair_content",
+    ]),
+    ("ncurses_curses_version", &["curses_version"]),
+    ("ncurses_mouse", &[
+        "has_mouse", "mousemask", "ungetmouse", "mouseinterval", "wenclose", "wmouse_trafo", "mouse_trafo",
+    ]),
+    ("ncurses_resize", &["resizeterm", "resize_term", "is_term_resized"]),
+    ("ncurses_default_colors", &["use_default_colors", "assume_default_colors"]),
+    ("ncurses_tabsize", &["set_tabsize"]),
+];
+
+/// The groups the crate has always wrapped unconditionally. With ncurses
+/// they stay enabled even when their probe fails, with a warning, rather
+/// than taking long-standing API away.
+const NCURSES_BASELINE: &[&str] = &[
+    "ncurses_escdelay", "ncurses_window_queries", "ncurses_extended_colors", "ncurses_mouse",
+    "ncurses_resize", "ncurses_default_colors", "ncurses_tabsize",
+];
+
+impl Headers {
+    fn system(lib: &Option<Library>, fallback: &str) -> Headers {
+        match *lib {
+            Some(ref lib) => Headers {
+                include_paths: lib.include_paths.clone(),
+                defines: Vec::new(),
+                link_args: lib.link_paths.iter().map(|p| format!("-L{}", p.display()))
+                    .chain(lib.libs.iter().map(|l| format!("-l{}", l)))
+                    .collect(),
+            },
+            None => Headers {
+                include_paths: Vec::new(),
+                defines: Vec::new(),
+                link_args: vec![format!("-l{}", fallback)],
+            },
+        }
+    }
+
+    fn apply(&self, command: &mut Command) {
+        for path in &self.include_paths {
+            command.arg(format!("-I{}", path.display()));
+        }
+        for define in &self.defines {
+            command.arg(format!("-D{}", define));
+        }
+    }
+}
 
 fn find_library(names: &[&str]) -> Option<Library> {
+    find_library_named(names).map(|(_, lib)| lib)
+}
+
+fn find_library_named<'a>(names: &[&'a str]) -> Option<(&'a str, Library)> {
     for name in names {
         if let Ok(lib) = pkg_config::probe_library(name) {
-            return Some(lib);
+            return Some((name, lib));
         }
     }
     None
@@ -20,154 +126,540 @@ fn find_library(names: &[&str]) -> Option<Library> {
 fn main() {
     println!("cargo:rerun-if-env-changed=PKG_CONFIG_PATH");
 
+    println!("cargo:rustc-check-cfg=cfg(ncurses_reentrant)");
+    println!("cargo:rustc-check-cfg=cfg(curses_backend, values(\"ncurses\", \"netbsd\", \"pdcursesmod_vt\"))");
+    for &(group, _) in SYMBOL_GROUPS {
+        println!("cargo:rustc-check-cfg=cfg({})", group);
+    }
+
     let wide = cfg!(all(feature = "wide", not(target_os = "macos")));
+    let threaded = cfg!(feature = "threaded");
 
-    let ncurses_lib_names = if wide {
-        &["ncursesw5", "ncursesw"]
+    let backend = Backend::from_env();
+    println!("cargo:rustc-cfg=curses_backend=\"{}\"", backend.cfg());
+
+    let headers = if backend != Backend::Ncurses {
+        link_backend(backend, wide, threaded)
+    } else if cfg!(feature = "vendored") {
+        build_vendored(wide, threaded)
     } else {
-        &["ncurses5", "ncurses"]
+        link_system(wide, threaded)
+    };
+
+    check_chtype_size(&headers);
+    probe_symbols(&headers, backend);
+
+    gen_constants(&headers);
+    gen_menu_constants(&headers);
+    gen_bindings(&headers, wide);
+    build_wrap(&headers);
+}
+
+/// Links a curses other than ncurses from `NCURSES_RS_BACKEND_DIR`, which
+/// holds its headers in `include` and its static libraries in `lib`, as
+/// installed by `ci/backends.sh`.
+fn link_backend(backend: Backend, wide: bool, threaded: bool) -> Headers {
+    println!("cargo:rerun-if-env-changed=NCURSES_RS_BACKEND_DIR");
+    if cfg!(feature = "vendored") || threaded {
+        panic!("the `vendored` and `threaded` features need NCURSES_RS_BACKEND=ncurses");
+    }
+    let dir = PathBuf::from(env::var("NCURSES_RS_BACKEND_DIR")
+        .unwrap_or_else(|_| panic!("NCURSES_RS_BACKEND={} needs NCURSES_RS_BACKEND_DIR", backend.cfg())));
+    let lib_dir = dir.join("lib");
+    println!("cargo:rustc-link-search=native={}", lib_dir.display());
+
+    let mut defines = Vec::new();
+    let libs: Vec<&str> = match backend {
+        Backend::NetBsd => {
+            let mut libs = Vec::new();
+            if cfg!(feature = "menu") {
+                libs.push("menu");
+            }
+            if cfg!(feature = "panel") {
+                libs.push("panel");
+            }
+            libs.push("curses");
+            libs.push("terminfo");
+            libs
+        }
+        Backend::PdCursesModVt => {
+            if cfg!(feature = "menu") {
+                panic!("PDCursesMod has no menu library");
+            }
+            // The ncurses flavour of the mouse interface (`MEVENT`,
+            // `nc_getmouse`), and the wide build the library was made with
+            // `make WIDE=Y UTF8=Y`. The panel functions are in libpdcurses.
+            defines.push("NCURSES_MOUSE_VERSION=2".to_string());
+            if wide {
+                defines.push("PDC_WIDE".to_string());
+                defines.push("PDC_FORCE_UTF8".to_string());
+            }
+            vec!["pdcurses"]
+        }
+        Backend::Ncurses => unreachable!(),
+    };
+    for lib in &libs {
+        println!("cargo:rustc-link-lib=static={}", lib);
+    }
+
+    Headers {
+        include_paths: vec![dir.join("include")],
+        defines,
+        link_args: std::iter::once(format!("-L{}", lib_dir.display()))
+            .chain(libs.iter().map(|l| format!("-l{}", l)))
+            .collect(),
+    }
+}
+
+/// Finds the system ncurses (and menu/panel) through pkg-config, falling
+/// back to plain `-l` flags.
+fn link_system(wide: bool, threaded: bool) -> Headers {
+    // The thread-aware builds (`ncursest`/`ncursestw`) are preferred when
+    // the `threaded` feature is on, falling back to the regular ones.
+    let ncurses_lib_names: &[&str] = match (wide, threaded) {
+        (true, true) => &["ncursestw", "ncursesw5", "ncursesw"],
+        (false, true) => &["ncursest", "ncurses5", "ncurses"],
+        (true, false) => &["ncursesw5", "ncursesw"],
+        (false, false) => &["ncurses5", "ncurses"],
+    };
+    let ncurses_lib = match find_library_named(ncurses_lib_names) {
+        Some((name, lib)) => {
+            if name.starts_with("ncursest") {
+                // Globals such as `stdscr` and `LINES` are only reachable
+                // through accessor functions in these builds.
+                println!("cargo:rustc-cfg=ncurses_reentrant");
+            }
+            Some(lib)
+        }
+        None => None,
     };
-    let ncurses_lib = find_library(ncurses_lib_names);
 
     if cfg!(feature = "menu") {
-        if wide {
-            find_library(&["menuw5", "menuw"]);
-        } else {
-            find_library(&["menu5", "menu"]);
+        match (wide, threaded) {
+            (true, true) => find_library(&["menutw", "menuw5", "menuw"]),
+            (false, true) => find_library(&["menut", "menu5", "menu"]),
+            (true, false) => find_library(&["menuw5", "menuw"]),
+            (false, false) => find_library(&["menu5", "menu"]),
+        };
+    }
+
+    if cfg!(feature = "panel") {
+        match (wide, threaded) {
+            (true, true) => find_library(&["paneltw", "panelw5", "panelw"]),
+            (false, true) => find_library(&["panelt", "panel5", "panel"]),
+            (true, false) => find_library(&["panelw5", "panelw"]),
+            (false, false) => find_library(&["panel5", "panel"]),
+        };
+    }
+
+    let fallback = match std::env::var("NCURSES_RS_RUSTC_LINK_LIB") {
+        Ok(x) => {
+            println!("cargo:rustc-link-lib={}", x);
+            // Drop a `kind=` prefix for the helper programs.
+            x.rsplit('=').next().unwrap_or(&x).to_string()
+        }
+        _ => {
+            let name = ncurses_lib_names.last().unwrap();
+            if ncurses_lib.is_none() {
+                println!("cargo:rustc-link-lib={}", name)
+            }
+            name.to_string()
         }
+    };
+
+    if let Ok(x) = std::env::var("NCURSES_RS_RUSTC_FLAGS") {
+        println!("cargo:rustc-flags={}", x);
+    }
+
+    Headers::system(&ncurses_lib, &fallback)
+}
+
+/// Copies the ncurses source bundled in `vendor/ncurses`, or the tree
+/// `NCURSES_RS_SOURCE_DIR` points at instead, into `OUT_DIR`, configures,
+/// builds and installs it there, and links the static libraries.
+///
+/// The build follows the crate features: `wide` adds `--enable-widec`,
+/// `extended_colors` `--enable-ext-colors` and `threaded`
+/// `--enable-reentrant`. The terminfo search path defaults to the usual
+/// system directories and can be set with `NCURSES_RS_TERMINFO_DIRS`.
+fn build_vendored(wide: bool, threaded: bool) -> Headers {
+    println!("cargo:rerun-if-env-changed=NCURSES_RS_SOURCE_DIR");
+    println!("cargo:rerun-if-env-changed=NCURSES_RS_TERMINFO_DIRS");
+
+    let manifest_dir = env::var("CARGO_MANIFEST_DIR").expect("cannot get CARGO_MANIFEST_DIR");
+    let src = env::var_os("NCURSES_RS_SOURCE_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| Path::new(&manifest_dir).join("vendor").join("ncurses"));
+    if !src.join("configure").exists() {
+        panic!("no ncurses source at {}; in a git checkout, run `git submodule update --init vendor/ncurses`, \
+                or point NCURSES_RS_SOURCE_DIR at an unpacked ncurses release", src.display());
+    }
+    println!("cargo:rerun-if-changed={}", src.join("configure").display());
+
+    // Build in a copy: the registry's copy of the crate is read-only, and
+    // ncurses' configure writes into its source tree.
+    let out_dir = PathBuf::from(env::var("OUT_DIR").expect("cannot get OUT_DIR"));
+    let build_dir = out_dir.join("ncurses-src");
+    let prefix = out_dir.join("ncurses");
+
+    let terminfo_dirs = env::var("NCURSES_RS_TERMINFO_DIRS")
+        .unwrap_or_else(|_| "/etc/terminfo:/lib/terminfo:/usr/share/terminfo".to_string());
+    let default_terminfo = terminfo_dirs.split(':').next_back().unwrap_or("/usr/share/terminfo").to_string();
+
+    let mut args = vec![
+        format!("--prefix={}", prefix.display()),
+        "--without-shared".to_string(),
+        "--with-normal".to_string(),
+        "--without-debug".to_string(),
+        "--without-ada".to_string(),
+        "--without-cxx".to_string(),
+        "--without-cxx-binding".to_string(),
+        "--without-manpages".to_string(),
+        "--without-progs".to_string(),
+        "--without-tests".to_string(),
+        "--enable-overwrite".to_string(),
+        format!("--with-terminfo-dirs={}", terminfo_dirs),
+        format!("--with-default-terminfo-dir={}", default_terminfo),
+    ];
+    if wide {
+        args.push("--enable-widec".to_string());
+    }
+    if cfg!(feature = "extended_colors") {
+        args.push("--enable-ext-colors".to_string());
+    }
+    if threaded {
+        args.push("--with-pthread".to_string());
+        args.push("--enable-reentrant".to_string());
+    }
+    let host = env::var("HOST").expect("cannot get HOST");
+    let target = env::var("TARGET").expect("cannot get TARGET");
+    if host != target {
+        // ncurses builds a few tools it runs during the build; those need
+        // the host's compiler.
+        let build_cc = cc::Build::new().host(&host).target(&host).try_get_compiler()
+            .expect("Failed Build::try_get_compiler");
+        args.push(format!("--host={}", target));
+        args.push(format!("--with-build-cc={}", build_cc.path().display()));
+    }
+
+    let suffix = format!("{}{}", if threaded { "t" } else { "" }, if wide { "w" } else { "" });
+    let ncurses = format!("ncurses{}", suffix);
+
+    // Only rebuild when the configuration changes, not on every build
+    // script run.
+    let stamp = out_dir.join("ncurses-configure-args");
+    let configured = std::fs::read_to_string(&stamp).ok() == Some(args.join("\n"));
+    if !configured || !prefix.join("lib").join(format!("lib{}.a", ncurses)).exists() {
+        let _ = std::fs::remove_dir_all(&build_dir);
+        copy_tree(&src, &build_dir).unwrap_or_else(|e| panic!("cannot copy {} to OUT_DIR: {}", src.display(), e));
+        let compiler = cc::Build::new().pic(true).try_get_compiler().expect("Failed Build::try_get_compiler");
+        let cflags = compiler.args().iter().map(|a| a.to_string_lossy().into_owned()).collect::<Vec<_>>().join(" ");
+
+        run(Command::new(build_dir.join("configure"))
+            .args(&args)
+            .current_dir(&build_dir)
+            .env("CC", compiler.path())
+            .env("CFLAGS", cflags));
+        let jobs = env::var("NUM_JOBS").unwrap_or_else(|_| "1".to_string());
+        run(Command::new("make").arg(format!("-j{}", jobs)).arg("libs").current_dir(&build_dir));
+        run(Command::new("make").arg("install.libs").arg("install.includes").current_dir(&build_dir));
+        std::fs::write(&stamp, args.join("\n")).expect("cannot write the configure stamp");
     }
 
+    let lib_dir = prefix.join("lib");
+    println!("cargo:rustc-link-search=native={}", lib_dir.display());
+    if cfg!(feature = "menu") {
+        println!("cargo:rustc-link-lib=static=menu{}", suffix);
+    }
     if cfg!(feature = "panel") {
-        if wide {
-            find_library(&["panelw5", "panelw"]);
+        println!("cargo:rustc-link-lib=static=panel{}", suffix);
+    }
+    println!("cargo:rustc-link-lib=static={}", ncurses);
+    if threaded {
+        println!("cargo:rustc-cfg=ncurses_reentrant");
+    }
+
+    Headers {
+        include_paths: vec![prefix.join("include")],
+        defines: Vec::new(),
+        link_args: vec![format!("-L{}", lib_dir.display()), format!("-l{}", ncurses)],
+    }
+}
+
+/// Copies the directory `from` to `to`, leaving out `.git`.
+fn copy_tree(from: &Path, to: &Path) -> std::io::Result<()> {
+    std::fs::create_dir_all(to)?;
+    for entry in std::fs::read_dir(from)? {
+        let entry = entry?;
+        if entry.file_name() == ".git" {
+            continue;
+        }
+        let target = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_tree(&entry.path(), &target)?;
         } else {
-            find_library(&["panel5", "panel"]);
+            std::fs::copy(entry.path(), &target)?;
         }
     }
+    Ok(())
+}
 
-    match std::env::var("NCURSES_RS_RUSTC_LINK_LIB") {
-        Ok(x) => println!("cargo:rustc-link-lib={}", x),
-        _ => if ncurses_lib.is_none() {
-            println!("cargo:rustc-link-lib={}", ncurses_lib_names.last().unwrap())
+/// Emits the `cfg` of each of `SYMBOL_GROUPS` whose symbols all link.
+/// Only linking is tried, nothing is run, and the symbols are declared
+/// by hand rather than through the headers, so that a macro or a
+/// differently typed declaration cannot get in the way.
+fn probe_symbols(headers: &Headers, backend: Backend) {
+    let out_dir = PathBuf::from(env::var("OUT_DIR").expect("cannot get OUT_DIR"));
+    let compiler = cc::Build::new().try_get_compiler().expect("Failed Build::try_get_compiler");
+
+    for &(group, symbols) in SYMBOL_GROUPS {
+        // Only used with the feature, which needs the wide library.
+        if group == "ncurses_extended_colors" && !cfg!(feature = "extended_colors") {
+            continue;
         }
-    }
+        let src = out_dir.join(format!("probe_{}.c", group));
+        let bin = out_dir.join(if cfg!(windows) { format!("probe_{}.exe", group) } else { format!("probe_{}", group) });
 
-    if let Ok(x) = std::env::var("NCURSES_RS_RUSTC_FLAGS") {
-        println!("cargo:rustc-flags={}", x);
+        let mut code = String::new();
+        for symbol in symbols {
+            code.push_str(&format!("char {}(void);\n", symbol));
+        }
+        code.push_str("int main(void)\n{\n    long n = 0;\n");
+        for symbol in symbols {
+            code.push_str(&format!("    n += (long) &{};\n", symbol));
+        }
+        code.push_str("    return n == 0;\n}\n");
+        std::fs::write(&src, code).unwrap_or_else(|e| panic!("cannot write {}: {}", src.display(), e));
+
+        let mut command = compiler.to_command();
+        if let Ok(x) = std::env::var("NCURSES_RS_CFLAGS") {
+            command.args(x.split(" "));
+        }
+        command.arg("-o").arg(&bin).arg(&src).args(&headers.link_args)
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped());
+        let error = match command.output() {
+            Ok(ref output) if output.status.success() => {
+                println!("cargo:rustc-cfg={}", group);
+                continue;
+            }
+            Ok(output) => String::from_utf8_lossy(&output.stderr).into_owned(),
+            Err(e) => format!("cannot run {:?}: {}", command, e),
+        };
+
+        let baseline = backend == Backend::Ncurses && NCURSES_BASELINE.contains(&group);
+        if baseline {
+            println!("cargo:warning=the probe for {} ({}) failed; enabled anyway, as ncurses has had \
+                      these for long. If linking fails, this ncurses lacks them.", group, symbols.join(", "));
+            println!("cargo:rustc-cfg={}", group);
+        } else {
+            println!("cargo:warning={} ({}) not found in the curses library; their wrappers are left out.",
+                     group, symbols.join(", "));
+        }
+        for line in error.lines().filter(|l| !l.trim().is_empty()).take(8) {
+            println!("cargo:warning=  {}", line);
+        }
     }
+}
+
+fn run(command: &mut Command) {
+    let status = command.status().unwrap_or_else(|e| panic!("cannot run {:?}: {}", command, e));
+    assert!(status.success(), "{:?} failed with {}", command, status);
+}
 
-    check_chtype_size(&ncurses_lib);
+/// Generates `bindings.rs` in `OUT_DIR` from the headers in
+/// `src/bindings.h`, for the `bindings` module and for checking the
+/// hand-written declarations in `ll.rs` against.
+#[cfg(feature = "bindgen")]
+fn gen_bindings(headers: &Headers, wide: bool) {
+    println!("cargo:rerun-if-changed=src/bindings.h");
+    let out_dir = PathBuf::from(env::var("OUT_DIR").expect("cannot get OUT_DIR"));
+
+    let mut builder = bindgen::Builder::default()
+        .header("src/bindings.h")
+        .allowlist_file(r".*[/\\](n?curses|ncurses_dll|unctrl|eti|menu|panel|form)\.h")
+        .layout_tests(false);
+    for path in &headers.include_paths {
+        builder = builder.clang_arg(format!("-I{}", path.display()));
+    }
+    for define in &headers.defines {
+        builder = builder.clang_arg(format!("-D{}", define));
+    }
+    if wide {
+        builder = builder.clang_arg("-D_XOPEN_SOURCE_EXTENDED").clang_arg("-DNCURSES_WIDECHAR=1");
+    }
+    if let Ok(x) = std::env::var("NCURSES_RS_CFLAGS") {
+        builder = builder.clang_args(x.split(" "));
+    }
 
-    gen_constants();
-    gen_menu_constants();
-    build_wrap();
+    builder.generate()
+        .expect("bindgen failed to generate the bindings")
+        .write_to_file(out_dir.join("bindings.rs"))
+        .expect("cannot write bindings.rs");
 }
 
-fn build_wrap() {
+#[cfg(not(feature = "bindgen"))]
+fn gen_bindings(_: &Headers, _: bool) {}
+
+fn build_wrap(headers: &Headers) {
     println!("cargo:rerun-if-changed=src/wrap.c");
-    cc::Build::new()
-        .file("src/wrap.c")
-        .compile("wrap");
+    let mut build = cc::Build::new();
+    for path in &headers.include_paths {
+        build.include(path);
+    }
+    for define in &headers.defines {
+        let mut parts = define.splitn(2, '=');
+        build.define(parts.next().unwrap(), parts.next());
+    }
+    build.file("src/wrap.c").compile("wrap");
 }
 
-fn gen_constants() {
+fn gen_constants(headers: &Headers) {
     println!("cargo:rerun-if-changed=src/genconstants.c");
+    println!("cargo:rerun-if-changed=src/genconstants.h");
     let out_dir = env::var("OUT_DIR").expect("cannot get OUT_DIR");
-    let bin = format!("{}", Path::new(&out_dir).join(if cfg!(windows) { "genconstants.exe" } else { "genconstants" }).display());
     let src = format!("{}", Path::new(&out_dir).join("raw_constants.rs").display());
 
-    let build = cc::Build::new();
-    let compiler = build.try_get_compiler().expect("Failed Build::try_get_compiler");
-    let mut command = compiler.to_command();
-
-    if let Ok(x) = std::env::var("NCURSES_RS_CFLAGS") {
-        command.args(x.split(" "));
-    }    
-
-    command.arg("-o").arg(&bin).arg("src/genconstants.c").arg("-lcurses");
-    assert!(command.status().expect("compilation failed").success());
-
-    let consts = Command::new(&bin).output()
-        .expect(&format!("{} failed", bin));
+    let consts = constants_output(headers, Path::new("src/genconstants.c"), "genconstants");
 
     let mut file = File::create(&src).unwrap();
     
-    file.write_all(&consts.stdout).unwrap();
+    file.write_all(consts.as_bytes()).unwrap();
 }
 
-fn gen_menu_constants() {
+fn gen_menu_constants(headers: &Headers) {
     println!("cargo:rerun-if-changed=src/menu/genconstants.c");
     let out_dir = env::var("OUT_DIR").expect("cannot get OUT_DIR");
-    let bin = format!("{}", Path::new(&out_dir).join(if cfg!(windows) { "genmenuconstants.exe" } else { "genmenuconstants" }).display());
     let src = format!("{}", Path::new(&out_dir).join("menu_constants.rs").display());
 
+    let consts = constants_output(headers, Path::new("src/menu/genconstants.c"), "genmenuconstants");
+
+    let mut file = File::create(&src).unwrap();
+    
+    file.write_all(consts.as_bytes()).unwrap();
+}
+
+/// Compiles the generator `src` (see `src/genconstants.h`) and returns the
+/// lines of Rust it prints.
+///
+/// When cross-compiling, the generator is built for the target and cannot
+/// run here, so it is only compiled to an object file and the constants
+/// are read out of that instead; see `read_records`.
+fn constants_output(headers: &Headers, src: &Path, name: &str) -> String {
+    let out_dir = PathBuf::from(env::var("OUT_DIR").expect("cannot get OUT_DIR"));
+    let cross = env::var("HOST").ok() != env::var("TARGET").ok();
+
     let build = cc::Build::new();
     let compiler = build.try_get_compiler().expect("Failed Build::try_get_compiler");
     let mut command = compiler.to_command();
 
     if let Ok(x) = std::env::var("NCURSES_RS_CFLAGS") {
         command.args(x.split(" "));
-    }    
-
-    command.arg("-o").arg(&bin).arg("src/menu/genconstants.c").arg("-lcurses");
-    assert!(command.status().expect("compilation failed").success());
+    }
 
-    let consts = Command::new(&bin).output()
-        .expect(&format!("{} failed", bin));
+    headers.apply(&mut command);
+    command.arg("-Isrc");
+    if cross {
+        let obj = out_dir.join(format!("{}.o", name));
+        command.arg("-DNCURSES_RS_OBJECT").arg("-c").arg("-o").arg(&obj).arg(src);
+        assert!(command.status().expect("compilation failed").success());
+        let bytes = std::fs::read(&obj).unwrap_or_else(|e| panic!("cannot read {}: {}", obj.display(), e));
+        read_records(&bytes)
+    } else {
+        let bin = out_dir.join(if cfg!(windows) { format!("{}.exe", name) } else { name.to_string() });
+        command.arg("-o").arg(&bin).arg(src).args(&headers.link_args);
+        assert!(command.status().expect("compilation failed").success());
+        let output = Command::new(&bin).output()
+            .unwrap_or_else(|e| panic!("{} failed: {}", bin.display(), e));
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    }
+}
 
-    let mut file = File::create(&src).unwrap();
-    
-    file.write_all(&consts.stdout).unwrap();
+/// Finds the records a generator compiled with `NCURSES_RS_OBJECT` leaves
+/// in its object file, and turns them into the lines it would have
+/// printed. Object formats keep initialized data as is, so the records
+/// are found by their markers; the values are in the target's byte order.
+fn read_records(object: &[u8]) -> String {
+    // NCURSES_RS_TEXT in src/genconstants.h.
+    const TEXT: usize = 120;
+    const MARKER: &[u8] = b"NCURSES_RS_";
+    let big_endian = env::var("CARGO_CFG_TARGET_ENDIAN").map(|e| e == "big").unwrap_or(false);
+
+    let mut out = String::new();
+    let mut records = 0;
+    let mut i = 0;
+    while let Some(found) = object[i..].windows(MARKER.len()).position(|w| w == MARKER) {
+        let start = i + found;
+        i = start + MARKER.len();
+        let end = match object[start..].iter().position(|&b| b == 0) {
+            Some(n) => start + n,
+            None => break,
+        };
+        let record = String::from_utf8_lossy(&object[start..end]);
+        let (kind, text) = match record.find(':') {
+            Some(colon) => (&record[MARKER.len()..colon], &record[colon + 1..]),
+            None => continue,
+        };
+        match kind {
+            "STR" => out.push_str(text),
+            "CONST" | "CONSTU" if start + TEXT + 8 <= object.len() => {
+                let mut value = [0u8; 8];
+                value.copy_from_slice(&object[start + TEXT..start + TEXT + 8]);
+                let value = if big_endian { u64::from_be_bytes(value) } else { u64::from_le_bytes(value) };
+                if kind == "CONST" {
+                    out.push_str(&format!("{}{};", text, value as i64));
+                } else {
+                    out.push_str(&format!("{}{};", text, value));
+                }
+            }
+            _ => continue,
+        }
+        out.push('\n');
+        records += 1;
+        i = end;
+    }
+    assert!(records > 0, "no constants found in the generator's object file \
+                          (is it LLVM bitcode from -flto in CFLAGS?)");
+    out
 }
 
-fn check_chtype_size(ncurses_lib: &Option<Library>) {
+fn check_chtype_size(headers: &Headers) {
     let out_dir = env::var("OUT_DIR").expect("cannot get OUT_DIR");
-    let src = format!("{}", Path::new(&out_dir).join("chtype_size.c").display());
-    let bin = format!("{}", Path::new(&out_dir).join(if cfg!(windows) { "chtype_size.exe" } else { "chtype_size" }).display());
+    let src = Path::new(&out_dir).join("chtype_size.c");
 
-    let mut fp = File::create(&src).expect(&format!("cannot create {}", src));
+    let mut fp = File::create(&src).unwrap_or_else(|e| panic!("cannot create {}: {}", src.display(), e));
     fp.write_all(b"
-#include <assert.h>
 #include <limits.h>
-#include <stdio.h>
 
-#include <ncurses.h>
+#include <curses.h>
 
-int main(void)
-{
-    if (sizeof(chtype)*CHAR_BIT == 64) {
-        puts(\"cargo:rustc-cfg=feature=\\\"wide_chtype\\\"\");
-    } else {
-        /* We only support 32-bit and 64-bit chtype. */
-        assert(sizeof(chtype)*CHAR_BIT == 32 && \"unsupported size for chtype\");
-    }
+#include \"genconstants.h\"
 
-#if defined(NCURSES_MOUSE_VERSION) && NCURSES_MOUSE_VERSION == 1
-	puts(\"cargo:rustc-cfg=feature=\\\"mouse_v1\\\"\");
-#endif
-    return 0;
-}
-    ").expect(&format!("cannot write into {}", src));
+#define CHTYPE_BITS (sizeof(chtype)*CHAR_BIT)
 
-    let mut build = cc::Build::new();
-    if let Some(lib) = ncurses_lib {
-        for path in lib.include_paths.iter() {
-            build.include(path);
-        }
+BEGIN
+    PCONST(u32, CHTYPE_BITS);
+#if defined(NCURSES_MOUSE_VERSION)
+    PCONST(i32, NCURSES_MOUSE_VERSION);
+#endif
+END
+    ").unwrap_or_else(|e| panic!("cannot write into {}: {}", src.display(), e));
+
+    let output = constants_output(headers, &src, "chtype_size");
+    let value = |name: &str| output.lines()
+        .find(|l| l.starts_with(&format!("pub const {}:", name)))
+        .and_then(|l| l.rsplit("= ").next())
+        .and_then(|v| v.trim_end_matches(';').parse::<i64>().ok());
+
+    match value("CHTYPE_BITS") {
+        Some(64) => println!("cargo:rustc-cfg=feature=\"wide_chtype\""),
+        // We only support 32-bit and 64-bit chtype.
+        Some(32) => {}
+        bits => panic!("unsupported size for chtype: {:?} bits", bits),
     }
-    let compiler = build.try_get_compiler().expect("Failed Build::try_get_compiler");
-    let mut command = compiler.to_command();
-
-    if let Ok(x) = std::env::var("NCURSES_RS_CFLAGS") {
-        command.args(x.split(" "));
+    if value("NCURSES_MOUSE_VERSION") == Some(1) {
+        println!("cargo:rustc-cfg=feature=\"mouse_v1\"");
     }
 
-    command.arg("-o").arg(&bin).arg(&src);
-    assert!(command.status().expect("compilation failed").success());
-    let features = Command::new(&bin).output()
-        .expect(&format!("{} failed", bin));
-    print!("{}", String::from_utf8_lossy(&features.stdout));
-
-    std::fs::remove_file(&src).expect(&format!("cannot delete {}", src));
-    std::fs::remove_file(&bin).expect(&format!("cannot delete {}", bin));
+    std::fs::remove_file(&src).unwrap_or_else(|e| panic!("cannot delete {}: {}", src.display(), e));
 }
@@ -0,0 +1,81 @@
+#!/bin/sh
+#
+# Builds and tests the crate against each curses backend, the non-ncurses
+# ones built from source into a local prefix first:
+#
+#   ci/backends.sh [ncurses|netbsd|pdcursesmod-vt]...
+#
+# With no arguments all three are run. Sources are cloned into
+# $CI_BACKENDS_DIR (default target/backends) unless NETBSD_CURSES_SRC or
+# PDCURSESMOD_SRC point at existing checkouts.
+
+set -eu
+
+ROOT=$(cd "$(dirname "$0")/.." && pwd)
+WORK=${CI_BACKENDS_DIR:-$ROOT/target/backends}
+JOBS=${JOBS:-4}
+NETBSD_CURSES_REPO=https://github.com/sabotage-linux/netbsd-curses
+PDCURSESMOD_REPO=https://github.com/Bill-Gray/PDCursesMod
+
+mkdir -p "$WORK"
+cd "$ROOT"
+
+# fetch NAME REPO: clones REPO into $WORK/NAME unless already there.
+fetch() {
+    [ -d "$WORK/$1" ] || git clone --depth 1 "$2" "$WORK/$1"
+}
+
+# check BACKEND DIR FEATURES...: builds and tests with each feature set.
+check() {
+    backend=$1 dir=$2
+    shift 2
+    for features in "$@"; do
+        echo "== $backend, features: ${features:-none}"
+        NCURSES_RS_BACKEND=$backend NCURSES_RS_BACKEND_DIR=$dir \
+            CARGO_TARGET_DIR="$WORK/target-$backend" \
+            cargo build --verbose --features "$features"
+        NCURSES_RS_BACKEND=$backend NCURSES_RS_BACKEND_DIR=$dir \
+            CARGO_TARGET_DIR="$WORK/target-$backend" \
+            cargo test --verbose --features "$features"
+    done
+}
+
+ncurses() {
+    check ncurses "" "" "wide" "wide,panel,menu"
+}
+
+netbsd() {
+    src=${NETBSD_CURSES_SRC:-$WORK/netbsd-curses}
+    [ -n "${NETBSD_CURSES_SRC:-}" ] || fetch netbsd-curses "$NETBSD_CURSES_REPO"
+    prefix=$WORK/netbsd-prefix
+    make -C "$src" -j"$JOBS" CFLAGS="-O2 -fPIC" PREFIX="$prefix" all-static
+    make -C "$src" PREFIX="$prefix" install-static
+    check netbsd "$prefix" "" "wide,panel,menu"
+}
+
+pdcursesmod_vt() {
+    src=${PDCURSESMOD_SRC:-$WORK/PDCursesMod}
+    [ -n "${PDCURSESMOD_SRC:-}" ] || fetch PDCursesMod "$PDCURSESMOD_REPO"
+    prefix=$WORK/pdcursesmod-prefix
+    # Rust links position-independent executables, so the static library
+    # has to be PIC too.
+    make -C "$src/vt" -j"$JOBS" CC="${CC:-cc} -fPIC" WIDE=Y UTF8=Y
+    mkdir -p "$prefix/include" "$prefix/lib"
+    cp "$src/curses.h" "$src/panel.h" "$prefix/include/"
+    for lib in libpdcurses.a pdcurses.a; do
+        if [ -f "$src/vt/$lib" ]; then
+            cp "$src/vt/$lib" "$prefix/lib/libpdcurses.a"
+        fi
+    done
+    check pdcursesmod-vt "$prefix" "wide" "wide,panel"
+}
+
+[ $# -gt 0 ] || set -- ncurses netbsd pdcursesmod-vt
+for backend in "$@"; do
+    case $backend in
+        ncurses) ncurses ;;
+        netbsd) netbsd ;;
+        pdcursesmod-vt) pdcursesmod_vt ;;
+        *) echo "unknown backend: $backend" >&2; exit 1 ;;
+    esac
+done
@@ -25,7 +25,8 @@ fn main()
 
   /* Require input within 2 seconds. */
   halfdelay(20);
-  /* Enable mouse events. */
+  /* Enable mouse events, where the curses library has them. */
+  #[cfg(ncurses_mouse)]
   mousemask(ALL_MOUSE_EVENTS as mmask_t, None);
 
   /* Allow for extended keyboard (like F1). */
@@ -0,0 +1,64 @@
+/*
+    File: examples/ex_9.rs
+    Description:
+      A file viewer built on `Pad`, with scrollbars and resize handling.
+
+      Usage:
+        cargo run --example ex_9 <file>
+      Example:
+        cargo run --example ex_9 examples/ex_9.rs
+*/
+
+extern crate ncurses;
+
+use std::env;
+use std::fs;
+use ncurses::*;
+
+fn main()
+{
+  let args: Vec<_> = env::args().collect();
+  if args.len() != 2
+  {
+    println!("Usage:\n\t{} <file>", args[0]);
+    return;
+  }
+  let text = fs::read_to_string(&args[1]).expect("Unable to read file");
+  let lines: Vec<&str> = text.lines().collect();
+  let width = lines.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+
+  let session = Curses::init();
+  let status = || Rect::new(LINES() - 1, 0, 1, COLS());
+  let body = || Rect::new(0, 0, LINES() - 1, COLS());
+
+  let mut pad = Pad::new(&session, lines.len() as i32, width as i32 + 1, body()).expect("newpad failed");
+  pad.set_scrollbars(true);
+  for (y, line) in lines.iter().enumerate()
+  { pad.window().mvaddstr(y as i32, 0, line).unwrap(); }
+
+  loop
+  {
+    let (top, left) = pad.offset();
+    let s = status();
+    mvhline(s.y, 0, ' ' as chtype, s.cols);
+    mvaddstr(s.y, 0, &format!("{} line {}/{} col {}  (q to quit)", args[1], top + 1, lines.len(), left + 1)).unwrap();
+    wnoutrefresh(stdscr());
+    pad.refresh();
+
+    let page = pad.visible().lines;
+    match getch()
+    {
+      KEY_UP => pad.scroll_by(-1, 0),
+      KEY_DOWN => pad.scroll_by(1, 0),
+      KEY_LEFT => pad.scroll_by(0, -1),
+      KEY_RIGHT => pad.scroll_by(0, 1),
+      KEY_PPAGE => pad.scroll_by(-page, 0),
+      KEY_NPAGE | 0x20 => pad.scroll_by(page, 0),
+      KEY_HOME => pad.scroll_to(0, 0),
+      KEY_END => pad.scroll_to(lines.len() as i32, 0),
+      KEY_RESIZE => { clear(); pad.set_viewport(body()); }
+      0x71 => break,
+      _ => {}
+    }
+  }
+}
@@ -0,0 +1,19 @@
+/*
+    Headers read by bindgen with the `bindgen` feature. The menu, panel
+    and form libraries are optional; their headers are skipped when not
+    installed.
+*/
+
+#include <curses.h>
+
+#if __has_include(<menu.h>)
+#include <menu.h>
+#endif
+
+#if __has_include(<panel.h>)
+#include <panel.h>
+#endif
+
+#if __has_include(<form.h>)
+#include <form.h>
+#endif
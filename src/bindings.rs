@@ -0,0 +1,13 @@
+/*
+    Declarations generated by bindgen from the installed curses, menu,
+    panel and form headers, with the `bindgen` feature.
+
+    Unlike `ll`, these follow the headers exactly: `MENU`, `ITEM` and
+    `PANEL` are real struct types, and integer types have the widths the
+    headers give them. The wrappers in this crate still use `ll`.
+*/
+
+#![allow(non_upper_case_globals, non_camel_case_types, non_snake_case, dead_code)]
+#![allow(clippy::all)]
+
+include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
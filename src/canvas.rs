@@ -0,0 +1,357 @@
+/*
+    Line drawing with automatic junctions.
+
+    `hline`/`vline`/`border` each draw on their own, so where two lines meet
+    one simply overwrites the other. A `LineCanvas` records which of the
+    four arms (up, down, left, right) of each cell carry a line and only
+    picks the character when drawing, so crossings become `ACS_PLUS`, tees
+    become `ACS_TTEE` and so on. The characters come from `Glyphs`, so with
+    Unicode the heavy and double line styles show too.
+*/
+
+use std::cmp::max;
+
+use curses::Window;
+use rect::Rect;
+use ll::chtype;
+use constants::{OK, ERR};
+use glyphs::Glyphs;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LineStyle {
+  Light = 1,
+  Heavy = 2,
+  Double = 3,
+}
+
+impl LineStyle {
+  fn from_bits(bits: u8) -> Option<LineStyle> {
+    match bits & 3 {
+      1 => Some(LineStyle::Light),
+      2 => Some(LineStyle::Heavy),
+      3 => Some(LineStyle::Double),
+      _ => None,
+    }
+  }
+}
+
+/// The lines leaving a cell in each direction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Arms {
+  pub up: Option<LineStyle>,
+  pub down: Option<LineStyle>,
+  pub left: Option<LineStyle>,
+  pub right: Option<LineStyle>,
+}
+
+const UP: u32 = 0;
+const DOWN: u32 = 2;
+const LEFT: u32 = 4;
+const RIGHT: u32 = 6;
+
+impl Arms {
+  fn from_bits(bits: u8) -> Arms {
+    Arms {
+      up: LineStyle::from_bits(bits >> UP),
+      down: LineStyle::from_bits(bits >> DOWN),
+      left: LineStyle::from_bits(bits >> LEFT),
+      right: LineStyle::from_bits(bits >> RIGHT),
+    }
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.up.is_none() && self.down.is_none() && self.left.is_none() && self.right.is_none()
+  }
+
+  /// The ACS character for this junction. Line styles are ignored, since
+  /// the alternate character set only has one.
+  pub fn acs(&self) -> chtype {
+    let (u, d, l, r) = (self.up.is_some(), self.down.is_some(), self.left.is_some(), self.right.is_some());
+    match (u, d, l, r) {
+      (true, true, true, true) => ::ACS_PLUS(),
+      (true, true, false, true) => ::ACS_LTEE(),
+      (true, true, true, false) => ::ACS_RTEE(),
+      (false, true, true, true) => ::ACS_TTEE(),
+      (true, false, true, true) => ::ACS_BTEE(),
+      (false, true, false, true) => ::ACS_ULCORNER(),
+      (false, true, true, false) => ::ACS_URCORNER(),
+      (true, false, false, true) => ::ACS_LLCORNER(),
+      (true, false, true, false) => ::ACS_LRCORNER(),
+      (_, _, false, false) => ::ACS_VLINE(),
+      _ => ::ACS_HLINE(),
+    }
+  }
+
+  /// The Unicode box drawing character for this junction.
+  pub fn unicode(&self) -> char {
+    let mut a = *self;
+    let double = [a.up, a.down, a.left, a.right].contains(&Some(LineStyle::Double));
+    if double {
+      return a.unicode_double();
+    }
+
+    // Single arms are drawn as a full line, so that line ends look
+    // like lines rather than stubs.
+    match (a.up, a.down, a.left, a.right) {
+      (Some(s), None, None, None) | (None, Some(s), None, None) => a = Arms { up: Some(s), down: Some(s), ..a },
+      (None, None, Some(s), None) | (None, None, None, Some(s)) => a = Arms { left: Some(s), right: Some(s), ..a },
+      _ => {}
+    }
+
+    let h = |s: Option<LineStyle>| (s == Some(LineStyle::Heavy)) as u32;
+    let (u, d, l, r) = (h(a.up), h(a.down), h(a.left), h(a.right));
+    let code = match (a.up.is_some(), a.down.is_some(), a.left.is_some(), a.right.is_some()) {
+      (true, true, false, false) => match (u, d) {
+        (0, 0) => 0x2502,
+        (1, 1) => 0x2503,
+        (0, _) => 0x257D,
+        _ => 0x257F,
+      },
+      (false, false, true, true) => match (l, r) {
+        (0, 0) => 0x2500,
+        (1, 1) => 0x2501,
+        (0, _) => 0x257C,
+        _ => 0x257E,
+      },
+      (false, true, false, true) => 0x250C + 2 * d + r,
+      (false, true, true, false) => 0x2510 + 2 * d + l,
+      (true, false, false, true) => 0x2514 + 2 * u + r,
+      (true, false, true, false) => 0x2518 + 2 * u + l,
+      (true, true, false, true) => 0x251C + vertical_tee(u, d, r),
+      (true, true, true, false) => 0x2524 + vertical_tee(u, d, l),
+      (false, true, true, true) => 0x252C + l + 2 * r + 4 * d,
+      (true, false, true, true) => 0x2534 + l + 2 * r + 4 * u,
+      (true, true, true, true) => {
+        const CROSS: [u32; 16] = [0, 1, 2, 3, 4, 7, 8, 11, 5, 9, 10, 12, 6, 13, 14, 15];
+        0x253C + CROSS[(l | r << 1 | u << 2 | d << 3) as usize]
+      }
+      _ => 0x0020,
+    };
+    ::std::char::from_u32(code).unwrap_or(' ')
+  }
+
+  /// Unicode only mixes double lines with light ones, and only with both
+  /// vertical (or both horizontal) arms alike.
+  fn unicode_double(&self) -> char {
+    let is_double = |s: Option<LineStyle>| s == Some(LineStyle::Double);
+    let vd = is_double(self.up) || is_double(self.down);
+    let hd = is_double(self.left) || is_double(self.right);
+    let (u, d, l, r) = (self.up.is_some(), self.down.is_some(), self.left.is_some(), self.right.is_some());
+    let group = match (u, d, l, r) {
+      (_, _, false, false) => return if vd { '\u{2551}' } else { '\u{2502}' },
+      (false, false, _, _) => return if hd { '\u{2550}' } else { '\u{2500}' },
+      (false, true, false, true) => 0,
+      (false, true, true, false) => 1,
+      (true, false, false, true) => 2,
+      (true, false, true, false) => 3,
+      (true, true, false, true) => 4,
+      (true, true, true, false) => 5,
+      (false, true, true, true) => 6,
+      (true, false, true, true) => 7,
+      _ => 8,
+    };
+    let variant = match (vd, hd) {
+      (false, _) => 0,
+      (true, false) => 1,
+      (true, true) => 2,
+    };
+    ::std::char::from_u32(0x2552 + 3 * group + variant).unwrap_or(' ')
+  }
+}
+
+/// Offset within the `├`/`┤` groups, which are not in binary order.
+fn vertical_tee(up: u32, down: u32, side: u32) -> u32 {
+  match (up, down, side) {
+    (0, 0, 0) => 0,
+    (0, 0, _) => 1,
+    (1, 0, 0) => 2,
+    (0, 1, 0) => 3,
+    (1, 1, 0) => 4,
+    (1, 0, _) => 5,
+    (0, 1, _) => 6,
+    _ => 7,
+  }
+}
+
+/// Line segments recorded over a `lines` x `cols` area.
+#[derive(Debug, Clone)]
+pub struct LineCanvas {
+  lines: i32,
+  cols: i32,
+  cells: Vec<u8>,
+}
+
+impl LineCanvas {
+  pub fn new(lines: i32, cols: i32) -> LineCanvas {
+    let (lines, cols) = (max(0, lines), max(0, cols));
+    LineCanvas { lines, cols, cells: vec![0; (lines * cols) as usize] }
+  }
+
+  /// A canvas the size of `w`.
+  pub fn for_window(w: &Window) -> LineCanvas {
+    let (lines, cols) = w.size();
+    LineCanvas::new(lines, cols)
+  }
+
+  pub fn size(&self) -> (i32, i32) {
+    (self.lines, self.cols)
+  }
+
+  pub fn clear(&mut self) {
+    for c in self.cells.iter_mut() {
+      *c = 0;
+    }
+  }
+
+  fn add(&mut self, y: i32, x: i32, arm: u32, style: LineStyle) {
+    if y < 0 || x < 0 || y >= self.lines || x >= self.cols {
+      return;
+    }
+    let cell = &mut self.cells[(y * self.cols + x) as usize];
+    let current = (*cell >> arm) & 3;
+    let wanted = max(current, style as u8);
+    *cell = (*cell & !(3 << arm)) | (wanted << arm);
+  }
+
+  /// A horizontal line of `len` cells starting at `(y, x)`.
+  pub fn hline(&mut self, y: i32, x: i32, len: i32, style: LineStyle) {
+    for i in 0..len {
+      if i > 0 {
+        self.add(y, x + i, LEFT, style);
+      }
+      if i < len - 1 {
+        self.add(y, x + i, RIGHT, style);
+      }
+    }
+    if len == 1 {
+      self.add(y, x, LEFT, style);
+      self.add(y, x, RIGHT, style);
+    }
+  }
+
+  /// A vertical line of `len` cells starting at `(y, x)`.
+  pub fn vline(&mut self, y: i32, x: i32, len: i32, style: LineStyle) {
+    for i in 0..len {
+      if i > 0 {
+        self.add(y + i, x, UP, style);
+      }
+      if i < len - 1 {
+        self.add(y + i, x, DOWN, style);
+      }
+    }
+    if len == 1 {
+      self.add(y, x, UP, style);
+      self.add(y, x, DOWN, style);
+    }
+  }
+
+  /// The outline of `r`.
+  pub fn rect(&mut self, r: Rect, style: LineStyle) {
+    if r.is_empty() {
+      return;
+    }
+    self.hline(r.y, r.x, r.cols, style);
+    self.hline(r.bottom() - 1, r.x, r.cols, style);
+    self.vline(r.y, r.x, r.lines, style);
+    self.vline(r.y, r.right() - 1, r.lines, style);
+  }
+
+  pub fn arms(&self, y: i32, x: i32) -> Arms {
+    if y < 0 || x < 0 || y >= self.lines || x >= self.cols {
+      return Arms::default();
+    }
+    Arms::from_bits(self.cells[(y * self.cols + x) as usize])
+  }
+
+  /// Draws every cell that carries a line, with the window's current
+  /// attributes and `Glyphs::current()`. Other cells are left alone.
+  pub fn draw(&self, w: &Window) -> i32 {
+    self.draw_with(w, &Glyphs::current())
+  }
+
+  pub fn draw_with(&self, w: &Window, glyphs: &Glyphs) -> i32 {
+    let mut ret = OK;
+    for y in 0..self.lines {
+      for x in 0..self.cols {
+        let arms = self.arms(y, x);
+        if arms.is_empty() {
+          continue;
+        }
+        if glyphs.junction(&arms).mvput(w, y, x) == ERR {
+          ret = ERR;
+        }
+      }
+    }
+    ret
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use testing::with_screen;
+
+  fn arms(up: Option<LineStyle>, down: Option<LineStyle>, left: Option<LineStyle>, right: Option<LineStyle>) -> Arms {
+    Arms { up, down, left, right }
+  }
+
+  const L: Option<LineStyle> = Some(LineStyle::Light);
+  const H: Option<LineStyle> = Some(LineStyle::Heavy);
+  const D: Option<LineStyle> = Some(LineStyle::Double);
+
+  #[test]
+  fn unicode_joins() {
+    let cases = [
+      (arms(L, L, L, L), '┼'), (arms(H, H, H, H), '╋'), (arms(L, L, H, H), '┿'), (arms(H, L, L, L), '╀'),
+      (arms(L, L, None, L), '├'), (arms(L, L, None, H), '┝'), (arms(H, L, None, L), '┞'), (arms(H, H, None, H), '┣'),
+      (arms(None, L, None, L), '┌'), (arms(None, L, None, H), '┍'), (arms(None, H, None, H), '┏'),
+      (arms(L, None, H, None), '┙'), (arms(None, L, L, L), '┬'), (arms(None, H, H, H), '┳'),
+      (arms(L, None, None, None), '│'), (arms(None, None, None, H), '━'), (arms(L, H, None, None), '╽'),
+    ];
+    for &(a, c) in &cases {
+      assert_eq!(a.unicode(), c, "{:?}", a);
+    }
+  }
+
+  #[test]
+  fn unicode_double_joins() {
+    let cases = [
+      (arms(None, None, D, None), '═'), (arms(D, None, None, None), '║'), (arms(None, D, None, D), '╔'),
+      (arms(None, L, None, D), '╒'), (arms(None, D, None, L), '╓'), (arms(D, D, D, D), '╬'),
+      (arms(D, D, None, D), '╠'), (arms(L, L, D, D), '╪'),
+    ];
+    for &(a, c) in &cases {
+      assert_eq!(a.unicode(), c, "{:?}", a);
+    }
+  }
+
+  #[test]
+  fn acs_joins() {
+    with_screen(|_| {
+      let cases = [
+        (arms(L, L, L, L), ::ACS_PLUS()), (arms(H, H, None, L), ::ACS_LTEE()), (arms(L, L, D, None), ::ACS_RTEE()),
+        (arms(None, L, L, L), ::ACS_TTEE()), (arms(L, None, L, L), ::ACS_BTEE()),
+        (arms(None, L, None, L), ::ACS_ULCORNER()), (arms(None, L, L, None), ::ACS_URCORNER()),
+        (arms(L, None, None, L), ::ACS_LLCORNER()), (arms(L, None, L, None), ::ACS_LRCORNER()),
+        (arms(L, None, None, None), ::ACS_VLINE()), (arms(None, None, None, D), ::ACS_HLINE()),
+      ];
+      for &(a, ch) in &cases {
+        assert_eq!(a.acs(), ch, "{:?}", a);
+      }
+    });
+  }
+
+  #[test]
+  fn crossing_lines_record_junctions() {
+    let mut canvas = LineCanvas::new(3, 5);
+    canvas.rect(Rect::new(0, 0, 3, 5), LineStyle::Light);
+    canvas.vline(0, 2, 3, LineStyle::Heavy);
+    canvas.hline(1, 0, 5, LineStyle::Light);
+    assert_eq!(canvas.arms(0, 2), arms(None, H, L, L));
+    assert_eq!(canvas.arms(1, 2), arms(H, H, L, L));
+    assert_eq!(canvas.arms(1, 0), arms(L, L, None, L));
+    assert_eq!(canvas.arms(2, 4), arms(L, None, L, None));
+    assert!(canvas.arms(1, 1).up.is_none());
+    assert!(canvas.arms(3, 0).is_empty());
+  }
+}
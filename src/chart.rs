@@ -0,0 +1,559 @@
+/*
+    Charts for monitoring screens: sparklines, bar charts and line or
+    scatter plots.
+
+    Sparklines and bars are built from the lower block characters (U+2581
+    to U+2588) and plots from braille patterns, which give 2x4 dots per
+    cell. Both need the `wide` feature and Unicode glyphs; otherwise the
+    charts fall back to ASCII, with `.:-=+*#` for heights and one `*` per
+    cell for plots.
+
+    Colours come from `Style`, so series and bars can be told apart with
+    `Style::new().fg(Color::Green)` and the like.
+*/
+
+use std::cmp::max;
+
+use curses::Window;
+use rect::Rect;
+use constants::*;
+use canvas::{Arms, LineStyle};
+use glyphs::{Glyph, Glyphs, GlyphSet, Symbol};
+use styled::{Align, Style};
+use width::pad;
+
+const ASCII_LEVELS: [char; 8] = ['.', ':', '-', '=', '+', '*', '#', '#'];
+
+fn unicode(glyphs: &Glyphs) -> bool {
+  cfg!(feature = "wide") && glyphs.set() == GlyphSet::Unicode
+}
+
+/// The glyph for a cell filled `eighths`/8 from the bottom, 1 to 8.
+fn level(glyphs: &Glyphs, eighths: usize) -> Glyph {
+  let eighths = eighths.clamp(1, 8);
+  if unicode(glyphs) {
+    Glyph::Char(::std::char::from_u32(0x2580 + eighths as u32).unwrap_or(' '))
+  } else {
+    Glyph::Char(ASCII_LEVELS[eighths - 1])
+  }
+}
+
+/// Runs `f` with `style` set on `w`, restoring the window's own
+/// attributes afterwards.
+fn with_style<F: FnOnce() -> i32>(w: &Window, style: Style, f: F) -> i32 {
+  let (mut attrs, mut pair) = (0, 0);
+  ::wattr_get(w.raw(), &mut attrs, &mut pair);
+  let (a, p) = style.resolve(attrs, pair);
+  w.attr_set(a, p);
+  let ret = f();
+  w.attr_set(attrs, pair);
+  ret
+}
+
+/// Short label for an axis value.
+fn format_value(v: f64) -> String {
+  if v.fract() == 0.0 || v.abs() >= 100.0 {
+    format!("{:.0}", v)
+  } else {
+    format!("{:.1}", v)
+  }
+}
+
+/// Smallest and largest finite value, widened if they are equal.
+fn bounds<I: Iterator<Item = f64>>(values: I) -> (f64, f64) {
+  let (lo, hi) = values.filter(|v| v.is_finite()).fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), v| (lo.min(v), hi.max(v)));
+  if lo > hi {
+    (0.0, 1.0)
+  } else {
+    widen(lo, hi)
+  }
+}
+
+/// An empty range made wide enough to plot over: by 1 each way, or by a
+/// millionth of the value where 1 would get lost in rounding.
+fn widen(lo: f64, hi: f64) -> (f64, f64) {
+  if lo != hi {
+    return (lo, hi);
+  }
+  let d = (lo.abs() * 1e-6).max(1.0);
+  (lo - d, hi + d)
+}
+
+/// The range of an axis: `fixed` if both ends are finite, widened like a
+/// fitted one if they are equal, and otherwise fitted to `values`.
+fn axis_range<I: Iterator<Item = f64>>(fixed: Option<(f64, f64)>, values: I) -> (f64, f64) {
+  match fixed {
+    Some((lo, hi)) if lo.is_finite() && hi.is_finite() => widen(lo, hi),
+    _ => bounds(values),
+  }
+}
+
+/// Values drawn as a one-line run of bars.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sparkline {
+  style: Style,
+  glyphs: Glyphs,
+  max: Option<f64>,
+}
+
+impl Default for Sparkline {
+  fn default() -> Sparkline {
+    Sparkline::new()
+  }
+}
+
+impl Sparkline {
+  pub fn new() -> Sparkline {
+    Sparkline { style: Style::new(), glyphs: Glyphs::current(), max: None }
+  }
+
+  pub fn style(mut self, style: Style) -> Sparkline {
+    self.style = style;
+    self
+  }
+
+  pub fn glyphs(mut self, glyphs: Glyphs) -> Sparkline {
+    self.glyphs = glyphs;
+    self
+  }
+
+  /// The value of a full cell; by default the largest value shown.
+  pub fn max(mut self, max: f64) -> Sparkline {
+    self.max = Some(max);
+    self
+  }
+
+  /// Draws the last `cols` values of `data` at `(y, x)`, oldest first.
+  /// Zero, negative and missing values leave a blank cell.
+  pub fn draw(&self, w: &Window, y: i32, x: i32, cols: i32, data: &[f64]) -> i32 {
+    let cols = max(0, cols) as usize;
+    let shown = &data[data.len().saturating_sub(cols)..];
+    let top = self.max.unwrap_or_else(|| bounds(shown.iter().cloned()).1);
+    with_style(w, self.style, || {
+      let mut ret = OK;
+      for i in 0..cols {
+        let g = match shown.get(i) {
+          Some(&v) if v > 0.0 && top > 0.0 => level(&self.glyphs, (v / top * 8.0).round() as usize),
+          _ => Glyph::Char(' '),
+        };
+        ret |= g.mvput(w, y, x + i as i32);
+      }
+      if ret == OK { OK } else { ERR }
+    })
+  }
+}
+
+/// One bar of a `BarChart`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bar {
+  pub label: String,
+  pub value: f64,
+  pub style: Option<Style>,
+}
+
+impl Bar {
+  pub fn new(label: &str, value: f64) -> Bar {
+    Bar { label: label.to_string(), value, style: None }
+  }
+
+  pub fn style(mut self, style: Style) -> Bar {
+    self.style = Some(style);
+    self
+  }
+}
+
+/// Vertical bars with a value axis on the left and labels underneath.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BarChart {
+  bars: Vec<Bar>,
+  bar_width: i32,
+  gap: i32,
+  style: Style,
+  max: Option<f64>,
+  glyphs: Glyphs,
+}
+
+impl BarChart {
+  pub fn new(bars: Vec<Bar>) -> BarChart {
+    BarChart { bars, bar_width: 3, gap: 1, style: Style::new(), max: None, glyphs: Glyphs::current() }
+  }
+
+  pub fn bar_width(mut self, cols: i32) -> BarChart {
+    self.bar_width = max(1, cols);
+    self
+  }
+
+  pub fn gap(mut self, cols: i32) -> BarChart {
+    self.gap = max(0, cols);
+    self
+  }
+
+  /// Style for bars that have none of their own.
+  pub fn style(mut self, style: Style) -> BarChart {
+    self.style = style;
+    self
+  }
+
+  /// The value at the top of the axis; by default the largest bar.
+  pub fn max(mut self, max: f64) -> BarChart {
+    self.max = Some(max);
+    self
+  }
+
+  pub fn glyphs(mut self, glyphs: Glyphs) -> BarChart {
+    self.glyphs = glyphs;
+    self
+  }
+
+  /// Draws into `area`, which is cleared first. Bars that do not fit on
+  /// the right are left out.
+  pub fn draw(&self, w: &Window, area: Rect) -> i32 {
+    if area.lines < 3 || area.cols < 4 {
+      return ERR;
+    }
+    let top = self.max.unwrap_or_else(|| bounds(self.bars.iter().map(|b| b.value).chain(Some(0.0))).1);
+    let labels = [format_value(top), format_value(0.0)];
+    let axis_x = area.x + labels.iter().map(|l| l.len() as i32).max().unwrap_or(1);
+    let plot_rows = area.lines - 2;
+    let base_y = area.y + plot_rows;
+
+    for y in area.y..area.bottom() {
+      w.put_str_padded(y, area.x, "", area.cols, Align::Left).ok();
+    }
+    w.put_str_clipped(area.y, area.x, &labels[0], axis_x - area.x).ok();
+    w.put_str_clipped(base_y - 1, area.x, &labels[1], axis_x - area.x).ok();
+    draw_axes(w, &self.glyphs, axis_x, area.y, base_y, area.right());
+
+    let mut x = axis_x + 1 + self.gap;
+    for bar in &self.bars {
+      if x + self.bar_width > area.right() {
+        break;
+      }
+      let eighths = if top > 0.0 { (bar.value.max(0.0) / top * (plot_rows * 8) as f64).round() as i32 } else { 0 };
+      let style = bar.style.unwrap_or(self.style);
+      with_style(w, style, || {
+        for row in 0..plot_rows {
+          let fill = eighths - row * 8;
+          if fill <= 0 {
+            break;
+          }
+          let g = if fill >= 8 || !unicode(&self.glyphs) { self.glyphs.get(Symbol::Block) } else { level(&self.glyphs, fill as usize) };
+          for i in 0..self.bar_width {
+            g.mvput(w, base_y - 1 - row, x + i);
+          }
+        }
+        OK
+      });
+      let label = pad(&bar.label, self.bar_width as usize, Align::Center);
+      w.put_str_clipped(base_y + 1, x, &label, self.bar_width).ok();
+      x += self.bar_width + self.gap;
+    }
+    OK
+  }
+}
+
+/// The value axis from `top` down to the baseline at `base_y`, and the
+/// baseline out to `right`.
+fn draw_axes(w: &Window, glyphs: &Glyphs, axis_x: i32, top: i32, base_y: i32, right: i32) {
+  let v = glyphs.get(Symbol::VLine);
+  for y in top..base_y {
+    v.mvput(w, y, axis_x);
+  }
+  let s = Some(LineStyle::Light);
+  glyphs.junction(&Arms { up: s, right: s, ..Arms::default() }).mvput(w, base_y, axis_x);
+  let h = glyphs.get(Symbol::HLine);
+  for x in axis_x + 1..right {
+    h.mvput(w, base_y, x);
+  }
+}
+
+/// A grid of braille dots, 2 wide and 4 high per cell, each cell
+/// remembering the series that last set a dot in it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dots {
+  lines: i32,
+  cols: i32,
+  cells: Vec<u8>,
+  owners: Vec<usize>,
+}
+
+/// Bit of each dot in a braille pattern, by `[x][y]` within the cell.
+const BRAILLE: [[u8; 4]; 2] = [[0x01, 0x02, 0x04, 0x40], [0x08, 0x10, 0x20, 0x80]];
+
+/// The part of the segment from `a` to `b` inside `0..=right` x
+/// `0..=bottom` (Liang-Barsky), or `None` if it misses it.
+fn clip_segment(a: (f64, f64), b: (f64, f64), right: f64, bottom: f64) -> Option<((f64, f64), (f64, f64))> {
+  let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+  if !(dx.is_finite() && dy.is_finite()) {
+    return None;
+  }
+  let (mut t0, mut t1) = (0.0f64, 1.0f64);
+  for &(p, q) in &[(-dx, a.0), (dx, right - a.0), (-dy, a.1), (dy, bottom - a.1)] {
+    if p == 0.0 {
+      if q < 0.0 {
+        return None;
+      }
+      continue;
+    }
+    let r = q / p;
+    if p < 0.0 {
+      t0 = t0.max(r);
+    } else {
+      t1 = t1.min(r);
+    }
+    if t0 > t1 {
+      return None;
+    }
+  }
+  Some(((a.0 + t0 * dx, a.1 + t0 * dy), (a.0 + t1 * dx, a.1 + t1 * dy)))
+}
+
+impl Dots {
+  /// Dots for an area of `lines` x `cols` cells.
+  pub fn new(lines: i32, cols: i32) -> Dots {
+    let (lines, cols) = (max(0, lines), max(0, cols));
+    let n = (lines * cols) as usize;
+    Dots { lines, cols, cells: vec![0; n], owners: vec![0; n] }
+  }
+
+  /// `(width, height)` in dots.
+  pub fn resolution(&self) -> (i32, i32) {
+    (self.cols * 2, self.lines * 4)
+  }
+
+  /// Sets the dot at `(x, y)`, counted from the top left; dots outside
+  /// the grid are ignored.
+  pub fn set(&mut self, x: i32, y: i32, series: usize) {
+    let (w, h) = self.resolution();
+    if x < 0 || y < 0 || x >= w || y >= h {
+      return;
+    }
+    let i = ((y / 4) * self.cols + x / 2) as usize;
+    self.cells[i] |= BRAILLE[(x % 2) as usize][(y % 4) as usize];
+    self.owners[i] = series;
+  }
+
+  /// A straight line of dots from `(x0, y0)` to `(x1, y1)`, clipped to the
+  /// grid.
+  pub fn line(&mut self, x0: i32, y0: i32, x1: i32, y1: i32, series: usize) {
+    self.line_between((x0 as f64, y0 as f64), (x1 as f64, y1 as f64), series);
+  }
+
+  /// `line` between dot coordinates that need not be whole or anywhere
+  /// near the grid; only the part inside it is walked.
+  fn line_between(&mut self, from: (f64, f64), to: (f64, f64), series: usize) {
+    let (w, h) = self.resolution();
+    let ((x0, y0), (x1, y1)) = match clip_segment(from, to, (w - 1) as f64, (h - 1) as f64) {
+      Some(clipped) => clipped,
+      None => return,
+    };
+    let (x0, y0, x1, y1) = (x0.round() as i64, y0.round() as i64, x1.round() as i64, y1.round() as i64);
+    let (dx, dy) = ((x1 - x0).abs(), -(y1 - y0).abs());
+    let (sx, sy) = (if x0 < x1 { 1 } else { -1 }, if y0 < y1 { 1 } else { -1 });
+    let (mut x, mut y, mut err) = (x0, y0, dx + dy);
+    loop {
+      self.set(x as i32, y as i32, series);
+      if x == x1 && y == y1 {
+        break;
+      }
+      let e2 = 2 * err;
+      if e2 >= dy {
+        err += dy;
+        x += sx;
+      }
+      if e2 <= dx {
+        err += dx;
+        y += sy;
+      }
+    }
+  }
+
+  /// Draws every cell with a dot in it at `(y, x)` onwards, in the style
+  /// of the series that owns it. Empty cells are left alone.
+  pub fn draw(&self, w: &Window, y: i32, x: i32, styles: &[Style], glyphs: &Glyphs) -> i32 {
+    let mut ret = OK;
+    for row in 0..self.lines {
+      for col in 0..self.cols {
+        let i = (row * self.cols + col) as usize;
+        if self.cells[i] == 0 {
+          continue;
+        }
+        let g = if unicode(glyphs) {
+          Glyph::Char(::std::char::from_u32(0x2800 + self.cells[i] as u32).unwrap_or(' '))
+        } else {
+          Glyph::Char('*')
+        };
+        let style = styles.get(self.owners[i]).cloned().unwrap_or_default();
+        ret |= with_style(w, style, || g.mvput(w, y + row, x + col));
+      }
+    }
+    if ret == OK { OK } else { ERR }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SeriesKind {
+  /// Points joined by lines.
+  Line,
+  /// Points on their own.
+  Scatter,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Series {
+  pub points: Vec<(f64, f64)>,
+  pub kind: SeriesKind,
+  pub style: Style,
+}
+
+impl Series {
+  pub fn line(points: Vec<(f64, f64)>, style: Style) -> Series {
+    Series { points, kind: SeriesKind::Line, style }
+  }
+
+  pub fn scatter(points: Vec<(f64, f64)>, style: Style) -> Series {
+    Series { points, kind: SeriesKind::Scatter, style }
+  }
+}
+
+/// `points` as dot coordinates on a `(width, height)` grid spanning the
+/// given ranges, y growing downwards. Points outside fixed bounds land far
+/// off the grid; lines to them are clipped, and `as i32` saturates for the
+/// rest. Points that are not finite, or overflow on the way, are left out.
+fn to_dots(points: &[(f64, f64)], (x0, x1): (f64, f64), (y0, y1): (f64, f64), (dw, dh): (i32, i32)) -> Vec<(f64, f64)> {
+  points.iter()
+    .filter(|p| p.0.is_finite() && p.1.is_finite())
+    .map(|&(x, y)| {
+      let dx = (x - x0) / (x1 - x0) * (dw - 1) as f64;
+      let dy = (1.0 - (y - y0) / (y1 - y0)) * (dh - 1) as f64;
+      (dx, dy)
+    })
+    .filter(|p| !p.0.is_nan() && !p.1.is_nan())
+    .collect()
+}
+
+/// Line and scatter series on shared axes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Plot {
+  series: Vec<Series>,
+  x_bounds: Option<(f64, f64)>,
+  y_bounds: Option<(f64, f64)>,
+  glyphs: Glyphs,
+}
+
+impl Plot {
+  pub fn new(series: Vec<Series>) -> Plot {
+    Plot { series, x_bounds: None, y_bounds: None, glyphs: Glyphs::current() }
+  }
+
+  /// Fixed x range; by default it fits the data. Equal ends are widened,
+  /// and a range with an end that is not finite is ignored.
+  pub fn x_bounds(mut self, lo: f64, hi: f64) -> Plot {
+    self.x_bounds = Some((lo, hi));
+    self
+  }
+
+  /// Fixed y range, taken as `x_bounds` takes the x range.
+  pub fn y_bounds(mut self, lo: f64, hi: f64) -> Plot {
+    self.y_bounds = Some((lo, hi));
+    self
+  }
+
+  pub fn glyphs(mut self, glyphs: Glyphs) -> Plot {
+    self.glyphs = glyphs;
+    self
+  }
+
+  /// Draws into `area`, which is cleared first: y labels on the left, x
+  /// labels underneath, and the series in between.
+  pub fn draw(&self, w: &Window, area: Rect) -> i32 {
+    if area.lines < 3 || area.cols < 4 {
+      return ERR;
+    }
+    let all = || self.series.iter().flat_map(|s| s.points.iter());
+    let (x0, x1) = axis_range(self.x_bounds, all().map(|p| p.0));
+    let (y0, y1) = axis_range(self.y_bounds, all().map(|p| p.1));
+
+    let y_labels = [format_value(y1), format_value(y0)];
+    let axis_x = area.x + y_labels.iter().map(|l| l.len() as i32).max().unwrap_or(1);
+    let base_y = area.bottom() - 2;
+    let plot = Rect::new(area.y, axis_x + 1, base_y - area.y, area.right() - axis_x - 1);
+
+    for y in area.y..area.bottom() {
+      w.put_str_padded(y, area.x, "", area.cols, Align::Left).ok();
+    }
+    w.put_str_clipped(area.y, area.x, &y_labels[0], axis_x - area.x).ok();
+    w.put_str_clipped(base_y - 1, area.x, &y_labels[1], axis_x - area.x).ok();
+    draw_axes(w, &self.glyphs, axis_x, area.y, base_y, area.right());
+    let x_hi = format_value(x1);
+    w.put_str_clipped(base_y + 1, plot.x, &format_value(x0), plot.cols).ok();
+    w.put_str_clipped(base_y + 1, plot.right() - x_hi.len() as i32, &x_hi, x_hi.len() as i32).ok();
+
+    let mut dots = Dots::new(plot.lines, plot.cols);
+    let (dw, dh) = dots.resolution();
+    for (i, s) in self.series.iter().enumerate() {
+      let points = to_dots(&s.points, (x0, x1), (y0, y1), (dw, dh));
+      match s.kind {
+        SeriesKind::Scatter => points.iter().for_each(|&(x, y)| dots.set(x.round() as i32, y.round() as i32, i)),
+        SeriesKind::Line => {
+          for pair in points.windows(2) {
+            dots.line_between(pair[0], pair[1], i);
+          }
+          if points.len() == 1 {
+            dots.set(points[0].0.round() as i32, points[0].1.round() as i32, i);
+          }
+        }
+      }
+    }
+    let styles: Vec<Style> = self.series.iter().map(|s| s.style).collect();
+    dots.draw(w, plot.y, plot.x, &styles, &self.glyphs)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use glyphs::BoxStyle;
+  use testing::with_screen;
+
+  #[test]
+  fn axis_ranges() {
+    let none = || ::std::iter::empty();
+    assert_eq!(axis_range(Some((5.0, 5.0)), none()), (4.0, 6.0));
+    assert_eq!(axis_range(Some((3.0, 1.0)), none()), (3.0, 1.0));
+    let (lo, hi) = axis_range(Some((1e20, 1e20)), none());
+    assert!(lo < 1e20 && hi > 1e20);
+    assert_eq!(axis_range(Some((::std::f64::NAN, 1.0)), vec![2.0, 3.0].into_iter()), (2.0, 3.0));
+    assert_eq!(axis_range(Some((0.0, ::std::f64::INFINITY)), vec![2.0, 2.0].into_iter()), (1.0, 3.0));
+    assert_eq!(axis_range(None, vec![::std::f64::NAN, 7.0].into_iter()), (6.0, 8.0));
+    assert_eq!(axis_range(None, none()), (0.0, 1.0));
+  }
+
+  #[test]
+  fn to_dots_skips_what_cannot_be_placed() {
+    let points = [(0.0, 5.0), (::std::f64::NAN, 5.0), (1.0, ::std::f64::INFINITY), (1.0, 5.0)];
+    assert_eq!(to_dots(&points, (0.0, 1.0), (4.0, 6.0), (11, 9)), vec![(0.0, 4.0), (10.0, 4.0)]);
+    let huge = [(1e308, 0.0), (0.0, 0.0)];
+    assert_eq!(to_dots(&huge, (-1e308, 1e308), (0.0, 1.0), (3, 3)), vec![(0.0, 2.0)]);
+  }
+
+  #[test]
+  fn degenerate_bounds_plot_in_the_middle() {
+    with_screen(|curses| {
+      let w = curses.new_window(6, 12, 0, 0).unwrap();
+      let series = Series::scatter(vec![(0.0, 5.0), (1.0, 5.0), (::std::f64::NAN, 0.0)], Style::new());
+      let plot = Plot::new(vec![series]).x_bounds(0.0, 1.0).y_bounds(5.0, 5.0)
+        .glyphs(Glyphs::new(GlyphSet::Ascii, BoxStyle::Light));
+      assert_eq!(plot.draw(&w, Rect::new(0, 0, 6, 12)), OK);
+      let mut stars = Vec::new();
+      for y in 0..6 {
+        for x in 0..12 {
+          if ::mvwinch(w.raw(), y, x) & A_CHARTEXT == '*' as ::ll::chtype {
+            stars.push((y, x));
+          }
+        }
+      }
+      assert_eq!(stars, vec![(2, 2), (2, 11)]);
+    });
+  }
+}
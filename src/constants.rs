@@ -15,8 +15,10 @@ pub use libc::{LC_ALL, LC_COLLATE, LC_CTYPE, LC_MONETARY, LC_NUMERIC, LC_TIME, L
 
 use super::ll::*;
 
+#[cfg(not(ncurses_reentrant))]
 mod wrapped {
     use libc::{ c_char, c_int };
+    #[cfg(not(curses_backend = "netbsd"))]
     use ll::chtype;
     use ll::WINDOW;
 
@@ -29,15 +31,50 @@ mod wrapped {
         pub static COLORS: c_int;
         pub static COLOR_PAIRS: c_int;
         pub static COLS: c_int;
+        #[cfg(ncurses_escdelay)]
         pub static ESCDELAY: c_int;
         pub static LINES: c_int;
         pub static TABSIZE: c_int;
 
-        /* Line graphics */
+        /* Line graphics; a pointer rather than an array in PDCurses, and
+           not exported at all by NetBSD curses. */
+        #[cfg(curses_backend = "ncurses")]
         pub static mut acs_map: [chtype; 0];
+        #[cfg(curses_backend = "pdcursesmod_vt")]
+        pub static acs_map: *mut chtype;
     }
 }
 
+/*
+ * Thread-aware ncurses (ncursest) does not export its globals; they are
+ * reached through per-screen accessor functions instead.
+ */
+#[cfg(ncurses_reentrant)]
+mod wrapped {
+    use libc::{ c_char, c_int };
+    use ll::chtype;
+    use ll::WINDOW;
+
+    extern "C"
+    {
+        #[link_name = "_nc_curscr"] pub fn curscr() -> WINDOW;
+        #[link_name = "_nc_newscr"] pub fn newscr() -> WINDOW;
+        #[link_name = "_nc_stdscr"] pub fn stdscr() -> WINDOW;
+        #[link_name = "_nc_ttytype"] pub fn ttytype() -> *mut c_char;
+        #[link_name = "_nc_COLORS"] pub fn COLORS() -> c_int;
+        #[link_name = "_nc_COLOR_PAIRS"] pub fn COLOR_PAIRS() -> c_int;
+        #[link_name = "_nc_COLS"] pub fn COLS() -> c_int;
+        #[cfg(ncurses_escdelay)]
+        #[link_name = "_nc_ESCDELAY"] pub fn ESCDELAY() -> c_int;
+        #[link_name = "_nc_LINES"] pub fn LINES() -> c_int;
+        #[link_name = "_nc_TABSIZE"] pub fn TABSIZE() -> c_int;
+
+        /* Line graphics */
+        #[link_name = "_nc_acs_map"] pub fn acs_map() -> *mut chtype;
+    }
+}
+
+#[cfg(not(ncurses_reentrant))]
 macro_rules! wrap_extern {
     ($name:ident: $t:ty) => {
         pub fn $name() -> $t {
@@ -46,6 +83,15 @@ macro_rules! wrap_extern {
     }
 }
 
+#[cfg(ncurses_reentrant)]
+macro_rules! wrap_extern {
+    ($name:ident: $t:ty) => {
+        pub fn $name() -> $t {
+            unsafe { wrapped::$name() }
+        }
+    }
+}
+
 wrap_extern!(curscr: WINDOW);
 wrap_extern!(newscr: WINDOW);
 wrap_extern!(stdscr: WINDOW);
@@ -53,14 +99,28 @@ wrap_extern!(ttytype: *mut c_char);
 wrap_extern!(COLORS: c_int);
 wrap_extern!(COLOR_PAIRS: c_int);
 wrap_extern!(COLS: c_int);
+#[cfg(ncurses_escdelay)]
 wrap_extern!(ESCDELAY: c_int);
 wrap_extern!(LINES: c_int);
 wrap_extern!(TABSIZE: c_int);
+#[cfg(all(not(ncurses_reentrant), curses_backend = "ncurses"))]
 pub fn acs_map() -> *const chtype {
     unsafe {
         &wrapped::acs_map as *const chtype
     }
 }
+#[cfg(curses_backend = "pdcursesmod_vt")]
+pub fn acs_map() -> *const chtype {
+    unsafe {
+        wrapped::acs_map as *const chtype
+    }
+}
+#[cfg(ncurses_reentrant)]
+pub fn acs_map() -> *const chtype {
+    unsafe {
+        wrapped::acs_map() as *const chtype
+    }
+}
 
 include!(concat!(env!("OUT_DIR"), "/raw_constants.rs"));
 
@@ -0,0 +1,335 @@
+/*
+    Serialised access to curses.
+
+    ncurses keeps its state in globals and is not safe to call from more
+    than one thread at a time. A `Curses` token holds a process-wide lock
+    and is neither `Send` nor `Sync`, so a `Window` borrowed from it cannot
+    leave the thread that drew into it. Drawing from two threads at once
+    therefore fails to compile instead of corrupting the screen.
+*/
+
+use std::cell::Cell;
+use std::ffi::NulError;
+use std::marker::PhantomData;
+use std::sync::{Mutex, MutexGuard, TryLockError};
+
+use ll::{WINDOW, chtype, attr_t};
+#[cfg(feature = "threaded")]
+use ll::SCREEN;
+use constants::{LINES, COLS, stdscr};
+
+static CURSES_LOCK: Mutex<()> = Mutex::new(());
+
+thread_local! {
+  /// Whether this thread holds the token, so that taking it twice panics
+  /// rather than deadlocking on the mutex.
+  static HELD: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Proof that the calling thread holds the global curses lock.
+#[derive(Debug)]
+pub struct Curses {
+  _guard: MutexGuard<'static, ()>,
+  _not_send: PhantomData<*mut ()>,
+}
+
+impl Curses {
+  /// Blocks until no other thread holds a `Curses` token.
+  ///
+  /// The lock is not reentrant: panics if this thread already holds a
+  /// token, including the one inside a live `Session`. Borrow that one
+  /// instead.
+  pub fn lock() -> Curses {
+    if HELD.with(|h| h.get()) {
+      panic!("Curses::lock: this thread already holds the curses token \
+              (a live Session holds one too); use that token instead");
+    }
+    // A panic while drawing leaves the screen in an unknown state, but
+    // the lock itself protects no Rust data, so poisoning is ignored.
+    let guard = CURSES_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    Curses::held(guard)
+  }
+
+  /// Returns `None` if another thread currently holds the token, or this
+  /// one does.
+  pub fn try_lock() -> Option<Curses> {
+    if HELD.with(|h| h.get()) {
+      return None;
+    }
+    match CURSES_LOCK.try_lock() {
+      Ok(guard) => Some(Curses::held(guard)),
+      Err(TryLockError::Poisoned(e)) => Some(Curses::held(e.into_inner())),
+      Err(TryLockError::WouldBlock) => None,
+    }
+  }
+
+  fn held(guard: MutexGuard<'static, ()>) -> Curses {
+    HELD.with(|h| h.set(true));
+    Curses { _guard: guard, _not_send: PhantomData }
+  }
+
+  pub fn stdscr(&self) -> Window<'_> {
+    Window::borrowed(stdscr())
+  }
+
+  /// Wraps a window created elsewhere. It is not deleted on drop.
+  pub fn borrow_window(&self, w: WINDOW) -> Window<'_> {
+    Window::borrowed(w)
+  }
+
+  pub fn new_window(&self, lines: i32, cols: i32, y: i32, x: i32) -> Option<Window<'_>> {
+    Window::owned(::newwin(lines, cols, y, x))
+  }
+
+  pub fn new_pad(&self, lines: i32, cols: i32) -> Option<Window<'_>> {
+    Window::owned(::newpad(lines, cols))
+  }
+
+  pub fn lines(&self) -> i32 {
+    LINES()
+  }
+
+  pub fn cols(&self) -> i32 {
+    COLS()
+  }
+
+  pub fn refresh(&self) -> i32 {
+    ::refresh()
+  }
+
+  pub fn doupdate(&self) -> i32 {
+    ::doupdate()
+  }
+
+  /// Runs `f` while ncurses holds its own per-window lock.
+  #[cfg(feature = "threaded")]
+  pub fn use_window<'c, F, R>(&'c self, w: &Window<'c>, f: F) -> R
+    where F: FnOnce(&Window<'c>) -> R
+  {
+    let mut ret = None;
+    ::use_window(w.raw(), |_| {
+      ret = Some(f(w));
+      ::constants::OK
+    });
+    ret.expect("use_window did not run its callback")
+  }
+
+  /// Runs `f` while ncurses holds its own per-screen lock.
+  #[cfg(feature = "threaded")]
+  pub fn use_screen<F, R>(&self, s: SCREEN, f: F) -> R
+    where F: FnOnce(&Curses) -> R
+  {
+    let mut ret = None;
+    ::use_screen(s, |_| {
+      ret = Some(f(self));
+      ::constants::OK
+    });
+    ret.expect("use_screen did not run its callback")
+  }
+}
+
+impl Drop for Curses {
+  fn drop(&mut self) {
+    HELD.with(|h| h.set(false));
+  }
+}
+
+/// A curses window that can only be drawn into while its `Curses` token is alive.
+#[derive(Debug)]
+pub struct Window<'c> {
+  raw: WINDOW,
+  owned: bool,
+  _curses: PhantomData<&'c Curses>,
+}
+
+impl<'c> Window<'c> {
+  fn owned(raw: WINDOW) -> Option<Window<'c>> {
+    if raw.is_null() {
+      None
+    } else {
+      Some(Window { raw, owned: true, _curses: PhantomData })
+    }
+  }
+
+  fn borrowed(raw: WINDOW) -> Window<'c> {
+    Window { raw, owned: false, _curses: PhantomData }
+  }
+
+  pub fn raw(&self) -> WINDOW {
+    self.raw
+  }
+
+  /// Gives up ownership; the caller becomes responsible for `delwin`.
+  pub fn into_raw(mut self) -> WINDOW {
+    self.owned = false;
+    self.raw
+  }
+
+  /// Creates a subwindow relative to this one. The subwindow shares this
+  /// window's cells, so it borrows it: it has to be dropped, and deleted,
+  /// before this one can be.
+  ///
+  /// ```compile_fail
+  /// # let curses = ncurses::Curses::lock();
+  /// let parent = curses.new_window(10, 10, 0, 0).unwrap();
+  /// let child = parent.derwin(5, 5, 1, 1).unwrap();
+  /// drop(parent);
+  /// child.erase();
+  /// ```
+  pub fn derwin<'p>(&'p self, lines: i32, cols: i32, y: i32, x: i32) -> Option<Window<'p>> {
+    Window::owned(::derwin(self.raw, lines, cols, y, x))
+  }
+
+  /// Creates a subpad; only valid on pads. Borrows the pad like `derwin`.
+  pub fn subpad<'p>(&'p self, lines: i32, cols: i32, y: i32, x: i32) -> Option<Window<'p>> {
+    Window::owned(::subpad(self.raw, lines, cols, y, x))
+  }
+
+  /// `(lines, cols)`
+  pub fn size(&self) -> (i32, i32) {
+    (::getmaxy(self.raw), ::getmaxx(self.raw))
+  }
+
+  /// Screen position of the top-left corner as `(y, x)`.
+  pub fn origin(&self) -> (i32, i32) {
+    (::getbegy(self.raw), ::getbegx(self.raw))
+  }
+
+  /// Cursor position as `(y, x)`.
+  pub fn cursor(&self) -> (i32, i32) {
+    (::getcury(self.raw), ::getcurx(self.raw))
+  }
+
+  pub fn mv(&self, y: i32, x: i32) -> i32 {
+    ::wmove(self.raw, y, x)
+  }
+
+  pub fn mvwin(&self, y: i32, x: i32) -> i32 {
+    ::mvwin(self.raw, y, x)
+  }
+
+  pub fn resize(&self, lines: i32, cols: i32) -> i32 {
+    ::wresize(self.raw, lines, cols)
+  }
+
+  pub fn addch(&self, ch: chtype) -> i32 {
+    ::waddch(self.raw, ch)
+  }
+
+  pub fn mvaddch(&self, y: i32, x: i32, ch: chtype) -> i32 {
+    ::mvwaddch(self.raw, y, x, ch)
+  }
+
+  pub fn addstr(&self, s: &str) -> Result<i32, NulError> {
+    ::waddstr(self.raw, s)
+  }
+
+  pub fn mvaddstr(&self, y: i32, x: i32, s: &str) -> Result<i32, NulError> {
+    ::mvwaddstr(self.raw, y, x, s)
+  }
+
+  pub fn addnstr(&self, s: &str, n: i32) -> Result<i32, NulError> {
+    ::waddnstr(self.raw, s, n)
+  }
+
+  pub fn mvaddnstr(&self, y: i32, x: i32, s: &str, n: i32) -> Result<i32, NulError> {
+    ::mvwaddnstr(self.raw, y, x, s, n)
+  }
+
+  pub fn attron(&self, a: attr_t) -> i32 {
+    ::wattron(self.raw, a)
+  }
+
+  pub fn attroff(&self, a: attr_t) -> i32 {
+    ::wattroff(self.raw, a)
+  }
+
+  pub fn attrset(&self, a: attr_t) -> i32 {
+    ::wattrset(self.raw, a)
+  }
+
+  pub fn attr_set(&self, a: attr_t, pair: i16) -> i32 {
+    ::wattr_set(self.raw, a, pair)
+  }
+
+  pub fn bkgd(&self, ch: chtype) -> i32 {
+    ::wbkgd(self.raw, ch)
+  }
+
+  #[allow(clippy::too_many_arguments)]
+  pub fn border(&self, ls: chtype, rs: chtype, ts: chtype, bs: chtype, tl: chtype, tr: chtype, bl: chtype, br: chtype) -> i32 {
+    ::wborder(self.raw, ls, rs, ts, bs, tl, tr, bl, br)
+  }
+
+  pub fn draw_box(&self, v: chtype, h: chtype) -> i32 {
+    ::box_(self.raw, v, h)
+  }
+
+  pub fn mvhline(&self, y: i32, x: i32, ch: chtype, n: i32) -> i32 {
+    ::mvwhline(self.raw, y, x, ch, n)
+  }
+
+  pub fn mvvline(&self, y: i32, x: i32, ch: chtype, n: i32) -> i32 {
+    ::mvwvline(self.raw, y, x, ch, n)
+  }
+
+  pub fn mvinch(&self, y: i32, x: i32) -> chtype {
+    ::mvwinch(self.raw, y, x)
+  }
+
+  pub fn erase(&self) -> i32 {
+    ::werase(self.raw)
+  }
+
+  pub fn clear(&self) -> i32 {
+    ::wclear(self.raw)
+  }
+
+  pub fn clrtoeol(&self) -> i32 {
+    ::wclrtoeol(self.raw)
+  }
+
+  pub fn clrtobot(&self) -> i32 {
+    ::wclrtobot(self.raw)
+  }
+
+  pub fn touch(&self) -> i32 {
+    ::touchwin(self.raw)
+  }
+
+  pub fn refresh(&self) -> i32 {
+    ::wrefresh(self.raw)
+  }
+
+  pub fn noutrefresh(&self) -> i32 {
+    ::wnoutrefresh(self.raw)
+  }
+
+  pub fn keypad(&self, bf: bool) -> i32 {
+    ::keypad(self.raw, bf)
+  }
+
+  pub fn nodelay(&self, bf: bool) -> i32 {
+    ::nodelay(self.raw, bf)
+  }
+
+  pub fn scrollok(&self, bf: bool) -> i32 {
+    ::scrollok(self.raw, bf)
+  }
+
+  pub fn timeout(&self, delay: i32) {
+    ::wtimeout(self.raw, delay)
+  }
+
+  pub fn getch(&self) -> i32 {
+    ::wgetch(self.raw)
+  }
+}
+
+impl<'c> Drop for Window<'c> {
+  fn drop(&mut self) {
+    if self.owned {
+      ::delwin(self.raw);
+    }
+  }
+}
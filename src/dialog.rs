@@ -0,0 +1,322 @@
+/*
+    Modal dialogs: alert, confirm, input and choose.
+
+    Each helper opens a bordered window centred on the screen, runs its own
+    `getch` loop until the user answers, and puts back what it covered.
+    With the `panel` feature the window sits in a new top panel, and
+    closing it is `del_panel` plus `update_panels`. Without panels, the
+    covered part of the screen is copied out of `curscr` when the dialog
+    opens and drawn back when it closes; the windows underneath are never
+    touched either way.
+
+    A `KEY_RESIZE` that arrives while a dialog is open recentres it and is
+    pushed back with `ungetch` on close, so the application still sees it.
+*/
+
+use std::cmp::{max, min};
+
+use curses::{Curses, Window};
+use rect::Rect;
+use constants::*;
+use glyphs::Glyphs;
+use reflow::Reflow;
+use styled::Style;
+use width::{str_width, truncate};
+use widget::{List, ListSource, Response};
+use CURSOR_VISIBILITY;
+#[cfg(feature = "panel")]
+use panel::wrapper::PANEL;
+
+const ESC: i32 = 0x1B;
+
+/// A bordered window floating over the screen.
+#[derive(Debug)]
+pub struct Dialog<'c> {
+  window: Window<'c>,
+  title: String,
+  #[cfg(feature = "panel")]
+  panel: PANEL,
+  #[cfg(not(feature = "panel"))]
+  saved: Option<Window<'c>>,
+  /// The size asked for, which a resize recentres to as far as it fits.
+  size: (i32, i32),
+  resized: bool,
+}
+
+fn centred(lines: i32, cols: i32) -> Rect {
+  let screen = Rect::screen();
+  let (lines, cols) = (min(lines, screen.lines), min(cols, screen.cols));
+  Rect::new((screen.lines - lines) / 2, (screen.cols - cols) / 2, max(1, lines), max(1, cols))
+}
+
+impl<'c> Dialog<'c> {
+  /// Opens an empty dialog of `lines` x `cols`, border included, shrunk
+  /// to fit the screen if need be.
+  pub fn open(curses: &'c Curses, title: &str, lines: i32, cols: i32) -> Option<Dialog<'c>> {
+    let r = centred(lines, cols);
+    let window = curses.new_window_at(r)?;
+    window.keypad(true);
+
+    #[cfg(feature = "panel")]
+    let d = Dialog { panel: ::new_panel(window.raw()), window, title: title.to_string(), size: (lines, cols), resized: false };
+    #[cfg(not(feature = "panel"))]
+    let d = {
+      let saved = curses.new_window_at(r);
+      if let Some(ref s) = saved {
+        ::copywin(::curscr(), s.raw(), r.y, r.x, 0, 0, r.lines - 1, r.cols - 1, 0);
+      }
+      Dialog { saved, window, title: title.to_string(), size: (lines, cols), resized: false }
+    };
+
+    d.frame();
+    Some(d)
+  }
+
+  pub fn window(&self) -> &Window<'c> {
+    &self.window
+  }
+
+  /// The area inside the border, in window coordinates.
+  pub fn inner(&self) -> Rect {
+    let (lines, cols) = self.window.size();
+    Rect::new(1, 1, lines - 2, cols - 2)
+  }
+
+  /// Clears the window and draws the border and title.
+  pub fn frame(&self) {
+    self.window.erase();
+    Glyphs::current().border(&self.window);
+    if !self.title.is_empty() {
+      let (_, cols) = self.window.size();
+      let (title, _) = truncate(&self.title, max(0, cols - 6) as usize);
+      self.window.mvaddstr(0, 2, &format!(" {} ", title)).ok();
+    }
+  }
+
+  /// Shows the dialog on screen.
+  pub fn show(&self) -> i32 {
+    #[cfg(feature = "panel")]
+    {
+      ::update_panels();
+      ::doupdate()
+    }
+    #[cfg(not(feature = "panel"))]
+    {
+      self.window.refresh()
+    }
+  }
+
+  /// Reads a key. `KEY_RESIZE` recentres the dialog and is remembered for
+  /// close; the caller should redraw the contents when it gets one.
+  pub fn getch(&mut self) -> i32 {
+    let key = self.window.getch();
+    if key == KEY_RESIZE {
+      self.resized = true;
+      let r = centred(self.size.0, self.size.1);
+      self.window.resize(r.lines, r.cols);
+      #[cfg(feature = "panel")]
+      ::move_panel(self.panel, r.y, r.x);
+      #[cfg(not(feature = "panel"))]
+      {
+        // What was saved no longer matches the screen; the application
+        // repaints after the resize anyway.
+        self.saved = None;
+        self.window.mvwin(r.y, r.x);
+      }
+      self.frame();
+    }
+    key
+  }
+}
+
+impl<'c> Drop for Dialog<'c> {
+  fn drop(&mut self) {
+    #[cfg(feature = "panel")]
+    {
+      ::del_panel(self.panel);
+      ::update_panels();
+      ::doupdate();
+    }
+    #[cfg(not(feature = "panel"))]
+    {
+      if let Some(ref saved) = self.saved {
+        saved.touch();
+        saved.refresh();
+      }
+    }
+    if self.resized {
+      ::ungetch(KEY_RESIZE);
+    }
+  }
+}
+
+/// Message text wrapped for a dialog, and the width it needs.
+fn wrap_message(message: &str) -> (Vec<String>, i32) {
+  let width = (COLS() - 8).clamp(10, 60) as usize;
+  let reflow = Reflow::new(width);
+  let lines: Vec<String> = reflow.lines(message).iter().map(|l| reflow.format(message, l)).collect();
+  let w = lines.iter().map(|l| str_width(l)).max().unwrap_or(0);
+  (lines, w as i32)
+}
+
+fn buttons_width(buttons: &[&str]) -> i32 {
+  if buttons.is_empty() {
+    return 0;
+  }
+  buttons.iter().map(|b| str_width(b) as i32 + 4).sum::<i32>() + 2 * (buttons.len() as i32 - 1)
+}
+
+fn draw_buttons(d: &Dialog, buttons: &[&str], current: usize) {
+  let inner = d.inner();
+  let y = inner.bottom() - 1;
+  let mut x = inner.x + max(0, inner.cols - buttons_width(buttons)) / 2;
+  for (i, b) in buttons.iter().enumerate() {
+    let label = format!("< {} >", b);
+    if i == current {
+      d.window.attron(A_REVERSE);
+    }
+    d.window.put_str_clipped(y, x, &label, inner.right() - x).ok();
+    d.window.attroff(A_REVERSE);
+    x += str_width(&label) as i32 + 2;
+  }
+}
+
+/// Shows `message` with a row of buttons and returns the index of the one
+/// chosen, or `None` on Escape. Arrows and Tab move between buttons, Enter
+/// picks one, and so does typing its first letter. Without buttons, an
+/// "OK" is shown, and is index 0.
+pub fn message(curses: &Curses, title: &str, message: &str, buttons: &[&str], default: usize) -> Option<usize> {
+  let buttons = if buttons.is_empty() { &["OK"][..] } else { buttons };
+  let (text, text_w) = wrap_message(message);
+  let cols = max(max(text_w, buttons_width(buttons)), str_width(title) as i32 + 4) + 4;
+  let lines = text.len() as i32 + 4;
+  let mut d = Dialog::open(curses, title, lines, cols)?;
+  let mut current = min(default, buttons.len().saturating_sub(1));
+  let old_cursor = ::curs_set(CURSOR_VISIBILITY::CURSOR_INVISIBLE);
+
+  let result = loop {
+    let inner = d.inner();
+    for (i, line) in text.iter().enumerate().take(max(0, inner.lines - 2) as usize) {
+      d.window.put_str_clipped(inner.y + i as i32, inner.x + 1, line, inner.cols - 2).ok();
+    }
+    draw_buttons(&d, buttons, current);
+    d.show();
+
+    match d.getch() {
+      KEY_LEFT | KEY_BTAB => current = (current + buttons.len() - 1) % buttons.len(),
+      KEY_RIGHT | 0x09 => current = (current + 1) % buttons.len(),
+      KEY_ENTER | 0x0A | 0x0D => break Some(current),
+      ESC => break None,
+      key if (0x20..0x7F).contains(&key) => {
+        let c = (key as u8 as char).to_ascii_lowercase();
+        if let Some(i) = buttons.iter().position(|b| b.chars().next().map(|f| f.to_ascii_lowercase()) == Some(c)) {
+          break Some(i);
+        }
+      }
+      _ => {}
+    }
+  };
+  if let Some(v) = old_cursor {
+    ::curs_set(v);
+  }
+  result
+}
+
+/// Shows `text` until it is dismissed.
+pub fn alert(curses: &Curses, title: &str, text: &str) {
+  message(curses, title, text, &["OK"], 0);
+}
+
+/// Asks a yes/no question; Escape counts as no.
+pub fn confirm(curses: &Curses, title: &str, question: &str) -> bool {
+  message(curses, title, question, &["Yes", "No"], 0) == Some(0)
+}
+
+/// Asks for a line of text, starting from `initial`. Returns `None` on
+/// Escape.
+pub fn input(curses: &Curses, title: &str, prompt: &str, initial: &str) -> Option<String> {
+  let (text, text_w) = wrap_message(prompt);
+  let cols = max(max(text_w, 30), str_width(title) as i32 + 4) + 4;
+  let lines = text.len() as i32 + 4;
+  let mut d = Dialog::open(curses, title, lines, cols)?;
+  let old_cursor = ::curs_set(CURSOR_VISIBILITY::CURSOR_VISIBLE);
+
+  let mut value = initial.to_string();
+  // Bytes of a UTF-8 sequence still being typed; `getch` returns them
+  // one at a time.
+  let mut pending: Vec<u8> = Vec::new();
+
+  let result = loop {
+    let inner = d.inner();
+    for (i, line) in text.iter().enumerate() {
+      d.window.put_str_clipped(inner.y + i as i32, inner.x + 1, line, inner.cols - 2).ok();
+    }
+    // Show the end of the value if it is wider than the field.
+    let field = max(1, inner.cols - 2);
+    let y = inner.bottom() - 1;
+    let mut shown = &value[..];
+    while str_width(shown) as i32 >= field {
+      let mut chars = shown.chars();
+      chars.next();
+      shown = chars.as_str();
+    }
+    d.window.attron(A_UNDERLINE);
+    d.window.put_str_padded(y, inner.x + 1, shown, field, ::styled::Align::Left).ok();
+    d.window.attroff(A_UNDERLINE);
+    d.window.mv(y, inner.x + 1 + str_width(shown) as i32);
+    d.show();
+
+    match d.getch() {
+      KEY_ENTER | 0x0A | 0x0D => break Some(value),
+      ESC => break None,
+      KEY_BACKSPACE | 0x08 | 0x7F => {
+        pending.clear();
+        value.pop();
+      }
+      key if (0x20..0x100).contains(&key) && key != 0x7F => {
+        pending.push(key as u8);
+        match ::std::str::from_utf8(&pending) {
+          Ok(s) => {
+            value.push_str(s);
+            pending.clear();
+          }
+          Err(e) if e.error_len().is_some() => pending.clear(),
+          Err(_) => {}
+        }
+      }
+      _ => {}
+    }
+  };
+  if let Some(v) = old_cursor {
+    ::curs_set(v);
+  }
+  result
+}
+
+/// Lets the user pick one of `items` from a scrolling list. Returns its
+/// index, or `None` on Escape.
+pub fn choose<S: ListSource>(curses: &Curses, title: &str, items: S) -> Option<usize> {
+  let widest = (0..min(items.len(), 1000)).map(|i| str_width(&items.label(i)) as i32).max().unwrap_or(0);
+  let cols = max(widest, str_width(title) as i32 + 2) + 4;
+  // An empty list still gets a line inside the border.
+  let lines = max(1, min(items.len() as i32, max(3, LINES() - 6))) + 2;
+  let mut d = Dialog::open(curses, title, lines, cols)?;
+  let mut list = List::new(items).cursor_style(Style::new().reverse());
+  let old_cursor = ::curs_set(CURSOR_VISIBILITY::CURSOR_INVISIBLE);
+
+  let result = loop {
+    let inner = d.inner();
+    list.draw(&d.window, Rect::new(inner.y, inner.x + 1, inner.lines, inner.cols - 2));
+    d.show();
+    match d.getch() {
+      ESC => break None,
+      key => if let Response::Activated(i) = list.handle_key(key) {
+        break Some(i);
+      },
+    }
+  };
+  if let Some(v) = old_cursor {
+    ::curs_set(v);
+  }
+  result
+}
@@ -0,0 +1,351 @@
+/*
+    Retained-mode drawing with damage tracking.
+
+    Redrawing a whole window every frame and calling `wrefresh` on each
+    makes ncurses repaint and flush window by window, which flickers and,
+    over a slow link, resends cells that did not change. A `Frame` is a
+    grid of `chtype`s the application draws into; `render` compares it
+    with what it wrote last time and passes only the changed runs to
+    `mvwaddchnstr`, then `wnoutrefresh`es. `present` renders several frames
+    and ends with a single `doupdate`, so the terminal sees one update.
+
+    Frames hold `chtype`s, so only single-byte characters fit; draw wide
+    characters onto the window directly after rendering.
+*/
+
+use std::cmp::{max, min};
+use std::ops::{Add, AddAssign};
+
+use curses::{Curses, Window};
+use ll::{chtype, attr_t};
+
+/// Unchanged cells between two changed runs on a line that are rewritten
+/// rather than starting a new run; a short run of cells costs about as
+/// much as moving the cursor past them.
+const GAP: usize = 4;
+
+/// What a `render` wrote, or the sum over several renders.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FrameStats {
+  /// Number of renders counted.
+  pub frames: u64,
+  /// Cells that differed from the previous frame.
+  pub cells_changed: u64,
+  /// Cells passed to `mvwaddchnstr`, including the unchanged ones inside
+  /// a merged run.
+  pub cells_written: u64,
+  /// Calls to `mvwaddchnstr`.
+  pub runs: u64,
+  /// Lines with at least one changed cell.
+  pub lines_changed: u64,
+  /// Lines marked with `wtouchln` after `expose`, without rewriting them.
+  pub lines_touched: u64,
+  /// Renders that rewrote the whole frame.
+  pub full_redraws: u64,
+}
+
+impl FrameStats {
+  /// Average cells changed per frame.
+  pub fn cells_per_frame(&self) -> f64 {
+    if self.frames == 0 {
+      0.0
+    } else {
+      self.cells_changed as f64 / self.frames as f64
+    }
+  }
+}
+
+impl Add for FrameStats {
+  type Output = FrameStats;
+
+  fn add(self, o: FrameStats) -> FrameStats {
+    FrameStats {
+      frames: self.frames + o.frames,
+      cells_changed: self.cells_changed + o.cells_changed,
+      cells_written: self.cells_written + o.cells_written,
+      runs: self.runs + o.runs,
+      lines_changed: self.lines_changed + o.lines_changed,
+      lines_touched: self.lines_touched + o.lines_touched,
+      full_redraws: self.full_redraws + o.full_redraws,
+    }
+  }
+}
+
+impl AddAssign for FrameStats {
+  fn add_assign(&mut self, o: FrameStats) {
+    *self = *self + o;
+  }
+}
+
+#[derive(Debug, Clone)]
+pub struct Frame {
+  lines: i32,
+  cols: i32,
+  cells: Vec<chtype>,
+  previous: Vec<chtype>,
+  /// False until the first render and after `invalidate`/`resize`, when
+  /// `previous` says nothing about the window.
+  valid: bool,
+  /// Size of the window last rendered to. Cells beyond it were never
+  /// written, so rendering to a window of another size rewrites everything.
+  window: (i32, i32),
+  exposed: Vec<bool>,
+  last: FrameStats,
+  totals: FrameStats,
+}
+
+impl Frame {
+  /// A blank frame of `lines` x `cols`.
+  pub fn new(lines: i32, cols: i32) -> Frame {
+    let (lines, cols) = (max(0, lines), max(0, cols));
+    let n = (lines * cols) as usize;
+    Frame {
+      lines,
+      cols,
+      cells: vec![b' ' as chtype; n],
+      previous: vec![b' ' as chtype; n],
+      valid: false,
+      window: (0, 0),
+      exposed: vec![false; lines as usize],
+      last: FrameStats::default(),
+      totals: FrameStats::default(),
+    }
+  }
+
+  /// A blank frame the size of `w`.
+  pub fn for_window(w: &Window) -> Frame {
+    let (lines, cols) = w.size();
+    Frame::new(lines, cols)
+  }
+
+  /// `(lines, cols)`
+  pub fn size(&self) -> (i32, i32) {
+    (self.lines, self.cols)
+  }
+
+  /// Changes the size, keeping the cells that still fit. The next render
+  /// rewrites everything.
+  pub fn resize(&mut self, lines: i32, cols: i32) {
+    let mut resized = Frame::new(lines, cols);
+    for y in 0..min(self.lines, resized.lines) {
+      let n = min(self.cols, resized.cols) as usize;
+      let from = (y * self.cols) as usize;
+      let to = (y * resized.cols) as usize;
+      resized.cells[to..to + n].copy_from_slice(&self.cells[from..from + n]);
+    }
+    resized.totals = self.totals;
+    *self = resized;
+  }
+
+  /// Forgets what was drawn last, so the next render rewrites every cell;
+  /// for when the window was erased or drawn over behind the frame's back.
+  pub fn invalidate(&mut self) {
+    self.valid = false;
+  }
+
+  /// Marks `n` lines from `y` as uncovered, say by a popup closing over
+  /// them. The window still holds the right cells, so the next render only
+  /// `wtouchln`s them for `wnoutrefresh` to copy again.
+  pub fn expose(&mut self, y: i32, n: i32) {
+    for l in max(0, y)..min(self.lines, y.saturating_add(n)) {
+      self.exposed[l as usize] = true;
+    }
+  }
+
+  fn index(&self, y: i32, x: i32) -> Option<usize> {
+    if y < 0 || x < 0 || y >= self.lines || x >= self.cols {
+      None
+    } else {
+      Some((y * self.cols + x) as usize)
+    }
+  }
+
+  pub fn get(&self, y: i32, x: i32) -> Option<chtype> {
+    self.index(y, x).map(|i| self.cells[i])
+  }
+
+  /// Sets one cell; out of range is ignored.
+  pub fn set(&mut self, y: i32, x: i32, ch: chtype) {
+    if let Some(i) = self.index(y, x) {
+      self.cells[i] = ch;
+    }
+  }
+
+  /// The cells of line `y`.
+  pub fn row(&self, y: i32) -> &[chtype] {
+    let (start, end) = self.row_range(y);
+    &self.cells[start..end]
+  }
+
+  pub fn row_mut(&mut self, y: i32) -> &mut [chtype] {
+    let (start, end) = self.row_range(y);
+    &mut self.cells[start..end]
+  }
+
+  /// Empty for lines out of range.
+  fn row_range(&self, y: i32) -> (usize, usize) {
+    match self.index(y, 0) {
+      Some(start) => (start, start + self.cols as usize),
+      None => (0, 0),
+    }
+  }
+
+  /// Sets every cell to `ch`.
+  pub fn fill(&mut self, ch: chtype) {
+    for c in &mut self.cells {
+      *c = ch;
+    }
+  }
+
+  /// Blanks every cell, like `werase`.
+  pub fn erase(&mut self) {
+    self.fill(b' ' as chtype);
+  }
+
+  /// Writes `s` from `(y, x)` with `attrs`, clipped to the line. Characters
+  /// that do not fit in one byte are shown as `?`. Returns the number of
+  /// cells written.
+  pub fn print(&mut self, y: i32, x: i32, s: &str, attrs: attr_t) -> i32 {
+    let mut written = 0;
+    for (i, c) in s.chars().enumerate() {
+      let cx = x.saturating_add(i as i32);
+      if cx >= self.cols {
+        break;
+      }
+      let byte = if (c as u32) < 0x80 && !c.is_control() { c as chtype } else { b'?' as chtype };
+      if let Some(at) = self.index(y, cx) {
+        self.cells[at] = byte | attrs;
+        written += 1;
+      }
+    }
+    written
+  }
+
+  /// Writes the changes since the last render into `w` and marks it for
+  /// the next `doupdate`. Cells outside `w` are skipped, and written once
+  /// `w` is resized to show them.
+  pub fn render(&mut self, w: &Window) -> FrameStats {
+    let (wlines, wcols) = w.size();
+    let lines = min(self.lines, wlines);
+    let cols = min(self.cols, wcols) as usize;
+    let mut stats = FrameStats { frames: 1, ..FrameStats::default() };
+    if self.window != (wlines, wcols) {
+      self.valid = false;
+      self.window = (wlines, wcols);
+    }
+    if !self.valid {
+      stats.full_redraws = 1;
+    }
+
+    for y in 0..lines {
+      let base = (y * self.cols) as usize;
+      let now = &self.cells[base..base + cols];
+      let before = &self.previous[base..base + cols];
+      let mut changed = 0;
+      let mut x = 0;
+      while x < cols {
+        if self.valid && now[x] == before[x] {
+          x += 1;
+          continue;
+        }
+        // Extend the run while changed cells are at most GAP apart.
+        let start = x;
+        let mut end = x + 1;
+        let mut probe = end;
+        while probe < cols && probe - end <= GAP {
+          if !self.valid || now[probe] != before[probe] {
+            end = probe + 1;
+          }
+          probe += 1;
+        }
+        changed += (start..end).filter(|&i| !self.valid || now[i] != before[i]).count();
+        ::mvwaddchnstr(w.raw(), y, start as i32, &now[start..end], (end - start) as i32);
+        stats.runs += 1;
+        stats.cells_written += (end - start) as u64;
+        x = end;
+      }
+      if changed > 0 {
+        stats.lines_changed += 1;
+        stats.cells_changed += changed as u64;
+      } else if self.exposed[y as usize] {
+        ::wtouchln(w.raw(), y, 1, 1);
+        stats.lines_touched += 1;
+      }
+      self.previous[base..base + cols].copy_from_slice(&self.cells[base..base + cols]);
+    }
+
+    for e in &mut self.exposed {
+      *e = false;
+    }
+    self.valid = true;
+    w.noutrefresh();
+    self.last = stats;
+    self.totals += stats;
+    stats
+  }
+
+  /// What the last render wrote.
+  pub fn last_stats(&self) -> FrameStats {
+    self.last
+  }
+
+  /// The sum over every render so far.
+  pub fn total_stats(&self) -> FrameStats {
+    self.totals
+  }
+}
+
+/// Renders each frame into its window, then updates the terminal once.
+pub fn present(curses: &Curses, frames: &mut [(&mut Frame, &Window)]) -> FrameStats {
+  let mut stats = FrameStats::default();
+  for &mut (ref mut frame, w) in frames.iter_mut() {
+    stats += frame.render(w);
+  }
+  curses.doupdate();
+  stats
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use constants::A_CHARTEXT;
+  use testing::with_screen;
+
+  fn row(w: &Window, y: i32) -> String {
+    let (_, cols) = w.size();
+    (0..cols).map(|x| (::mvwinch(w.raw(), y, x) & A_CHARTEXT) as u8 as char).collect()
+  }
+
+  #[test]
+  fn renders_only_changes() {
+    with_screen(|curses| {
+      let w = curses.new_window(2, 6, 0, 0).unwrap();
+      let mut frame = Frame::for_window(&w);
+      frame.print(0, 0, "abc", 0);
+      let first = frame.render(&w);
+      assert_eq!((first.full_redraws, first.cells_written), (1, 12));
+      frame.print(1, 4, "z", 0);
+      let second = frame.render(&w);
+      assert_eq!((second.full_redraws, second.cells_changed, second.runs), (0, 1, 1));
+      assert_eq!((row(&w, 0), row(&w, 1)), ("abc   ".to_string(), "    z ".to_string()));
+      assert_eq!(frame.render(&w).cells_written, 0);
+    });
+  }
+
+  #[test]
+  fn clipped_cells_are_drawn_once_the_window_grows() {
+    with_screen(|curses| {
+      let w = curses.new_window(2, 3, 0, 0).unwrap();
+      let mut frame = Frame::new(2, 6);
+      frame.print(0, 0, "abcdef", 0);
+      frame.print(1, 0, "ghijkl", 0);
+      frame.render(&w);
+      assert_eq!(row(&w, 0), "abc");
+      w.resize(2, 6);
+      let stats = frame.render(&w);
+      assert_eq!(stats.full_redraws, 1);
+      assert_eq!((row(&w, 0), row(&w, 1)), ("abcdef".to_string(), "ghijkl".to_string()));
+      assert_eq!(frame.render(&w).cells_written, 0);
+    });
+  }
+}
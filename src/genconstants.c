@@ -1,14 +1,15 @@
 #include <curses.h>
 
-#define PCONST(ty, NAME) printf("pub const " #NAME ": " #ty " = %lld;\n", (long long) (NAME))
-#define PCONSTU(ty, NAME) printf("pub const " #NAME ": " #ty " = %llu;\n", (unsigned long long) (NAME))
+#include "genconstants.h"
 
-int main() {
+BEGIN
+#if !defined(NCURSES_RS_OBJECT) && defined(NCURSES_VERSION)
         /* some values aren't set until after this is run */
         printf("//");
 	initscr();
 	endwin();
         printf("\n");
+#endif
 
 	/* Success/Failure. */
 	PCONST(i32, ERR);
@@ -222,7 +223,8 @@ int main() {
 	PCONST(i32, NCURSES_RESERVED_EVENT);
 #endif
 
-	/* event masks */
+	/* event masks; NetBSD curses has no mouse support */
+#ifdef BUTTON1_PRESSED
 	PCONST(i32, BUTTON1_RELEASED);
 	PCONST(i32, BUTTON1_PRESSED);
 	PCONST(i32, BUTTON1_CLICKED);
@@ -267,12 +269,15 @@ int main() {
 	PCONST(i32, BUTTON5_TRIPLE_CLICKED);
 #endif
 
+#ifdef BUTTON_CTRL
 	PCONST(i32, BUTTON_CTRL);
+#endif
 	PCONST(i32, BUTTON_SHIFT);
 	PCONST(i32, BUTTON_ALT);
 	PCONST(i32, REPORT_MOUSE_POSITION);
 
 	PCONST(i32, ALL_MOUSE_EVENTS);
+#endif
 
 	/* Attributes */
 	PCONSTU(crate::ll::chtype, A_NORMAL);
@@ -293,4 +298,22 @@ int main() {
 	PCONSTU(crate::ll::chtype, A_ATTRIBUTES);
 	PCONSTU(crate::ll::chtype, A_CHARTEXT);
 	PCONSTU(crate::ll::chtype, A_COLOR);
-}
+
+	/* Version of the headers; left empty or 0 by curses other than
+	   ncurses. */
+#ifdef NCURSES_VERSION
+	PSTR(NCURSES_VERSION, NCURSES_VERSION);
+#else
+	PSTR(NCURSES_VERSION, "");
+#endif
+#ifndef NCURSES_VERSION_MAJOR
+#define NCURSES_VERSION_MAJOR 0
+#define NCURSES_VERSION_MINOR 0
+#endif
+#ifndef NCURSES_VERSION_PATCH
+#define NCURSES_VERSION_PATCH 0
+#endif
+	PCONST(i32, NCURSES_VERSION_MAJOR);
+	PCONST(i32, NCURSES_VERSION_MINOR);
+	PCONST(i32, NCURSES_VERSION_PATCH);
+END
@@ -0,0 +1,42 @@
+/*
+    Output macros shared by the constant generators.
+
+    Natively, build.rs runs the generator and each PCONST prints a line
+    of Rust. When cross-compiling the generator cannot run, so build.rs
+    only compiles it to an object file, with NCURSES_RS_OBJECT defined.
+    Each PCONST is then a record in the object's data: a marker and the
+    start of the line, then the value as a 64-bit integer at a fixed
+    offset. build.rs finds the records and writes the lines itself.
+*/
+
+#include <stdio.h>
+
+#ifdef NCURSES_RS_OBJECT
+
+/* build.rs reads the value at this offset from the marker. */
+#define NCURSES_RS_TEXT 120
+
+struct ncurses_rs_record { char text[NCURSES_RS_TEXT]; long long value; };
+struct ncurses_rs_record_u { char text[NCURSES_RS_TEXT]; unsigned long long value; };
+
+#define PCONST(ty, NAME) const struct ncurses_rs_record ncurses_rs_##NAME = \
+	{ "NCURSES_RS_CONST:pub const " #NAME ": " #ty " = ", (long long) (NAME) }
+#define PCONSTU(ty, NAME) const struct ncurses_rs_record_u ncurses_rs_##NAME = \
+	{ "NCURSES_RS_CONSTU:pub const " #NAME ": " #ty " = ", (unsigned long long) (NAME) }
+#define PSTR(NAME, VALUE) const char ncurses_rs_##NAME[] = \
+	"NCURSES_RS_STR:pub const " #NAME ": &str = \"" VALUE "\";"
+
+/* The records are definitions at file scope rather than statements. */
+#define BEGIN
+#define END
+
+#else
+
+#define PCONST(ty, NAME) printf("pub const " #NAME ": " #ty " = %lld;\n", (long long) (NAME))
+#define PCONSTU(ty, NAME) printf("pub const " #NAME ": " #ty " = %llu;\n", (unsigned long long) (NAME))
+#define PSTR(NAME, VALUE) printf("pub const " #NAME ": &str = \"%s\";\n", VALUE)
+
+#define BEGIN int main(void) {
+#define END return 0; }
+
+#endif
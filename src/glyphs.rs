@@ -0,0 +1,441 @@
+/*
+    Line drawing and symbol characters that degrade gracefully.
+
+    The `ACS_*()` functions return whatever terminfo's `acsc` maps them to,
+    which on a wrong `TERM`, or in a UTF-8 locale with a narrow ncurses, is
+    often plain `qqqq`. `Glyphs` picks between the alternate character set,
+    Unicode (with light, heavy, double and rounded box styles) and plain
+    ASCII, based on the locale and terminal. The `NCURSES_RS_GLYPHS`
+    environment variable (`acs`, `unicode` or `ascii`) or
+    `Glyphs::set_default` overrides the detection.
+*/
+
+use std::env;
+use std::ffi::CStr;
+use std::sync::Mutex;
+
+use curses::Window;
+use canvas::{Arms, LineStyle};
+use ll::chtype;
+use constants::{OK, ERR};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GlyphSet {
+  Acs,
+  Unicode,
+  Ascii,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BoxStyle {
+  Light,
+  Heavy,
+  Double,
+  /// Light lines with rounded corners.
+  Rounded,
+}
+
+impl BoxStyle {
+  fn line_style(self) -> LineStyle {
+    match self {
+      BoxStyle::Light | BoxStyle::Rounded => LineStyle::Light,
+      BoxStyle::Heavy => LineStyle::Heavy,
+      BoxStyle::Double => LineStyle::Double,
+    }
+  }
+}
+
+/// The symbols available through `ACS_*()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Symbol {
+  ULCorner,
+  URCorner,
+  LLCorner,
+  LRCorner,
+  LTee,
+  RTee,
+  TTee,
+  BTee,
+  HLine,
+  VLine,
+  Plus,
+  S1,
+  S3,
+  S7,
+  S9,
+  Diamond,
+  CkBoard,
+  Degree,
+  PlMinus,
+  Bullet,
+  LArrow,
+  RArrow,
+  DArrow,
+  UArrow,
+  Board,
+  Lantern,
+  Block,
+  LEqual,
+  GEqual,
+  Pi,
+  NEqual,
+  Sterling,
+}
+
+/// A single character cell: either from the alternate character set or a
+/// plain character.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Glyph {
+  Acs(chtype),
+  Char(char),
+}
+
+impl Glyph {
+  /// Draws the glyph at the cursor.
+  pub fn put(&self, w: &Window) -> i32 {
+    match *self {
+      Glyph::Acs(ch) => w.addch(ch),
+      Glyph::Char(c) if c.is_ascii() => w.addch(c as chtype),
+      Glyph::Char(c) => {
+        let mut buf = [0; 4];
+        w.addstr(c.encode_utf8(&mut buf)).unwrap_or(ERR)
+      }
+    }
+  }
+
+  pub fn mvput(&self, w: &Window, y: i32, x: i32) -> i32 {
+    if w.mv(y, x) == ERR {
+      return ERR;
+    }
+    self.put(w)
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Glyphs {
+  set: GlyphSet,
+  style: BoxStyle,
+}
+
+static DEFAULT: Mutex<Option<Glyphs>> = Mutex::new(None);
+/// What `detect()` found, and for which screen, named by its `stdscr`.
+static DETECTED: Mutex<Option<(usize, Glyphs)>> = Mutex::new(None);
+
+impl Glyphs {
+  pub fn new(set: GlyphSet, style: BoxStyle) -> Glyphs {
+    Glyphs { set, style }
+  }
+
+  /// The override set with `set_default`, or else `detect()`, which runs
+  /// once per screen.
+  pub fn current() -> Glyphs {
+    if let Some(glyphs) = *DEFAULT.lock().unwrap_or_else(|e| e.into_inner()) {
+      return glyphs;
+    }
+    let screen = ::stdscr() as usize;
+    let mut detected = DETECTED.lock().unwrap_or_else(|e| e.into_inner());
+    match *detected {
+      Some((s, glyphs)) if s == screen => glyphs,
+      _ => {
+        let glyphs = Glyphs::detect();
+        *detected = Some((screen, glyphs));
+        glyphs
+      }
+    }
+  }
+
+  /// Makes `current()` return `glyphs` (or detect again, with `None`).
+  pub fn set_default(glyphs: Option<Glyphs>) {
+    *DEFAULT.lock().unwrap_or_else(|e| e.into_inner()) = glyphs;
+  }
+
+  /// Drops what `current()` detected, for when the screen it was for goes
+  /// away or the locale or environment changes.
+  pub fn redetect() {
+    *DETECTED.lock().unwrap_or_else(|e| e.into_inner()) = None;
+  }
+
+  /// Chooses a glyph set for the current locale and terminal. Terminal
+  /// capabilities are only known once curses has been initialised.
+  pub fn detect() -> Glyphs {
+    Glyphs::new(detect_set(), BoxStyle::Light)
+  }
+
+  pub fn set(&self) -> GlyphSet {
+    self.set
+  }
+
+  pub fn style(&self) -> BoxStyle {
+    self.style
+  }
+
+  pub fn with_style(self, style: BoxStyle) -> Glyphs {
+    Glyphs { style, ..self }
+  }
+
+  pub fn with_set(self, set: GlyphSet) -> Glyphs {
+    Glyphs { set, ..self }
+  }
+
+  pub fn get(&self, sym: Symbol) -> Glyph {
+    match self.set {
+      GlyphSet::Acs => Glyph::Acs(acs(sym)),
+      GlyphSet::Unicode => Glyph::Char(self.unicode(sym)),
+      GlyphSet::Ascii => Glyph::Char(ascii(sym)),
+    }
+  }
+
+  /// The glyph for a cell of a `LineCanvas`.
+  pub fn junction(&self, arms: &Arms) -> Glyph {
+    match self.set {
+      GlyphSet::Acs => Glyph::Acs(arms.acs()),
+      GlyphSet::Unicode => {
+        let rounded = self.style == BoxStyle::Rounded;
+        Glyph::Char(match (arms.up, arms.down, arms.left, arms.right) {
+          (None, Some(LineStyle::Light), None, Some(LineStyle::Light)) if rounded => '\u{256D}',
+          (None, Some(LineStyle::Light), Some(LineStyle::Light), None) if rounded => '\u{256E}',
+          (Some(LineStyle::Light), None, Some(LineStyle::Light), None) if rounded => '\u{256F}',
+          (Some(LineStyle::Light), None, None, Some(LineStyle::Light)) if rounded => '\u{2570}',
+          _ => arms.unicode(),
+        })
+      }
+      GlyphSet::Ascii => {
+        let vertical = arms.up.is_some() || arms.down.is_some();
+        let horizontal = arms.left.is_some() || arms.right.is_some();
+        Glyph::Char(match (vertical, horizontal) {
+          (true, true) => '+',
+          (true, false) => '|',
+          _ => '-',
+        })
+      }
+    }
+  }
+
+  /// The line style matching this set's box style, for a `LineCanvas`.
+  pub fn line_style(&self) -> LineStyle {
+    self.style.line_style()
+  }
+
+  /// Draws a border around the edge of `w`.
+  pub fn border(&self, w: &Window) -> i32 {
+    let (lines, cols) = w.size();
+    if lines < 2 || cols < 2 {
+      return ERR;
+    }
+    let h = self.get_styled(Symbol::HLine);
+    let v = self.get_styled(Symbol::VLine);
+    let mut ret = OK;
+    for x in 1..cols - 1 {
+      ret |= h.mvput(w, 0, x) | h.mvput(w, lines - 1, x);
+    }
+    for y in 1..lines - 1 {
+      ret |= v.mvput(w, y, 0) | v.mvput(w, y, cols - 1);
+    }
+    ret |= self.get_styled(Symbol::ULCorner).mvput(w, 0, 0);
+    ret |= self.get_styled(Symbol::URCorner).mvput(w, 0, cols - 1);
+    ret |= self.get_styled(Symbol::LLCorner).mvput(w, lines - 1, 0);
+    // Writing the bottom-right cell fails to advance the cursor, which is
+    // reported as an error even though the character is drawn.
+    self.get_styled(Symbol::LRCorner).mvput(w, lines - 1, cols - 1);
+    if ret == OK { OK } else { ERR }
+  }
+
+  /// Like `get`, but line and corner symbols follow the box style.
+  pub fn get_styled(&self, sym: Symbol) -> Glyph {
+    let s = Some(self.style.line_style());
+    let arms = match sym {
+      Symbol::ULCorner => Arms { down: s, right: s, ..Arms::default() },
+      Symbol::URCorner => Arms { down: s, left: s, ..Arms::default() },
+      Symbol::LLCorner => Arms { up: s, right: s, ..Arms::default() },
+      Symbol::LRCorner => Arms { up: s, left: s, ..Arms::default() },
+      Symbol::LTee => Arms { up: s, down: s, right: s, ..Arms::default() },
+      Symbol::RTee => Arms { up: s, down: s, left: s, ..Arms::default() },
+      Symbol::TTee => Arms { down: s, left: s, right: s, ..Arms::default() },
+      Symbol::BTee => Arms { up: s, left: s, right: s, ..Arms::default() },
+      Symbol::HLine => Arms { left: s, right: s, ..Arms::default() },
+      Symbol::VLine => Arms { up: s, down: s, ..Arms::default() },
+      Symbol::Plus => Arms { up: s, down: s, left: s, right: s },
+      _ => return self.get(sym),
+    };
+    if self.set == GlyphSet::Ascii {
+      return Glyph::Char(ascii(sym));
+    }
+    self.junction(&arms)
+  }
+
+  fn unicode(&self, sym: Symbol) -> char {
+    match sym {
+      Symbol::ULCorner => if self.style == BoxStyle::Rounded { '╭' } else { '┌' },
+      Symbol::URCorner => if self.style == BoxStyle::Rounded { '╮' } else { '┐' },
+      Symbol::LLCorner => if self.style == BoxStyle::Rounded { '╰' } else { '└' },
+      Symbol::LRCorner => if self.style == BoxStyle::Rounded { '╯' } else { '┘' },
+      Symbol::LTee => '├',
+      Symbol::RTee => '┤',
+      Symbol::TTee => '┬',
+      Symbol::BTee => '┴',
+      Symbol::HLine => '─',
+      Symbol::VLine => '│',
+      Symbol::Plus => '┼',
+      Symbol::S1 => '⎺',
+      Symbol::S3 => '⎻',
+      Symbol::S7 => '⎼',
+      Symbol::S9 => '⎽',
+      Symbol::Diamond => '◆',
+      Symbol::CkBoard => '▒',
+      Symbol::Degree => '°',
+      Symbol::PlMinus => '±',
+      Symbol::Bullet => '·',
+      Symbol::LArrow => '←',
+      Symbol::RArrow => '→',
+      Symbol::DArrow => '↓',
+      Symbol::UArrow => '↑',
+      Symbol::Board => '░',
+      Symbol::Lantern => '␋',
+      Symbol::Block => '█',
+      Symbol::LEqual => '≤',
+      Symbol::GEqual => '≥',
+      Symbol::Pi => 'π',
+      Symbol::NEqual => '≠',
+      Symbol::Sterling => '£',
+    }
+  }
+}
+
+fn acs(sym: Symbol) -> chtype {
+  match sym {
+    Symbol::ULCorner => ::ACS_ULCORNER(),
+    Symbol::URCorner => ::ACS_URCORNER(),
+    Symbol::LLCorner => ::ACS_LLCORNER(),
+    Symbol::LRCorner => ::ACS_LRCORNER(),
+    Symbol::LTee => ::ACS_LTEE(),
+    Symbol::RTee => ::ACS_RTEE(),
+    Symbol::TTee => ::ACS_TTEE(),
+    Symbol::BTee => ::ACS_BTEE(),
+    Symbol::HLine => ::ACS_HLINE(),
+    Symbol::VLine => ::ACS_VLINE(),
+    Symbol::Plus => ::ACS_PLUS(),
+    Symbol::S1 => ::ACS_S1(),
+    Symbol::S3 => ::ACS_S3(),
+    Symbol::S7 => ::ACS_S7(),
+    Symbol::S9 => ::ACS_S9(),
+    Symbol::Diamond => ::ACS_DIAMOND(),
+    Symbol::CkBoard => ::ACS_CKBOARD(),
+    Symbol::Degree => ::ACS_DEGREE(),
+    Symbol::PlMinus => ::ACS_PLMINUS(),
+    Symbol::Bullet => ::ACS_BULLET(),
+    Symbol::LArrow => ::ACS_LARROW(),
+    Symbol::RArrow => ::ACS_RARROW(),
+    Symbol::DArrow => ::ACS_DARROW(),
+    Symbol::UArrow => ::ACS_UARROW(),
+    Symbol::Board => ::ACS_BOARD(),
+    Symbol::Lantern => ::ACS_LANTERN(),
+    Symbol::Block => ::ACS_BLOCK(),
+    Symbol::LEqual => ::ACS_LEQUAL(),
+    Symbol::GEqual => ::ACS_GEQUAL(),
+    Symbol::Pi => ::ACS_PI(),
+    Symbol::NEqual => ::ACS_NEQUAL(),
+    Symbol::Sterling => ::ACS_STERLING(),
+  }
+}
+
+/// The same fallbacks ncurses itself uses when `acsc` is missing.
+fn ascii(sym: Symbol) -> char {
+  match sym {
+    Symbol::HLine | Symbol::S1 | Symbol::S3 | Symbol::S7 | Symbol::S9 => '-',
+    Symbol::VLine => '|',
+    Symbol::Diamond | Symbol::Plus => '+',
+    Symbol::ULCorner | Symbol::URCorner | Symbol::LLCorner | Symbol::LRCorner => '+',
+    Symbol::LTee | Symbol::RTee | Symbol::TTee | Symbol::BTee => '+',
+    Symbol::CkBoard | Symbol::Board | Symbol::Lantern | Symbol::Block => '#',
+    Symbol::Degree => '\'',
+    Symbol::PlMinus => '#',
+    Symbol::Bullet => 'o',
+    Symbol::LArrow | Symbol::LEqual => '<',
+    Symbol::RArrow | Symbol::GEqual => '>',
+    Symbol::DArrow => 'v',
+    Symbol::UArrow => '^',
+    Symbol::Pi => '*',
+    Symbol::NEqual => '!',
+    Symbol::Sterling => 'f',
+  }
+}
+
+fn detect_set() -> GlyphSet {
+  if let Ok(name) = env::var("NCURSES_RS_GLYPHS") {
+    match name.to_ascii_lowercase().as_str() {
+      "acs" => return GlyphSet::Acs,
+      "unicode" | "utf8" | "utf-8" => return GlyphSet::Unicode,
+      "ascii" => return GlyphSet::Ascii,
+      _ => {}
+    }
+  }
+
+  let term = env::var("TERM").unwrap_or_default();
+  if term.is_empty() || term == "dumb" {
+    return GlyphSet::Ascii;
+  }
+
+  let utf8 = utf8_locale();
+  if utf8 && cfg!(feature = "wide") {
+    return GlyphSet::Unicode;
+  }
+
+  // A narrow ncurses cannot use the Unicode fallback ncurses has for
+  // terminals that ignore the ACS switch in UTF-8 mode.
+  if utf8 && ignores_acs_in_utf8(&term) {
+    return GlyphSet::Ascii;
+  }
+  if !has_acsc() {
+    return GlyphSet::Ascii;
+  }
+  GlyphSet::Acs
+}
+
+/// Terminals flagged through `NCURSES_NO_UTF8_ACS`, the `U8` terminfo
+/// extension, or known to behave that way.
+fn ignores_acs_in_utf8(term: &str) -> bool {
+  if let Some(v) = env::var_os("NCURSES_NO_UTF8_ACS") {
+    return v != "0";
+  }
+  if !::stdscr().is_null() {
+    let u8 = unsafe { ::ll::tigetnum(b"U8\0".as_ptr() as *const libc::c_char) };
+    if u8 >= 0 {
+      return u8 > 0;
+    }
+  }
+  term.starts_with("screen") || term.starts_with("tmux") || term.starts_with("linux")
+}
+
+/// Whether `acsc` is defined. Assumed so if curses is not started yet.
+fn has_acsc() -> bool {
+  if ::stdscr().is_null() {
+    return true;
+  }
+  let cap = unsafe { ::ll::tigetstr(b"acsc\0".as_ptr() as *const libc::c_char) };
+  !cap.is_null() && cap as isize != -1
+}
+
+/// Checks the codeset of the current locale, falling back to the
+/// environment when `setlocale` has not been called yet.
+fn utf8_locale() -> bool {
+  let is_utf8 = |s: &str| {
+    let s = s.to_ascii_lowercase();
+    s.contains("utf-8") || s.contains("utf8")
+  };
+
+  let codeset = unsafe {
+    let p = libc::nl_langinfo(libc::CODESET);
+    if p.is_null() { String::new() } else { CStr::from_ptr(p).to_string_lossy().into_owned() }
+  };
+  if is_utf8(&codeset) {
+    return true;
+  }
+  for var in &["LC_ALL", "LC_CTYPE", "LANG"] {
+    if let Ok(value) = env::var(var) {
+      if !value.is_empty() {
+        return is_utf8(&value);
+      }
+    }
+  }
+  false
+}
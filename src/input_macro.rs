@@ -0,0 +1,442 @@
+/*
+    Recording and replaying the input an application reads.
+
+    While recording, every key the crate's `getch` and `get_wch` families
+    return, and every mouse event `getmouse` fetches, is kept with the time
+    it was read. Keys an application pushes back with `ungetch` and friends
+    are taken off again, since they are read, and recorded, a second time.
+
+    Playing an `InputMacro` back hands the same events to the application
+    in the same order: before each read, the next event is pushed into
+    curses' input queue with `ungetch`, `unget_wch` or `ungetmouse`, so no
+    terminal input is needed. At speed `1.0` a read waits until its event
+    is due; a window with a `timeout` shorter than that reads the terminal
+    instead and typically gets `ERR`, as it would have then. Infinite speed
+    replays as fast as the application reads.
+
+    The text format has one event per line, after the seconds since the
+    start of the recording:
+
+        0.000 key 97
+        0.412 key 259
+        1.250 char 233
+        2.003 mouse 0 10 4 0 0x4
+
+    `mouse` takes the `MEVENT` fields `id x y z bstate`. Anything after `#`
+    is a comment.
+*/
+
+use std::fmt::Write as FmtWrite;
+use std::io::{self, BufRead, Write};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use ll::{WINDOW, MEVENT, mmask_t};
+use constants::{ERR, KEY_MOUSE};
+use WchResult;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MacroEvent {
+  /// A key code or byte from `getch`, or a key code from `get_wch`.
+  Key(i32),
+  /// A character from `get_wch`.
+  Char(u32),
+  /// A mouse event from `getmouse`.
+  Mouse { id: i16, x: i32, y: i32, z: i32, bstate: mmask_t },
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct InputMacro {
+  /// Each event with the time it was read, from the start of the recording.
+  pub events: Vec<(Duration, MacroEvent)>,
+}
+
+impl InputMacro {
+  pub fn write<W: Write>(&self, mut w: W) -> io::Result<()> {
+    for &(at, ref event) in &self.events {
+      let mut line = format!("{}.{:03} ", at.as_secs(), at.subsec_millis());
+      let shown = match *event {
+        MacroEvent::Key(k) => {
+          let _ = write!(line, "key {}", k);
+          Some(k as u32).filter(|&k| k < 0x80)
+        }
+        MacroEvent::Char(c) => {
+          let _ = write!(line, "char {}", c);
+          Some(c)
+        }
+        MacroEvent::Mouse { id, x, y, z, bstate } => {
+          let _ = write!(line, "mouse {} {} {} {} {:#x}", id, x, y, z, bstate);
+          None
+        }
+      };
+      if let Some(c) = shown.and_then(std::char::from_u32).filter(|c| !c.is_control()) {
+        let _ = write!(line, " # {:?}", c);
+      }
+      writeln!(w, "{}", line)?;
+    }
+    w.flush()
+  }
+
+  pub fn read<R: BufRead>(r: R) -> io::Result<InputMacro> {
+    let mut m = InputMacro::default();
+    for (n, line) in r.lines().enumerate() {
+      let line = line?;
+      let fields: Vec<&str> = line.split('#').next().unwrap_or("").split_whitespace().collect();
+      if fields.is_empty() {
+        continue;
+      }
+      let bad = || io::Error::new(io::ErrorKind::InvalidData, format!("line {}: cannot parse {:?}", n + 1, line));
+      let at = fields[0].parse::<f64>().ok()
+        .filter(|t| *t >= 0.0)
+        .and_then(|t| Duration::try_from_secs_f64(t).ok())
+        .ok_or_else(bad)?;
+      let int = |i: usize| -> io::Result<i64> {
+        let f = fields.get(i).ok_or_else(bad)?;
+        match f.strip_prefix("0x") {
+          Some(hex) => i64::from_str_radix(hex, 16),
+          None => f.parse(),
+        }.map_err(|_| bad())
+      };
+      let event = match (fields.get(1).cloned(), fields.len()) {
+        (Some("key"), 3) => MacroEvent::Key(int(2)? as i32),
+        (Some("char"), 3) => MacroEvent::Char(int(2)? as u32),
+        (Some("mouse"), 7) => MacroEvent::Mouse {
+          id: int(2)? as i16,
+          x: int(3)? as i32,
+          y: int(4)? as i32,
+          z: int(5)? as i32,
+          bstate: int(6)? as mmask_t,
+        },
+        _ => return Err(bad()),
+      };
+      m.events.push((at, event));
+    }
+    Ok(m)
+  }
+
+  /// Starts feeding the events to the application's reads, `speed` times
+  /// as fast as recorded. Replaces any playback in progress.
+  pub fn play(&self, speed: f64) {
+    let mut state = lock();
+    state.playback = Some(Playback {
+      events: self.events.clone(),
+      next: 0,
+      speed,
+      start: Instant::now(),
+      unread: 0,
+    });
+    ACTIVE.store(true, Ordering::SeqCst);
+  }
+}
+
+#[derive(Debug)]
+struct Recording {
+  start: Instant,
+  events: Vec<(Duration, MacroEvent)>,
+}
+
+#[derive(Debug)]
+struct Playback {
+  events: Vec<(Duration, MacroEvent)>,
+  next: usize,
+  speed: f64,
+  start: Instant,
+  /// Keys pushed into the input queue and not yet read.
+  unread: usize,
+}
+
+#[derive(Debug, Default)]
+struct State {
+  recording: Option<Recording>,
+  playback: Option<Playback>,
+}
+
+static STATE: Mutex<State> = Mutex::new(State { recording: None, playback: None });
+/// Whether either is on, so reads skip the lock otherwise.
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+
+fn lock() -> ::std::sync::MutexGuard<'static, State> {
+  STATE.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn update_active(state: &State) {
+  ACTIVE.store(state.recording.is_some() || state.playback.is_some(), Ordering::SeqCst);
+}
+
+/// Starts recording, discarding any recording in progress.
+pub fn start_recording() {
+  let mut state = lock();
+  state.recording = Some(Recording { start: Instant::now(), events: Vec::new() });
+  update_active(&state);
+}
+
+/// Stops recording and returns what was read since `start_recording`.
+pub fn stop_recording() -> InputMacro {
+  let mut state = lock();
+  let events = state.recording.take().map(|r| r.events).unwrap_or_default();
+  update_active(&state);
+  InputMacro { events }
+}
+
+pub fn is_recording() -> bool {
+  lock().recording.is_some()
+}
+
+/// Stops playback; events not yet fed are dropped.
+pub fn stop_playback() {
+  let mut state = lock();
+  state.playback = None;
+  update_active(&state);
+}
+
+/// Whether events remain to be fed, or fed events remain to be read.
+pub fn is_playing() -> bool {
+  lock().playback.is_some()
+}
+
+/// Wraps a read from `w`, feeding it the next event and recording the key.
+pub fn read_key<F: FnOnce() -> i32>(w: WINDOW, read: F) -> i32 {
+  if !ACTIVE.load(Ordering::SeqCst) {
+    return read();
+  }
+  feed(w);
+  let ch = read();
+  if ch != ERR {
+    consumed();
+    record(MacroEvent::Key(ch));
+  }
+  ch
+}
+
+/// `read_key` for the `get_wch` family.
+pub fn read_wch<F: FnOnce() -> Option<WchResult>>(w: WINDOW, read: F) -> Option<WchResult> {
+  if !ACTIVE.load(Ordering::SeqCst) {
+    return read();
+  }
+  feed(w);
+  let result = read();
+  match result {
+    Some(WchResult::Char(c)) => {
+      consumed();
+      record(MacroEvent::Char(c));
+    }
+    Some(WchResult::KeyCode(k)) => {
+      consumed();
+      record(MacroEvent::Key(k));
+    }
+    None => {}
+  }
+  result
+}
+
+/// Records a mouse event returned by `getmouse`. It replaces the
+/// `KEY_MOUSE` read just before, since replaying it with `ungetmouse`
+/// queues a `KEY_MOUSE` of its own.
+pub fn read_mouse(event: &MEVENT) {
+  if !ACTIVE.load(Ordering::SeqCst) {
+    return;
+  }
+  let mut state = lock();
+  if let Some(ref mut rec) = state.recording {
+    let mouse = MacroEvent::Mouse { id: event.id, x: event.x, y: event.y, z: event.z, bstate: event.bstate };
+    match rec.events.last_mut() {
+      Some(last) if last.1 == MacroEvent::Key(KEY_MOUSE) => last.1 = mouse,
+      _ => rec.events.push((rec.start.elapsed(), mouse)),
+    }
+  }
+}
+
+/// Takes back the last recorded event if it is `event`, which the
+/// application has just pushed back to be read again. During playback,
+/// the next event waits until it has been.
+pub fn unread(event: MacroEvent) {
+  if !ACTIVE.load(Ordering::SeqCst) {
+    return;
+  }
+  let mut state = lock();
+  if let Some(ref mut p) = state.playback {
+    p.unread += 1;
+  }
+  if let Some(ref mut rec) = state.recording {
+    let matches = match (rec.events.last().map(|e| e.1), event) {
+      (Some(MacroEvent::Mouse { .. }), MacroEvent::Mouse { .. }) => true,
+      (Some(last), _) => last == event,
+      (None, _) => false,
+    };
+    if matches {
+      rec.events.pop();
+    }
+  }
+}
+
+fn record(event: MacroEvent) {
+  let mut state = lock();
+  if let Some(ref mut rec) = state.recording {
+    let at = rec.start.elapsed();
+    rec.events.push((at, event));
+  }
+}
+
+fn consumed() {
+  let mut state = lock();
+  let finished = match state.playback {
+    Some(ref mut p) => {
+      p.unread = p.unread.saturating_sub(1);
+      p.unread == 0 && p.next == p.events.len()
+    }
+    None => false,
+  };
+  if finished {
+    state.playback = None;
+    update_active(&state);
+  }
+}
+
+/// How long a read on `w` waits for input, in milliseconds; negative for
+/// as long as it takes.
+#[cfg(ncurses_wgetdelay)]
+fn read_delay(w: WINDOW) -> i32 {
+  ::wgetdelay(w)
+}
+
+#[cfg(not(ncurses_wgetdelay))]
+fn read_delay(_: WINDOW) -> i32 {
+  -1
+}
+
+/// Pushes the next event into the input queue once the ones pushed before
+/// are read, waiting for it to be due unless `w` gives up sooner.
+fn feed(w: WINDOW) {
+  let wait = {
+    let state = lock();
+    let p = match state.playback {
+      Some(ref p) if p.unread == 0 && p.next < p.events.len() => p,
+      _ => return,
+    };
+    // Times too far off to count in, at a tiny speed, count as due now.
+    let due = if p.speed.is_finite() && p.speed > 0.0 {
+      Duration::try_from_secs_f64(p.events[p.next].0.as_secs_f64() / p.speed).ok()
+        .and_then(|d| p.start.checked_add(d))
+        .unwrap_or(p.start)
+    } else {
+      p.start
+    };
+    due.saturating_duration_since(Instant::now())
+  };
+
+  let delay = read_delay(w);
+  if delay >= 0 && wait > Duration::from_millis(delay as u64) {
+    return;
+  }
+  if wait > Duration::from_secs(0) {
+    thread::sleep(wait);
+  }
+
+  let mut state = lock();
+  if let Some(ref mut p) = state.playback {
+    if p.unread == 0 && p.next < p.events.len() {
+      p.unread = inject(p.events[p.next].1);
+      p.next += 1;
+    }
+  }
+}
+
+/// Queues `event` to be read; returns how many reads it takes.
+fn inject(event: MacroEvent) -> usize {
+  match event {
+    MacroEvent::Key(k) => {
+      unsafe { ::ll::ungetch(k) };
+      1
+    }
+    MacroEvent::Char(c) => inject_char(c),
+    MacroEvent::Mouse { id, x, y, z, bstate } => inject_mouse(MEVENT { id, x, y, z, bstate }),
+  }
+}
+
+#[cfg(feature = "wide")]
+fn inject_char(c: u32) -> usize {
+  unsafe { ::ll::unget_wch(c) };
+  1
+}
+
+/// Without the wide library the character goes in as its UTF-8 bytes,
+/// pushed last first.
+#[cfg(not(feature = "wide"))]
+fn inject_char(c: u32) -> usize {
+  let mut buf = [0; 4];
+  let bytes = std::char::from_u32(c).unwrap_or('\u{fffd}').encode_utf8(&mut buf).as_bytes();
+  for &b in bytes.iter().rev() {
+    unsafe { ::ll::ungetch(b as i32) };
+  }
+  bytes.len()
+}
+
+#[cfg(ncurses_mouse)]
+fn inject_mouse(mut event: MEVENT) -> usize {
+  unsafe { ::ll::ungetmouse(&mut event) };
+  1
+}
+
+#[cfg(not(ncurses_mouse))]
+fn inject_mouse(_: MEVENT) -> usize {
+  0
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn sample() -> InputMacro {
+    InputMacro {
+      events: vec![
+        (Duration::from_millis(0), MacroEvent::Key(97)),
+        (Duration::from_millis(412), MacroEvent::Key(259)),
+        (Duration::from_millis(1250), MacroEvent::Char(233)),
+        (Duration::from_millis(1300), MacroEvent::Key(35)),
+        (Duration::from_millis(2003), MacroEvent::Mouse { id: 0, x: 10, y: 4, z: 0, bstate: 0x4 }),
+        (Duration::from_secs(90_000), MacroEvent::Key(-1)),
+      ],
+    }
+  }
+
+  #[test]
+  fn write_format() {
+    let mut buf = Vec::new();
+    sample().write(&mut buf).unwrap();
+    assert_eq!(String::from_utf8(buf).unwrap(), "0.000 key 97 # 'a'\n\
+                                                 0.412 key 259\n\
+                                                 1.250 char 233 # 'é'\n\
+                                                 1.300 key 35 # '#'\n\
+                                                 2.003 mouse 0 10 4 0 0x4\n\
+                                                 90000.000 key -1\n");
+  }
+
+  #[test]
+  fn round_trip() {
+    let mut buf = Vec::new();
+    sample().write(&mut buf).unwrap();
+    assert_eq!(InputMacro::read(&buf[..]).unwrap(), sample());
+    assert_eq!(InputMacro::read(&b""[..]).unwrap(), InputMacro::default());
+  }
+
+  #[test]
+  fn read_skips_comments_and_blank_lines() {
+    let text = "# recorded by hand\n\n  0.5 key 0x71  # q\n\t\n1 char 26085\n";
+    assert_eq!(InputMacro::read(text.as_bytes()).unwrap().events, vec![
+      (Duration::from_millis(500), MacroEvent::Key(0x71)),
+      (Duration::from_secs(1), MacroEvent::Char(26085)),
+    ]);
+  }
+
+  #[test]
+  fn read_errors() {
+    for bad in &["-1 key 97", "1e300 key 97", "NaN key 97", "0 key", "0 key 1 2", "0 chord 97", "0 key x",
+                 "0 mouse 0 1 2 3", "key 97"] {
+      let err = InputMacro::read(format!("0 key 1\n{}\n", bad).as_bytes()).unwrap_err();
+      assert_eq!(err.kind(), io::ErrorKind::InvalidData, "{}", bad);
+      assert!(err.to_string().starts_with("line 2: "), "{}", err);
+    }
+  }
+}
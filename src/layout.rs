@@ -0,0 +1,383 @@
+/*
+    Splitting the screen into regions.
+
+    A `Layout` divides a `Rect` into rows or columns according to a list of
+    constraints; layouts nest through `Node`. `Regions` owns one window per
+    leaf and moves/resizes them all again when the terminal is resized.
+*/
+
+use std::cmp::{max, min};
+
+use curses::{Curses, Window};
+use rect::Rect;
+use constants::{OK, ERR, KEY_RESIZE};
+#[cfg(feature = "panel")]
+use panel::wrapper::PANEL;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+  /// Regions are stacked top to bottom.
+  Rows,
+  /// Regions are placed left to right.
+  Columns,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Constraint {
+  /// Exactly this many lines or columns.
+  Fixed(i32),
+  /// A percentage of the space available.
+  Percentage(i32),
+  /// `Ratio(a, b)` takes a/b of the space available.
+  Ratio(i32, i32),
+  /// At least this much, growing into any space left over.
+  Min(i32),
+  /// Grows into any space left over, but no further than this.
+  Max(i32),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Layout {
+  direction: Direction,
+  constraints: Vec<Constraint>,
+  vmargin: i32,
+  hmargin: i32,
+  spacing: i32,
+}
+
+impl Layout {
+  pub fn new(direction: Direction, constraints: Vec<Constraint>) -> Layout {
+    Layout { direction, constraints, vmargin: 0, hmargin: 0, spacing: 0 }
+  }
+
+  pub fn rows(constraints: Vec<Constraint>) -> Layout {
+    Layout::new(Direction::Rows, constraints)
+  }
+
+  pub fn columns(constraints: Vec<Constraint>) -> Layout {
+    Layout::new(Direction::Columns, constraints)
+  }
+
+  /// Same margin on all four sides.
+  pub fn margin(self, margin: i32) -> Layout {
+    self.margins(margin, margin)
+  }
+
+  /// `vertical` lines above and below, `horizontal` columns left and right.
+  pub fn margins(mut self, vertical: i32, horizontal: i32) -> Layout {
+    self.vmargin = vertical;
+    self.hmargin = horizontal;
+    self
+  }
+
+  /// Gap left between neighbouring regions.
+  pub fn spacing(mut self, spacing: i32) -> Layout {
+    self.spacing = spacing;
+    self
+  }
+
+  pub fn direction(&self) -> Direction {
+    self.direction
+  }
+
+  pub fn constraints(&self) -> &[Constraint] {
+    &self.constraints
+  }
+
+  /// One rect per constraint, in order.
+  pub fn split(&self, area: Rect) -> Vec<Rect> {
+    let inner = area.inset(self.vmargin, self.hmargin);
+    let n = self.constraints.len() as i32;
+    let extent = match self.direction {
+      Direction::Rows => inner.lines,
+      Direction::Columns => inner.cols,
+    };
+    let spacing = if n > 1 { self.spacing * (n - 1) } else { 0 };
+    let sizes = solve(&self.constraints, max(0, extent - spacing));
+
+    let mut out = Vec::with_capacity(sizes.len());
+    let mut pos = 0;
+    for size in sizes {
+      out.push(match self.direction {
+        Direction::Rows => Rect::new(inner.y + pos, inner.x, size, inner.cols),
+        Direction::Columns => Rect::new(inner.y, inner.x + pos, inner.lines, size),
+      });
+      pos += size + self.spacing;
+    }
+    out
+  }
+}
+
+/// Sizes along one axis. The sum never exceeds `total`.
+fn solve(constraints: &[Constraint], total: i32) -> Vec<i32> {
+  let mut sizes: Vec<i32> = constraints.iter().map(|c| match *c {
+    Constraint::Fixed(n) | Constraint::Min(n) => max(0, n),
+    Constraint::Percentage(p) => max(0, total * p / 100),
+    Constraint::Ratio(a, b) => if b > 0 { max(0, total * a / b) } else { 0 },
+    Constraint::Max(_) => 0,
+  }).collect();
+
+  let used: i32 = sizes.iter().sum();
+  if used > total {
+    // Not enough room: take it back from the last regions first, leaving
+    // minimums for last.
+    let mut over = used - total;
+    for pass in 0..2 {
+      for (i, c) in constraints.iter().enumerate().rev() {
+        let is_min = matches!(*c, Constraint::Min(_));
+        if over == 0 || is_min != (pass == 1) {
+          continue;
+        }
+        let take = min(over, sizes[i]);
+        sizes[i] -= take;
+        over -= take;
+      }
+    }
+  } else if used < total {
+    let mut left = total - used;
+    let growable: Vec<usize> = constraints.iter().enumerate().filter_map(|(i, c)| match *c {
+      Constraint::Min(_) | Constraint::Max(_) => Some(i),
+      _ => None,
+    }).collect();
+
+    // Hand out the leftover evenly, one round at a time, so that regions
+    // capped by `Max` pass their share on to the others.
+    let mut open = growable;
+    while left > 0 && !open.is_empty() {
+      let share = max(1, left / open.len() as i32);
+      let mut still_open = Vec::new();
+      for &i in &open {
+        if left == 0 {
+          still_open.push(i);
+          continue;
+        }
+        let room = match constraints[i] {
+          Constraint::Max(n) => max(0, n - sizes[i]),
+          _ => left,
+        };
+        let give = min(min(share, room), left);
+        sizes[i] += give;
+        left -= give;
+        if room > give {
+          still_open.push(i);
+        }
+      }
+      open = still_open;
+    }
+
+    // Rounding from percentages and ratios; give it to the last region so
+    // the layout still fills its area.
+    if left > 0 && !constraints.iter().any(|c| matches!(*c, Constraint::Max(_))) {
+      if let Some(last) = sizes.last_mut() {
+        *last += left;
+      }
+    }
+  }
+  sizes
+}
+
+/// A layout tree: either a leaf region or a split into more nodes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Node {
+  Region,
+  Split(Layout, Vec<Node>),
+}
+
+impl Node {
+  /// Rects of all leaf regions, depth first.
+  pub fn regions(&self, area: Rect) -> Vec<Rect> {
+    let mut out = Vec::new();
+    self.collect(area, &mut out);
+    out
+  }
+
+  fn collect(&self, area: Rect, out: &mut Vec<Rect>) {
+    match *self {
+      Node::Region => out.push(area),
+      Node::Split(ref layout, ref children) => {
+        for (child, rect) in children.iter().zip(layout.split(area)) {
+          child.collect(rect, out);
+        }
+      }
+    }
+  }
+}
+
+/// The part of `rect` a window can cover on `screen`. Windows cannot be
+/// empty, nor start off the screen, so an empty rect, which a layout puts
+/// just past the bottom or right edge when it runs out of room, becomes a
+/// 1x1 window moved back onto the screen.
+fn window_rect(rect: Rect, screen: Rect) -> Rect {
+  let y = max(screen.y, min(rect.y, screen.bottom() - 1));
+  let x = max(screen.x, min(rect.x, screen.right() - 1));
+  let lines = max(1, min(rect.bottom(), screen.bottom()) - y);
+  let cols = max(1, min(rect.right(), screen.right()) - x);
+  Rect::new(y, x, lines, cols)
+}
+
+impl<'c> Window<'c> {
+  /// Resizes and moves the window to cover `rect`, as far as it is on the
+  /// screen; an empty rect leaves a 1x1 window.
+  pub fn place(&self, rect: Rect) -> i32 {
+    let r = window_rect(rect, Rect::screen());
+    if self.resize(r.lines, r.cols) == ERR {
+      return ERR;
+    }
+    self.mvwin(r.y, r.x)
+  }
+}
+
+impl Curses {
+  /// A window covering `rect`, as with `Window::place`.
+  pub fn new_window_at(&self, rect: Rect) -> Option<Window<'_>> {
+    let r = window_rect(rect, Rect::screen());
+    self.new_window(r.lines, r.cols, r.y, r.x)
+  }
+}
+
+/// Resizes a panel's window and moves the panel to cover `rect`, as with
+/// `Window::place`.
+#[cfg(feature = "panel")]
+pub fn place_panel(panel: PANEL, rect: Rect) -> i32 {
+  let r = window_rect(rect, Rect::screen());
+  if ::wresize(::panel_window(panel), r.lines, r.cols) == ERR {
+    return ERR;
+  }
+  ::move_panel(panel, r.y, r.x)
+}
+
+/// One window per leaf of a layout tree, kept in place across resizes.
+#[derive(Debug)]
+pub struct Regions<'c> {
+  root: Node,
+  rects: Vec<Rect>,
+  windows: Vec<Window<'c>>,
+}
+
+impl<'c> Regions<'c> {
+  /// Lays `root` out over the whole screen and creates its windows.
+  pub fn new(curses: &'c Curses, root: Node) -> Option<Regions<'c>> {
+    let rects = root.regions(Rect::screen());
+    let mut windows = Vec::with_capacity(rects.len());
+    for r in &rects {
+      windows.push(curses.new_window_at(*r)?);
+    }
+    Some(Regions { root, rects, windows })
+  }
+
+  pub fn len(&self) -> usize {
+    self.windows.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.windows.is_empty()
+  }
+
+  pub fn window(&self, i: usize) -> &Window<'c> {
+    &self.windows[i]
+  }
+
+  pub fn windows(&self) -> &[Window<'c>] {
+    &self.windows
+  }
+
+  /// The rect region `i` was last laid out to. It may be empty even
+  /// though its window is not.
+  pub fn rect(&self, i: usize) -> Rect {
+    self.rects[i]
+  }
+
+  /// Recomputes the layout for the current terminal size and moves every
+  /// window into place.
+  pub fn relayout(&mut self) -> i32 {
+    self.rects = self.root.regions(Rect::screen());
+    let mut ret = OK;
+    for (w, r) in self.windows.iter().zip(&self.rects) {
+      if w.place(*r) == ERR {
+        ret = ERR;
+      }
+    }
+    ret
+  }
+
+  /// Relayouts on `KEY_RESIZE`; returns whether it did.
+  pub fn handle_key(&mut self, key: i32) -> bool {
+    if key == KEY_RESIZE {
+      self.relayout();
+      true
+    } else {
+      false
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use super::Constraint::*;
+  use testing::with_screen;
+
+  #[test]
+  fn solve_fills_the_space() {
+    assert_eq!(solve(&[Fixed(3), Min(2), Percentage(50)], 20), vec![3, 7, 10]);
+    assert_eq!(solve(&[Ratio(1, 3), Ratio(1, 3), Ratio(1, 3)], 10), vec![3, 3, 4]);
+    assert_eq!(solve(&[Max(2), Min(0)], 10), vec![2, 8]);
+    assert_eq!(solve(&[Max(2), Max(3)], 10), vec![2, 3]);
+    assert_eq!(solve(&[], 10), Vec::<i32>::new());
+  }
+
+  #[test]
+  fn solve_takes_from_the_last_regions_first() {
+    assert_eq!(solve(&[Fixed(5), Fixed(5)], 7), vec![5, 2]);
+    // Minimums give way only after everything else has.
+    assert_eq!(solve(&[Min(4), Fixed(5)], 6), vec![4, 2]);
+    assert_eq!(solve(&[Min(4), Fixed(5)], 3), vec![3, 0]);
+  }
+
+  #[test]
+  fn solve_overflows_into_tiny_and_empty_areas() {
+    let constraints = [Min(3), Max(5), Fixed(2)];
+    assert_eq!(solve(&constraints, 1), vec![1, 0, 0]);
+    assert_eq!(solve(&constraints, 0), vec![0, 0, 0]);
+    assert_eq!(solve(&constraints, -4), vec![0, 0, 0]);
+    for total in 0..12 {
+      let sizes = solve(&constraints, total);
+      assert!(sizes.iter().all(|&s| s >= 0), "{:?}", sizes);
+      assert!(sizes.iter().sum::<i32>() <= total, "{:?} > {}", sizes, total);
+    }
+  }
+
+  #[test]
+  fn split_of_a_one_by_one_area() {
+    let layout = Layout::columns(vec![Min(3), Max(5)]);
+    assert_eq!(layout.split(Rect::new(0, 0, 1, 1)), vec![Rect::new(0, 0, 1, 1), Rect::new(0, 1, 1, 0)]);
+    let layout = Layout::rows(vec![Fixed(1), Fixed(1)]);
+    assert_eq!(layout.split(Rect::new(2, 3, 1, 0)), vec![Rect::new(2, 3, 1, 0), Rect::new(3, 3, 0, 0)]);
+  }
+
+  #[test]
+  fn empty_rects_become_windows_on_the_screen() {
+    let screen = Rect::new(0, 0, 24, 80);
+    assert_eq!(window_rect(Rect::new(24, 0, 0, 80), screen), Rect::new(23, 0, 1, 80));
+    assert_eq!(window_rect(Rect::new(0, 80, 24, 0), screen), Rect::new(0, 79, 24, 1));
+    assert_eq!(window_rect(Rect::new(24, 80, 0, 0), screen), Rect::new(23, 79, 1, 1));
+    assert_eq!(window_rect(Rect::new(20, 70, 10, 20), screen), Rect::new(20, 70, 4, 10));
+    assert_eq!(window_rect(Rect::new(3, 4, 5, 6), screen), Rect::new(3, 4, 5, 6));
+  }
+
+  #[test]
+  fn regions_with_empty_leaves_at_the_edges() {
+    with_screen(|curses| {
+      let (lines, cols) = (curses.lines(), curses.cols());
+      let root = Node::Split(Layout::rows(vec![Fixed(lines), Fixed(2)]), vec![
+        Node::Region,
+        Node::Split(Layout::columns(vec![Fixed(cols), Fixed(4)]), vec![Node::Region, Node::Region]),
+      ]);
+      let mut regions = Regions::new(curses, root).expect("windows for empty regions");
+      assert_eq!(regions.len(), 3);
+      assert!(regions.rect(1).is_empty());
+      assert_eq!(regions.window(2).origin(), (lines - 1, cols - 1));
+      assert_eq!(regions.relayout(), OK);
+    });
+  }
+}
@@ -22,6 +22,11 @@ pub use self::constants::*;
 pub use self::panel::wrapper::*;
 pub use self::menu::wrapper::*;
 pub use self::menu::constants::*;
+pub use self::curses::{Curses, Window};
+pub use self::session::{Session, SessionBuilder};
+pub use self::rect::Rect;
+pub use self::pad::Pad;
+pub use self::styled::{StyledText, Style, Color};
 
 pub type chtype = self::ll::chtype;
 pub type winttype = u32;
@@ -34,6 +39,28 @@ pub mod ll;
 pub mod constants;
 pub mod panel;
 pub mod menu;
+pub mod curses;
+pub mod session;
+pub mod rect;
+pub mod pad;
+pub mod layout;
+pub mod canvas;
+pub mod glyphs;
+pub mod width;
+pub mod styled;
+pub mod reflow;
+pub mod widget;
+pub mod dialog;
+pub mod chart;
+pub mod frame;
+pub mod scheduler;
+pub mod recording;
+pub mod input_macro;
+pub mod snapshot;
+#[cfg(test)]
+mod testing;
+#[cfg(feature = "bindgen")]
+pub mod bindings;
 
 trait FromCStr {
     unsafe fn from_c_str(s: *const libc::c_char) -> Self;
@@ -99,6 +126,7 @@ pub fn addstr(s: &str) -> Result<i32, std::ffi::NulError>
 { unsafe { Ok(ll::addstr(s.to_c_str()?.as_ptr())) } }
 
 
+#[cfg(ncurses_default_colors)]
 pub fn assume_default_colors(fg: i32, bg: i32) -> i32
 { unsafe { ll::assume_default_colors(fg, bg) } }
 
@@ -232,6 +260,14 @@ pub fn curs_set(visibility: CURSOR_VISIBILITY) -> Option<CURSOR_VISIBILITY>
 }
 
 
+/// The name and version of the curses library in use, such as
+/// `"ncurses 6.4.20221231"`. Unlike `version()`, this asks the library
+/// linked at run time.
+#[cfg(ncurses_curses_version)]
+pub fn curses_version() -> String
+{ unsafe { FromCStr::from_c_str(ll::curses_version()) } }
+
+
 pub fn def_prog_mode() -> i32
 { unsafe { ll::def_prog_mode() } }
 
@@ -249,7 +285,11 @@ pub fn delch() -> i32
 
 
 pub fn delscreen(s: SCREEN)
-{ unsafe { ll::delscreen(s) } }
+{
+  // A later screen may get the same stdscr address.
+  glyphs::Glyphs::redetect();
+  unsafe { ll::delscreen(s) }
+}
 
 
 pub fn delwin(w: WINDOW) -> i32
@@ -309,7 +349,7 @@ pub fn getbkgd(w: WINDOW) -> chtype
 
 
 pub fn getch() -> i32
-{ unsafe { ll::getch() } }
+{ input_macro::read_key(stdscr(), || unsafe { ll::getch() }) }
 
 #[derive(Debug)]
 pub enum WchResult {
@@ -318,7 +358,7 @@ pub enum WchResult {
 }
 
 pub fn get_wch() -> Option<WchResult> {
-    unsafe {
+    input_macro::read_wch(stdscr(), || unsafe {
         let mut x = 0;
         match ll::get_wch(&mut x) {
             OK => {
@@ -331,11 +371,11 @@ pub fn get_wch() -> Option<WchResult> {
                 None
             }
         }
-    }
+    })
 }
 
 pub fn mvget_wch(y: i32, x: i32) -> Option<WchResult> {
-    unsafe {
+    input_macro::read_wch(stdscr(), || unsafe {
         let mut result = 0;
         match ll::mvget_wch(y, x, &mut result) {
             OK => {
@@ -348,12 +388,12 @@ pub fn mvget_wch(y: i32, x: i32) -> Option<WchResult> {
                 None
             }
         }
-    }
+    })
 }
 
 #[cfg(feature = "wide")]
 pub fn wget_wch(w: WINDOW) -> Option<WchResult> {
-    unsafe {
+    input_macro::read_wch(w, || unsafe {
         let mut result = 0;
         match ll::wget_wch(w, &mut result) {
             OK => {
@@ -366,12 +406,12 @@ pub fn wget_wch(w: WINDOW) -> Option<WchResult> {
                 None
             }
         }
-    }
+    })
 }
 
 #[cfg(feature = "wide")]
 pub fn mvwget_wch(w: WINDOW, y: i32, x: i32) -> Option<WchResult> {
-    unsafe {
+    input_macro::read_wch(w, || unsafe {
         let mut result = 0;
         match ll::mvwget_wch(w, y, x, &mut result) {
             OK => {
@@ -384,10 +424,11 @@ pub fn mvwget_wch(w: WINDOW, y: i32, x: i32) -> Option<WchResult> {
                 None
             }
         }
-    }
+    })
 }
 
 pub fn unget_wch(ch: u32) -> i32 {
+    input_macro::unread(input_macro::MacroEvent::Char(ch));
     unsafe {
         ll::unget_wch(ch)
     }
@@ -617,46 +658,57 @@ pub fn is_wintouched(w: WINDOW) -> bool
 { unsafe { ll::is_wintouched(w) == TRUE } }
 
 
+#[cfg(ncurses_resize)]
 pub fn is_term_resized(lines: i32, cols: i32) -> bool
 { unsafe { ll::is_term_resized(lines, cols) == TRUE } }
 
 
+#[cfg(ncurses_window_queries)]
 pub fn is_cleared(w: WINDOW) -> bool
 { unsafe { ll::is_cleared(w) == TRUE } }
 
 
+#[cfg(ncurses_window_queries)]
 pub fn is_idcok(w: WINDOW) -> bool
 { unsafe { ll::is_idcok(w) == TRUE } }
 
 
+#[cfg(ncurses_window_queries)]
 pub fn is_idlok(w: WINDOW) -> bool
 { unsafe { ll::is_idlok(w) == TRUE } }
 
 
+#[cfg(ncurses_window_queries)]
 pub fn is_immedok(w: WINDOW) -> bool
 { unsafe { ll::is_immedok(w) == TRUE } }
 
 
+#[cfg(ncurses_window_queries)]
 pub fn is_keypad(w: WINDOW) -> bool
 { unsafe { ll::is_keypad(w) == TRUE } }
 
 
+#[cfg(ncurses_window_queries)]
 pub fn is_leaveok(w: WINDOW) -> bool
 { unsafe { ll::is_leaveok(w) == TRUE } }
 
 
+#[cfg(ncurses_window_queries)]
 pub fn is_nodelay(w: WINDOW) -> bool
 { unsafe { ll::is_nodelay(w) == TRUE } }
 
 
+#[cfg(ncurses_window_queries)]
 pub fn is_notimeout(w: WINDOW) -> bool
 { unsafe { ll::is_notimeout(w) == TRUE } }
 
 
+#[cfg(ncurses_window_queries)]
 pub fn is_scrollok(w: WINDOW) -> bool
 { unsafe { ll::is_scrollok(w) == TRUE } }
 
 
+#[cfg(ncurses_window_queries)]
 pub fn is_syncok(w: WINDOW) -> bool
 { unsafe { ll::is_syncok(w) == TRUE }}
 
@@ -742,7 +794,7 @@ pub fn mvderwin(w: WINDOW, y: i32, x: i32) -> i32
 
 
 pub fn mvgetch(y: i32, x: i32) -> i32
-{ unsafe { ll::mvgetch(y, x) } }
+{ input_macro::read_key(stdscr(), || unsafe { ll::mvgetch(y, x) }) }
 
 
 pub fn mvgetnstr(y: i32, x: i32, s: &mut String, n: i32) -> i32
@@ -856,7 +908,7 @@ pub fn mvwdelch(w: WINDOW, y: i32, x: i32) -> i32
 
 
 pub fn mvwgetch(w: WINDOW, y: i32, x: i32) -> i32
-{ unsafe { ll::mvwgetch(w, y, x) } }
+{ input_macro::read_key(w, || unsafe { ll::mvwgetch(w, y, x) }) }
 
 
 pub fn mvwgetnstr(w: WINDOW, y: i32, x: i32, s: &mut String, n: i32) -> i32
@@ -1143,10 +1195,12 @@ pub fn reset_shell_mode() -> i32
 { unsafe { ll::reset_shell_mode() } }
 
 
+#[cfg(ncurses_resize)]
 pub fn resizeterm(lines: i32, cols: i32) -> i32
 { unsafe { ll::resizeterm(lines, cols) } }
 
 
+#[cfg(ncurses_resize)]
 pub fn resize_term(lines: i32, cols: i32) -> i32
 { unsafe { ll::resize_term(lines, cols) } }
 
@@ -1205,9 +1259,11 @@ pub fn setscrreg(top: i32, bot: i32) -> i32
 pub fn set_term(s: SCREEN) -> SCREEN
 { unsafe { ll::set_term(s) } }
 
+#[cfg(ncurses_escdelay)]
 pub fn set_escdelay(size: i32) -> i32
 { unsafe { ll::set_escdelay(size) } }
 
+#[cfg(ncurses_tabsize)]
 pub fn set_tabsize(size: i32) -> i32
 { unsafe { ll::set_tabsize(size) } }
 
@@ -1340,7 +1396,10 @@ pub fn tparm(s: &str) -> Result<String, std::ffi::NulError>
 
 
 pub fn ungetch(ch: i32) -> i32
-{ unsafe { ll::ungetch(ch) } }
+{
+  input_macro::unread(input_macro::MacroEvent::Key(ch));
+  unsafe { ll::ungetch(ch) }
+}
 
 
 pub fn untouchwin(w: WINDOW) -> i32
@@ -1351,10 +1410,67 @@ pub fn use_env(f: bool)
 { unsafe { ll::use_env(f as ll::c_bool) } }
 
 
+#[cfg(ncurses_default_colors)]
 pub fn use_default_colors() -> i32
 { unsafe { ll::use_default_colors() } }
 
 
+/*
+ * Thread support. The callback runs while ncurses holds its own lock on the
+ * window or screen; a panic inside it is carried across the C frames and
+ * resumed once ncurses has released that lock.
+ */
+
+#[cfg(feature = "threaded")]
+struct UseCallback<F> {
+  f: Option<F>,
+  panic: Option<Box<dyn std::any::Any + Send>>,
+}
+
+#[cfg(feature = "threaded")]
+extern "C" fn use_trampoline<T, F: FnOnce(T) -> i32>(handle: T, data: *mut libc::c_void) -> libc::c_int
+{
+  let cb = unsafe { &mut *(data as *mut UseCallback<F>) };
+  let f = match cb.f.take() {
+    Some(f) => f,
+    None => return ERR,
+  };
+  match std::panic::catch_unwind(std::panic::AssertUnwindSafe(move || f(handle))) {
+    Ok(ret) => ret,
+    Err(payload) => {
+      cb.panic = Some(payload);
+      ERR
+    }
+  }
+}
+
+#[cfg(feature = "threaded")]
+pub fn use_window<F: FnOnce(WINDOW) -> i32>(w: WINDOW, f: F) -> i32
+{
+  let mut cb = UseCallback { f: Some(f), panic: None };
+  let ret = unsafe {
+    ll::use_window(w, use_trampoline::<WINDOW, F>, &mut cb as *mut UseCallback<F> as *mut libc::c_void)
+  };
+  if let Some(payload) = cb.panic {
+    std::panic::resume_unwind(payload);
+  }
+  ret
+}
+
+#[cfg(feature = "threaded")]
+pub fn use_screen<F: FnOnce(SCREEN) -> i32>(s: SCREEN, f: F) -> i32
+{
+  let mut cb = UseCallback { f: Some(f), panic: None };
+  let ret = unsafe {
+    ll::use_screen(s, use_trampoline::<SCREEN, F>, &mut cb as *mut UseCallback<F> as *mut libc::c_void)
+  };
+  if let Some(payload) = cb.panic {
+    std::panic::resume_unwind(payload);
+  }
+  ret
+}
+
+
 pub fn vidattr(attrs: chtype) -> i32
 { unsafe { ll::vidattr(attrs) } }
 
@@ -1464,7 +1580,7 @@ pub fn werase(w: WINDOW) -> i32
 
 
 pub fn wgetch(w: WINDOW) -> i32
-{ unsafe { ll::wgetch(w) } }
+{ input_macro::read_key(w, || unsafe { ll::wgetch(w) }) }
 
 
 pub fn wgetnstr(w: WINDOW, s: &mut String, n: i32) -> i32
@@ -1696,14 +1812,21 @@ pub fn wvline(w: WINDOW, ch: chtype, n: i32) -> i32
 { unsafe { ll::wvline(w, ch, n) } }
 
 
+#[cfg(ncurses_window_queries)]
 pub fn wgetparent(w: WINDOW) -> WINDOW
 { unsafe { ll::wgetparent(w) } }
 
 
+#[cfg(ncurses_window_queries)]
 pub fn wgetscrreg(w: WINDOW, top: &mut i32, bot: &mut i32) -> i32
 { unsafe { ll::wgetscrreg(w, &mut*top as *mut i32, &mut*bot as *mut i32) } }
 
 
+#[cfg(ncurses_wgetdelay)]
+pub fn wgetdelay(w: WINDOW) -> i32
+{ unsafe { ll::wgetdelay(w) } }
+
+
 pub fn COLOR_PAIR(n: i16) -> attr_t {
     unsafe {
         ll::COLOR_PAIR(n as i32) as attr_t
@@ -1738,7 +1861,11 @@ pub fn getsyx(y: &mut i32, x: &mut i32)
   {
     if newscr() != ptr::null_mut()
     {
-      if ll::is_leaveok(newscr()) == TRUE
+      #[cfg(ncurses_window_queries)]
+      let leaveok = ll::is_leaveok(newscr()) == TRUE;
+      #[cfg(not(ncurses_window_queries))]
+      let leaveok = false;
+      if leaveok
       {
         *x = -1 as i32;
         *y = -1 as i32;
@@ -1779,18 +1906,37 @@ pub fn KEY_F(n: u8) -> i32
  * Added mouse support
  */
 
+#[cfg(ncurses_mouse)]
 pub fn has_mouse() -> bool
 { unsafe { ll::has_mouse() == TRUE } }
 
+#[cfg(ncurses_mouse)]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
 pub fn getmouse(event: *mut MEVENT) -> i32
-{ unsafe { ll::getmouse(event) } }
+{
+  let ret = unsafe { ll::getmouse(event) };
+  if ret == OK && !event.is_null() {
+    input_macro::read_mouse(unsafe { &*event });
+  }
+  ret
+}
 
+#[cfg(ncurses_mouse)]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
 pub fn ungetmouse(event: *mut MEVENT) -> i32
-{ unsafe { ll::ungetmouse(event) } }
+{
+  if !event.is_null() {
+    let e = unsafe { &*event };
+    input_macro::unread(input_macro::MacroEvent::Mouse { id: e.id, x: e.x, y: e.y, z: e.z, bstate: e.bstate });
+  }
+  unsafe { ll::ungetmouse(event) }
+}
 
+#[cfg(ncurses_mouse)]
 pub fn mouseinterval(n: i32) -> i32
 { unsafe { ll::mouseinterval(n) } }
 
+#[cfg(ncurses_mouse)]
 pub fn mousemask(newmask: mmask_t, oldmask: Option<&mut mmask_t>) -> mmask_t
 {
     match oldmask {
@@ -1799,35 +1945,69 @@ pub fn mousemask(newmask: mmask_t, oldmask: Option<&mut mmask_t>) -> mmask_t
     }
 }
 
+#[cfg(ncurses_mouse)]
 pub fn wenclose(w: WINDOW, y: i32, x: i32) -> bool
 { unsafe { ll::wenclose(w, y as libc::c_int, x as libc::c_int) == TRUE } }
 
+#[cfg(ncurses_mouse)]
 pub fn wmouse_trafo(w: WINDOW, y: &mut[i32], x: &mut[i32], to_screen: bool) -> bool
 { unsafe { ll::wmouse_trafo(w, y.as_mut_ptr(), x.as_mut_ptr(), to_screen as ll::c_bool) == TRUE } }
 
+#[cfg(ncurses_mouse)]
 pub fn mouse_trafo(y: &mut[i32], x: &mut[i32], to_screen: bool) -> bool
 { unsafe { ll::mouse_trafo(y.as_mut_ptr(), x.as_mut_ptr(), to_screen as ll::c_bool) == TRUE } }
 
-#[cfg(feature = "extended_colors")]
+#[cfg(all(feature = "extended_colors", ncurses_extended_colors))]
 pub fn init_extended_color(color: i32, r: i32, g: i32, b: i32) -> i32 {
     unsafe { ll::init_extended_color(color, r, g, b) }
 }
 
-#[cfg(feature = "extended_colors")]
+#[cfg(all(feature = "extended_colors", ncurses_extended_colors))]
 pub fn init_extended_pair(color: i32, f: i32, b: i32) -> i32 {
     unsafe { ll::init_extended_pair(color, f, b) }
 }
 
-#[cfg(feature = "extended_colors")]
+#[cfg(all(feature = "extended_colors", ncurses_extended_colors))]
 pub fn extended_color_content(color: i32, r: &mut i32, g: &mut i32, b: &mut i32) -> i32 {
     unsafe { ll::extended_color_content(color, r, g, b) }
 }
 
-#[cfg(feature = "extended_colors")]
+#[cfg(all(feature = "extended_colors", ncurses_extended_colors))]
 pub fn extended_pair_content(pair: i32, f: &mut i32, b: &mut i32) -> i32 {
     unsafe { ll::extended_pair_content(pair, f, b) }
 }
 
+/// A curses version, as `major.minor.patch`; ncurses uses the release date
+/// (`YYYYMMDD`) as the patch level.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Version {
+    pub major: i32,
+    pub minor: i32,
+    pub patch: i32,
+}
+
+impl std::fmt::Display for Version {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// The version of the ncurses headers the crate was built against, from
+/// `NCURSES_VERSION_MAJOR`, `_MINOR` and `_PATCH`. `None` for other
+/// curses implementations, which do not define them. See also
+/// `NCURSES_VERSION` and, for the library loaded at run time,
+/// `curses_version`.
+pub fn version() -> Option<Version> {
+    if NCURSES_VERSION.is_empty() {
+        return None;
+    }
+    Some(Version {
+        major: NCURSES_VERSION_MAJOR,
+        minor: NCURSES_VERSION_MINOR,
+        patch: NCURSES_VERSION_PATCH,
+    })
+}
+
 
 pub fn ACS_ULCORNER() -> crate::ll::chtype {
     unsafe { crate::ll::impl_ACS_ULCORNER() }
@@ -43,12 +43,18 @@ pub type va_list = *mut u8;
 #[derive(Debug, Clone, Copy)]
 pub struct MEVENT { pub id: c_short, pub x: c_int, pub y: c_int, pub z: c_int, pub bstate: mmask_t}
 
+/// A cell of a wide build, as `ncursesw` lays it out; read with `getcchar`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct cchar_t { pub attr: attr_t, pub chars: [::libc::wchar_t; 5], pub ext_color: c_int }
+
 extern {
     pub fn addch(_:chtype) -> c_int;
     pub fn addchnstr(_:*const chtype, _:c_int) -> c_int;
     pub fn addchstr(_:*const chtype) -> c_int;
     pub fn addnstr(_:*const c_char, _:c_int) -> c_int;
     pub fn addstr(_:*const c_char) -> c_int;
+    #[cfg(ncurses_default_colors)]
     pub fn assume_default_colors(_:c_int, _:c_int) -> c_int;
     pub fn attroff(_:NCURSES_ATTR_T) -> c_int;
     pub fn attron(_:NCURSES_ATTR_T) -> c_int;
@@ -75,6 +81,8 @@ extern {
     pub fn COLOR_PAIR(_:c_int) -> c_int;
     pub fn copywin(_:WINDOW,_:WINDOW,_:c_int,_:c_int,_:c_int,_:c_int,_:c_int,_:c_int,_:c_int) -> c_int;
     pub fn curs_set(_:c_int) -> c_int;
+    #[cfg(ncurses_curses_version)]
+    pub fn curses_version() -> *const c_char;
     pub fn def_prog_mode() -> c_int;
     pub fn def_shell_mode() -> c_int;
     pub fn delay_output(_:c_int) -> c_int;
@@ -122,6 +130,7 @@ extern {
     pub fn intrflush(_:WINDOW,_:c_bool) -> c_int;
     pub fn isendwin() -> c_bool;
     pub fn is_linetouched(_:WINDOW,_:c_int) -> c_bool;
+    #[cfg(ncurses_resize)]
     pub fn is_term_resized(_:c_int, _:c_int) -> c_bool;
     pub fn is_wintouched(_:WINDOW) -> c_bool;
     pub fn keyname(_:c_int) -> *const c_char;
@@ -170,6 +179,8 @@ extern {
     pub fn mvwin(_:WINDOW,_:c_int,_:c_int) -> c_int;
     pub fn mvwinch(_:WINDOW, _:c_int, _:c_int) -> chtype;
     pub fn mvwinchnstr(_:WINDOW, _:c_int, _:c_int, _:chtype_p, _:c_int) -> c_int;
+    pub fn mvwin_wch(_:WINDOW, _:c_int, _:c_int, _:*mut cchar_t) -> c_int;
+    pub fn getcchar(_:*const cchar_t, _:*mut ::libc::wchar_t, _:attr_t_p, _:short_p, _:*mut c_void) -> c_int;
     pub fn mvwinchstr(_:WINDOW, _:c_int, _:c_int, _:chtype_p) -> c_int;
     pub fn mvwinnstr(_:WINDOW, _:c_int, _:c_int, _:char_p, _:c_int) -> c_int;
     pub fn mvwinsch(_:WINDOW, _:c_int, _:c_int, _:chtype) -> c_int;
@@ -206,7 +217,9 @@ extern {
     pub fn raw() -> c_int;
     pub fn redrawwin(_:WINDOW) -> c_int;
     pub fn refresh() -> c_int;
+    #[cfg(ncurses_resize)]
     pub fn resizeterm(_:c_int, _:c_int) -> c_int;
+    #[cfg(ncurses_resize)]
     pub fn resize_term(_:c_int, _:c_int) -> c_int;
     pub fn resetty() -> c_int;
     pub fn reset_prog_mode() -> c_int;
@@ -223,7 +236,9 @@ extern {
     pub fn scr_set(_:char_p) -> c_int;
     pub fn setscrreg(_:c_int,_:c_int) -> c_int;
     pub fn set_term(_:SCREEN) -> SCREEN;
+    #[cfg(ncurses_escdelay)]
     pub fn set_escdelay(_:c_int) -> c_int;
+    #[cfg(ncurses_tabsize)]
     pub fn set_tabsize(_:c_int) -> c_int;
     pub fn slk_attroff(_:chtype) -> c_int;
     pub fn slk_attr_off(_:attr_t, _:void_p) -> c_int;
@@ -256,6 +271,7 @@ extern {
     pub fn ungetch(_:c_int) -> c_int;
     pub fn untouchwin(_:WINDOW) -> c_int;
     pub fn use_env(_:c_bool);
+    #[cfg(ncurses_default_colors)]
     pub fn use_default_colors() -> c_int;
     pub fn vidattr(_:chtype) -> c_int;
     //  fn vidputs(_:chtype, extern  fn f(c_int) -> c_int) -> c_int;
@@ -353,28 +369,52 @@ extern {
      * These extensions provide access to information stored in the WINDOW even
      * when NCURSES_OPAQUE is set:
      */
+    #[cfg(ncurses_window_queries)]
     pub fn wgetparent(_:WINDOW) -> WINDOW;
+    #[cfg(ncurses_window_queries)]
     pub fn is_cleared(_:WINDOW) -> c_bool;
+    #[cfg(ncurses_window_queries)]
     pub fn is_idcok(_:WINDOW) -> c_bool;
+    #[cfg(ncurses_window_queries)]
     pub fn is_idlok(_:WINDOW) -> c_bool;
+    #[cfg(ncurses_window_queries)]
     pub fn is_immedok(_:WINDOW) -> c_bool;
+    #[cfg(ncurses_window_queries)]
     pub fn is_keypad(_:WINDOW) -> c_bool;
+    #[cfg(ncurses_window_queries)]
     pub fn is_leaveok(_:WINDOW) -> c_bool;
+    #[cfg(ncurses_window_queries)]
     pub fn is_nodelay(_:WINDOW) -> c_bool;
+    #[cfg(ncurses_window_queries)]
     pub fn is_notimeout(_:WINDOW) -> c_bool;
+    #[cfg(ncurses_window_queries)]
     pub fn is_scrollok(_:WINDOW) -> c_bool;
+    #[cfg(ncurses_window_queries)]
     pub fn is_syncok(_:WINDOW) -> c_bool;
+    #[cfg(ncurses_window_queries)]
     pub fn wgetscrreg(_:WINDOW, _:*mut c_int, _:*mut c_int) -> c_int;
+    #[cfg(ncurses_wgetdelay)]
+    pub fn wgetdelay(_:WINDOW) -> c_int;
     /*
      * Added mouse support
      */
+    #[cfg(ncurses_mouse)]
     pub fn has_mouse() -> c_bool;
+    /* PDCurses' own `getmouse` predates the ncurses one. */
+    #[cfg(ncurses_mouse)]
+    #[cfg_attr(curses_backend = "pdcursesmod_vt", link_name = "nc_getmouse")]
     pub fn getmouse(_:*mut MEVENT) -> c_int;
+    #[cfg(ncurses_mouse)]
     pub fn ungetmouse(_:*mut MEVENT) -> c_int;
+    #[cfg(ncurses_mouse)]
     pub fn mousemask(_:mmask_t,_:*mut mmask_t) -> mmask_t;
+    #[cfg(ncurses_mouse)]
     pub fn wenclose(_:WINDOW,_:c_int,_:c_int) -> c_bool;
+    #[cfg(ncurses_mouse)]
     pub fn mouseinterval(_:c_int) -> c_int;
+    #[cfg(ncurses_mouse)]
     pub fn wmouse_trafo(_:WINDOW,_:*mut c_int,_:*mut c_int,_:c_bool) -> c_bool;
+    #[cfg(ncurses_mouse)]
     pub fn mouse_trafo(_:*mut c_int,_:*mut c_int,_:c_bool) -> c_bool;
 
     pub fn impl_ACS_ULCORNER() -> chtype;
@@ -423,8 +463,18 @@ extern {
     
 }
 
+/// Callbacks run under the curses lock. Requires a thread-aware ncurses (`ncursest`).
+pub type NCURSES_WINDOW_CB = extern "C" fn(WINDOW, *mut c_void) -> c_int;
+pub type NCURSES_SCREEN_CB = extern "C" fn(SCREEN, *mut c_void) -> c_int;
+
+#[cfg(feature = "threaded")]
+extern "C" {
+    pub fn use_window(_:WINDOW, _:NCURSES_WINDOW_CB, _:*mut c_void) -> c_int;
+    pub fn use_screen(_:SCREEN, _:NCURSES_SCREEN_CB, _:*mut c_void) -> c_int;
+}
+
 /// Extended color support. Requires ncurses6.
-#[cfg(feature = "extended_colors")]
+#[cfg(all(feature = "extended_colors", ncurses_extended_colors))]
 extern {
     pub fn init_extended_color(_: c_int, _: c_int, _: c_int, _: c_int) -> c_int;
     pub fn init_extended_pair(_: c_int, _: c_int, _: c_int) -> c_int;
@@ -1,8 +1,8 @@
 #include <menu.h>
 
-#define PCONST(ty, NAME) printf("pub const " #NAME ": " #ty " = %d;\n", NAME)
+#include "../genconstants.h"
 
-int main() {
+BEGIN
 	PCONST(i32, O_ONEVALUE);
 	PCONST(i32, O_SHOWDESC);
 	PCONST(i32, O_ROWMAJOR);
@@ -37,4 +37,4 @@ int main() {
 #ifdef MAX_MENU_COMMAND
 	PCONST(i32, MAX_MENU_COMMAND);
 #endif
-}
+END
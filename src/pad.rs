@@ -0,0 +1,225 @@
+/*
+    A scrollable view onto a pad.
+
+    The pad holds the full content; the viewport is the part of the screen
+    it is shown in. `prefresh` needs six coordinates that must stay within
+    both the pad and the screen, so they are derived here from the scroll
+    offset and clipped against the current terminal size on every refresh.
+*/
+
+use std::cmp::{max, min};
+
+use curses::{Curses, Window};
+use rect::Rect;
+use constants::{OK, ERR};
+use {ACS_VLINE, ACS_HLINE, ACS_CKBOARD};
+
+#[derive(Debug)]
+pub struct Pad<'c> {
+  curses: &'c Curses,
+  pad: Window<'c>,
+  lines: i32,
+  cols: i32,
+  viewport: Rect,
+  top: i32,
+  left: i32,
+  scrollbars: bool,
+  vbar: Option<(Rect, Window<'c>)>,
+  hbar: Option<(Rect, Window<'c>)>,
+}
+
+impl<'c> Pad<'c> {
+  /// Creates a pad of `lines` x `cols` shown in `viewport`.
+  pub fn new(curses: &'c Curses, lines: i32, cols: i32, viewport: Rect) -> Option<Pad<'c>> {
+    let pad = curses.new_pad(max(1, lines), max(1, cols))?;
+    Some(Pad {
+      curses,
+      pad,
+      lines: max(1, lines),
+      cols: max(1, cols),
+      viewport,
+      top: 0,
+      left: 0,
+      scrollbars: false,
+      vbar: None,
+      hbar: None,
+    })
+  }
+
+  /// The pad itself, for drawing content into.
+  pub fn window(&self) -> &Window<'c> {
+    &self.pad
+  }
+
+  /// `(lines, cols)` of the content.
+  pub fn content_size(&self) -> (i32, i32) {
+    (self.lines, self.cols)
+  }
+
+  /// Grows or shrinks the content, keeping what still fits.
+  pub fn resize_content(&mut self, lines: i32, cols: i32) -> i32 {
+    let (lines, cols) = (max(1, lines), max(1, cols));
+    if self.pad.resize(lines, cols) == ERR {
+      return ERR;
+    }
+    self.lines = lines;
+    self.cols = cols;
+    self.clamp();
+    OK
+  }
+
+  pub fn viewport(&self) -> Rect {
+    self.viewport
+  }
+
+  pub fn set_viewport(&mut self, viewport: Rect) {
+    self.viewport = viewport;
+    self.clamp();
+  }
+
+  /// Draws scrollbars along the right and bottom edges of the viewport
+  /// whenever the content does not fit.
+  pub fn set_scrollbars(&mut self, on: bool) {
+    self.scrollbars = on;
+    self.clamp();
+  }
+
+  /// First content line and column shown, as `(y, x)`.
+  pub fn offset(&self) -> (i32, i32) {
+    (self.top, self.left)
+  }
+
+  /// The part of the screen the content is actually drawn to, after
+  /// clipping to the terminal and making room for scrollbars.
+  pub fn visible(&self) -> Rect {
+    self.layout().0
+  }
+
+  pub fn scroll_to(&mut self, y: i32, x: i32) {
+    self.top = y;
+    self.left = x;
+    self.clamp();
+  }
+
+  pub fn scroll_by(&mut self, dy: i32, dx: i32) {
+    let (y, x) = (self.top + dy, self.left + dx);
+    self.scroll_to(y, x);
+  }
+
+  /// Scrolls just far enough for content position `(y, x)` to be shown.
+  pub fn ensure_visible(&mut self, y: i32, x: i32) {
+    let visible = self.visible();
+    if y < self.top {
+      self.top = y;
+    } else if y >= self.top + visible.lines {
+      self.top = y - visible.lines + 1;
+    }
+    if x < self.left {
+      self.left = x;
+    } else if x >= self.left + visible.cols {
+      self.left = x - visible.cols + 1;
+    }
+    self.clamp();
+  }
+
+  pub fn noutrefresh(&mut self) -> i32 {
+    self.clamp();
+    let (visible, vbar, hbar) = self.layout();
+    if visible.is_empty() {
+      return OK;
+    }
+    self.draw_scrollbars(vbar, hbar);
+    ::pnoutrefresh(self.pad.raw(), self.top, self.left,
+                   visible.y, visible.x,
+                   visible.bottom() - 1, visible.right() - 1)
+  }
+
+  pub fn refresh(&mut self) -> i32 {
+    if self.noutrefresh() == ERR {
+      return ERR;
+    }
+    self.curses.doupdate()
+  }
+
+  fn clamp(&mut self) {
+    let visible = self.visible();
+    self.top = max(0, min(self.top, self.lines - visible.lines));
+    self.left = max(0, min(self.left, self.cols - visible.cols));
+  }
+
+  /// Content area plus the vertical and horizontal scrollbar areas.
+  fn layout(&self) -> (Rect, Option<Rect>, Option<Rect>) {
+    let area = self.viewport.intersect(&Rect::screen());
+    if !self.scrollbars || area.is_empty() {
+      return (area, None, None);
+    }
+
+    // Each scrollbar takes space from the other direction, so one can
+    // make the other necessary.
+    let mut need_v = self.lines > area.lines;
+    let mut need_h = self.cols > area.cols;
+    need_v = need_v || (need_h && self.lines > area.lines - 1);
+    need_h = need_h || (need_v && self.cols > area.cols - 1);
+
+    let mut content = area;
+    if need_v {
+      content.cols -= 1;
+    }
+    if need_h {
+      content.lines -= 1;
+    }
+    let vbar = if need_v && content.lines > 0 {
+      Some(Rect::new(area.y, area.right() - 1, content.lines, 1))
+    } else {
+      None
+    };
+    let hbar = if need_h && content.cols > 0 {
+      Some(Rect::new(area.bottom() - 1, area.x, 1, content.cols))
+    } else {
+      None
+    };
+    (content, vbar, hbar)
+  }
+
+  fn draw_scrollbars(&mut self, vbar: Option<Rect>, hbar: Option<Rect>) {
+    let curses = self.curses;
+    place_bar(curses, &mut self.vbar, vbar);
+    place_bar(curses, &mut self.hbar, hbar);
+
+    if let Some((ref r, ref w)) = self.vbar {
+      let (start, len) = thumb(r.lines, r.lines, self.lines, self.top);
+      for i in 0..r.lines {
+        let ch = if i >= start && i < start + len { ACS_CKBOARD() } else { ACS_VLINE() };
+        w.mvaddch(i, 0, ch);
+      }
+      w.noutrefresh();
+    }
+    if let Some((ref r, ref w)) = self.hbar {
+      let (start, len) = thumb(r.cols, r.cols, self.cols, self.left);
+      for i in 0..r.cols {
+        let ch = if i >= start && i < start + len { ACS_CKBOARD() } else { ACS_HLINE() };
+        w.mvaddch(0, i, ch);
+      }
+      w.noutrefresh();
+    }
+  }
+}
+
+/// Makes sure `slot` holds a window covering `want`, or nothing.
+fn place_bar<'c>(curses: &'c Curses, slot: &mut Option<(Rect, Window<'c>)>, want: Option<Rect>) {
+  let current = slot.as_ref().map(|&(r, _)| r);
+  if current == want {
+    return;
+  }
+  *slot = want.and_then(|r| curses.new_window(r.lines, r.cols, r.y, r.x).map(|w| (r, w)));
+}
+
+/// Start and length of a scrollbar thumb on a track of `track` cells.
+fn thumb(track: i32, visible: i32, total: i32, offset: i32) -> (i32, i32) {
+  if total <= visible || track <= 0 {
+    return (0, track);
+  }
+  let len = max(1, min(track, track * visible / total));
+  let start = (track - len) * offset / (total - visible);
+  (min(start, track - len), len)
+}
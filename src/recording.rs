@@ -0,0 +1,846 @@
+/*
+    Recording curses sessions as asciicast v2.
+
+    A `Recorder` starts a screen with `newterm` on the slave side of a
+    pseudo-terminal, so everything ncurses writes, and everything it
+    reads, passes through the master side, where two threads timestamp it
+    on the way: the output is passed on to the real terminal, and the
+    keys typed there are passed in. The result is a `Cast`, which reads
+    and writes asciinema's asciicast v2 format (a JSON header line, then
+    one `[time, code, data]` array per event).
+
+    `Recorder::replay` runs a session the same way but types the input of
+    a recorded cast instead of reading the keyboard, and writes the output
+    wherever it is told: to stdout, or, for regression tests, into a
+    `Vec<u8>` or `io::sink()` with no terminal at all. This crate has no
+    terminal emulator to turn that output back into a screen; inspect the
+    windows themselves, or feed `Cast::output` to one. `Cast::play` shows a
+    recording on a terminal at its original pace.
+*/
+
+use std::ffi::CStr;
+use std::fmt;
+use std::io::{self, BufRead, Write};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use libc::{self, c_char, c_int, c_void};
+
+use ll::{SCREEN, FILE_p};
+
+/// How often the pump threads look at the stop flag.
+const POLL_MS: c_int = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventKind {
+  /// Written by curses to the terminal: `"o"`.
+  Output,
+  /// Read by curses from the terminal: `"i"`.
+  Input,
+  /// The terminal changed size, as `"COLSxLINES"`: `"r"`.
+  Resize,
+  /// A bookmark: `"m"`.
+  Marker,
+}
+
+impl EventKind {
+  fn code(self) -> &'static str {
+    match self {
+      EventKind::Output => "o",
+      EventKind::Input => "i",
+      EventKind::Resize => "r",
+      EventKind::Marker => "m",
+    }
+  }
+
+  fn from_code(code: &str) -> Option<EventKind> {
+    match code {
+      "o" => Some(EventKind::Output),
+      "i" => Some(EventKind::Input),
+      "r" => Some(EventKind::Resize),
+      "m" => Some(EventKind::Marker),
+      _ => None,
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Event {
+  /// Seconds since the recording started.
+  pub time: f64,
+  pub kind: EventKind,
+  pub data: String,
+}
+
+/// A recorded session.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Cast {
+  pub width: u32,
+  pub height: u32,
+  /// Start of the recording, in seconds since the Unix epoch.
+  pub timestamp: Option<u64>,
+  pub title: Option<String>,
+  /// The `TERM` the session ran with.
+  pub term: Option<String>,
+  pub events: Vec<Event>,
+}
+
+impl Cast {
+  /// Everything curses wrote, in one string.
+  pub fn output(&self) -> String {
+    self.events.iter().filter(|e| e.kind == EventKind::Output).map(|e| e.data.as_str()).collect()
+  }
+
+  /// Writes the asciicast v2 form. Events must have finite times.
+  pub fn write<W: Write>(&self, mut w: W) -> io::Result<()> {
+    let mut header = format!("{{\"version\": 2, \"width\": {}, \"height\": {}", self.width, self.height);
+    if let Some(t) = self.timestamp {
+      header.push_str(&format!(", \"timestamp\": {}", t));
+    }
+    if let Some(ref title) = self.title {
+      header.push_str(&format!(", \"title\": {}", json_string(title)));
+    }
+    if let Some(ref term) = self.term {
+      header.push_str(&format!(", \"env\": {{\"TERM\": {}}}", json_string(term)));
+    }
+    writeln!(w, "{}}}", header)?;
+    for e in &self.events {
+      if !e.time.is_finite() {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "event time is not a finite number"));
+      }
+      writeln!(w, "[{:.6}, \"{}\", {}]", e.time, e.kind.code(), json_string(&e.data))?;
+    }
+    w.flush()
+  }
+
+  /// Reads the asciicast v2 form. Events of unknown types are skipped.
+  pub fn read<R: BufRead>(r: R) -> io::Result<Cast> {
+    let mut lines = r.lines();
+    let header = match lines.next() {
+      Some(line) => Json::parse(&line?)?,
+      None => return Err(invalid("empty cast")),
+    };
+    if header.get("version").and_then(Json::as_f64) != Some(2.0) {
+      return Err(invalid("not an asciicast v2 file"));
+    }
+    let mut cast = Cast {
+      width: header.get("width").and_then(Json::as_f64).unwrap_or(80.0) as u32,
+      height: header.get("height").and_then(Json::as_f64).unwrap_or(24.0) as u32,
+      timestamp: header.get("timestamp").and_then(Json::as_f64).map(|t| t as u64),
+      title: header.get("title").and_then(Json::as_str).map(String::from),
+      term: header.get("env").and_then(|e| e.get("TERM")).and_then(Json::as_str).map(String::from),
+      events: Vec::new(),
+    };
+    for line in lines {
+      let line = line?;
+      if line.trim().is_empty() {
+        continue;
+      }
+      let event = match Json::parse(&line)? {
+        Json::Arr(items) => items,
+        _ => return Err(invalid("event is not an array")),
+      };
+      let (time, code, data) = match (event.first().and_then(Json::as_f64),
+                                      event.get(1).and_then(Json::as_str),
+                                      event.get(2).and_then(Json::as_str)) {
+        (Some(t), Some(c), Some(d)) => (t, c, d),
+        _ => return Err(invalid("event is not [time, code, data]")),
+      };
+      if !time.is_finite() {
+        return Err(invalid("event time is not a finite number"));
+      }
+      if let Some(kind) = EventKind::from_code(code) {
+        cast.events.push(Event { time, kind, data: data.to_string() });
+      }
+    }
+    Ok(cast)
+  }
+
+  /// Writes the output to `out` at its recorded pace, `speed` times as fast.
+  /// Output too far off to be scheduled is left out.
+  pub fn play<W: Write>(&self, mut out: W, speed: f64) -> io::Result<()> {
+    let start = Instant::now();
+    for e in self.events.iter().filter(|e| e.kind == EventKind::Output) {
+      let due = match due_at(start, e.time, speed) {
+        Some(due) => due,
+        None => continue,
+      };
+      let now = Instant::now();
+      if due > now {
+        thread::sleep(due - now);
+      }
+      out.write_all(e.data.as_bytes())?;
+      out.flush()?;
+    }
+    Ok(())
+  }
+}
+
+/// When an event `time` seconds into a cast is due, played back `speed`
+/// times as fast from `start`; `None` if that is too far off to represent.
+fn due_at(start: Instant, time: f64, speed: f64) -> Option<Instant> {
+  Duration::try_from_secs_f64(time.max(0.0) / speed.max(1e-6)).ok().and_then(|d| start.checked_add(d))
+}
+
+/// The events recorded so far, shared with the pump threads.
+#[derive(Debug, Clone)]
+struct Log {
+  start: Instant,
+  events: Arc<Mutex<Vec<Event>>>,
+}
+
+impl Log {
+  fn push(&self, kind: EventKind, data: String) {
+    let time = self.start.elapsed().as_secs_f64();
+    self.events.lock().unwrap_or_else(|e| e.into_inner()).push(Event { time, kind, data });
+  }
+}
+
+/// A screen running on a pseudo-terminal, with its traffic recorded.
+pub struct Recorder {
+  screen: SCREEN,
+  master: c_int,
+  slave_out: FILE_p,
+  slave_in: FILE_p,
+  /// The real terminal's modes, restored when done.
+  saved_tty: Option<libc::termios>,
+  log: Log,
+  stop: Arc<AtomicBool>,
+  threads: Vec<JoinHandle<()>>,
+  cast: Cast,
+  done: bool,
+}
+
+impl fmt::Debug for Recorder {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.debug_struct("Recorder")
+      .field("screen", &self.screen)
+      .field("master", &self.master)
+      .field("raw_tty", &self.saved_tty.is_some())
+      .field("done", &self.done)
+      .finish()
+  }
+}
+
+impl Recorder {
+  /// Starts recording a new screen for terminal type `ty` (`TERM` if
+  /// `None`) on the terminal of stdin/stdout, which is put in raw mode
+  /// until the recording ends. The new screen is made current.
+  pub fn start(ty: Option<&str>) -> io::Result<Recorder> {
+    let (lines, cols) = winsize(libc::STDOUT_FILENO).unwrap_or((24, 80));
+    let mut rec = Recorder::open(ty, lines, cols)?;
+
+    if unsafe { libc::isatty(libc::STDIN_FILENO) } == 1 {
+      unsafe {
+        let mut t: libc::termios = std::mem::zeroed();
+        if libc::tcgetattr(libc::STDIN_FILENO, &mut t) == 0 {
+          rec.saved_tty = Some(t);
+          libc::cfmakeraw(&mut t);
+          libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &t);
+        }
+      }
+    }
+
+    let (master, log, stop) = (rec.master, rec.log.clone(), rec.stop.clone());
+    rec.threads.push(thread::spawn(move || pump_output(master, Box::new(io::stdout()), log, stop)));
+    let (log, stop) = (rec.log.clone(), rec.stop.clone());
+    rec.threads.push(thread::spawn(move || pump_tty_input(libc::STDIN_FILENO, master, log, stop)));
+    Ok(rec)
+  }
+
+  /// Starts a new screen, the size `cast` was recorded at, that receives
+  /// the cast's input and resizes at `speed` times their original pace.
+  /// What curses writes goes to `output`, and into the new recording.
+  pub fn replay(ty: Option<&str>, cast: &Cast, output: Box<dyn Write + Send>, speed: f64) -> io::Result<Recorder> {
+    let ty = ty.or(cast.term.as_deref());
+    let mut rec = Recorder::open(ty, cast.height as u16, cast.width as u16)?;
+    let (master, log, stop) = (rec.master, rec.log.clone(), rec.stop.clone());
+    rec.threads.push(thread::spawn(move || pump_output(master, output, log, stop)));
+    let events: Vec<Event> = cast.events.iter()
+      .filter(|e| e.kind == EventKind::Input || e.kind == EventKind::Resize)
+      .cloned()
+      .collect();
+    let (log, stop) = (rec.log.clone(), rec.stop.clone());
+    rec.threads.push(thread::spawn(move || pump_cast_input(events, speed, master, log, stop)));
+    Ok(rec)
+  }
+
+  fn open(ty: Option<&str>, lines: u16, cols: u16) -> io::Result<Recorder> {
+    let (master, slave) = open_pty()?;
+    set_winsize(master, lines, cols);
+    let (slave_out, slave_in) = unsafe {
+      let reader = libc::dup(slave);
+      let slave_out = libc::fdopen(slave, b"w\0".as_ptr() as *const c_char);
+      let slave_in = if reader < 0 { std::ptr::null_mut() } else { libc::fdopen(reader, b"r\0".as_ptr() as *const c_char) };
+      if slave_out.is_null() || slave_in.is_null() {
+        let err = io::Error::last_os_error();
+        // An fd that got a stream is closed with it.
+        if slave_out.is_null() { libc::close(slave) } else { libc::fclose(slave_out) };
+        if !slave_in.is_null() {
+          libc::fclose(slave_in);
+        } else if reader >= 0 {
+          libc::close(reader);
+        }
+        libc::close(master);
+        return Err(err);
+      }
+      (slave_out, slave_in)
+    };
+    let screen = match ::newterm(ty, slave_out as FILE_p, slave_in as FILE_p) {
+      Ok(screen) if !screen.is_null() => Ok(screen),
+      Ok(_) => Err(io::Error::other("newterm failed")),
+      Err(e) => Err(io::Error::new(io::ErrorKind::InvalidInput, e)),
+    };
+    let screen = match screen {
+      Ok(screen) => screen,
+      Err(e) => {
+        unsafe {
+          libc::fclose(slave_out);
+          libc::fclose(slave_in);
+          libc::close(master);
+        }
+        return Err(e);
+      }
+    };
+
+    let term = ty.map(String::from).or_else(|| std::env::var("TERM").ok());
+    Ok(Recorder {
+      screen,
+      master,
+      slave_out,
+      slave_in,
+      saved_tty: None,
+      log: Log { start: Instant::now(), events: Arc::new(Mutex::new(Vec::new())) },
+      stop: Arc::new(AtomicBool::new(false)),
+      threads: Vec::new(),
+      cast: Cast {
+        width: cols as u32,
+        height: lines as u32,
+        timestamp: SystemTime::now().duration_since(UNIX_EPOCH).ok().map(|d| d.as_secs()),
+        title: None,
+        term,
+        events: Vec::new(),
+      },
+      done: false,
+    })
+  }
+
+  /// The recorded screen, for `set_term`.
+  pub fn screen(&self) -> SCREEN {
+    self.screen
+  }
+
+  pub fn set_title(&mut self, title: &str) {
+    self.cast.title = Some(title.to_string());
+  }
+
+  /// Adds a marker event.
+  pub fn mark(&self, label: &str) {
+    self.log.push(EventKind::Marker, label.to_string());
+  }
+
+  /// Resizes the pseudo-terminal and records it. The screen does not get
+  /// SIGWINCH for it; call `resizeterm` after this.
+  pub fn resize(&self, lines: u16, cols: u16) {
+    set_winsize(self.master, lines, cols);
+    self.log.push(EventKind::Resize, format!("{}x{}", cols, lines));
+  }
+
+  /// Follows the real terminal's size, e.g. on SIGWINCH. Returns the new
+  /// `(lines, cols)` if it changed, for `resizeterm`.
+  pub fn sync_size(&self) -> Option<(u16, u16)> {
+    let size = winsize(libc::STDOUT_FILENO)?;
+    if winsize(self.master) == Some(size) {
+      return None;
+    }
+    self.resize(size.0, size.1);
+    Some(size)
+  }
+
+  /// Leaves curses mode if still in it, stops recording, deletes the
+  /// screen and restores the terminal.
+  pub fn finish(mut self) -> Cast {
+    self.shutdown();
+    let mut cast = self.cast.clone();
+    cast.events = std::mem::take(&mut *self.log.events.lock().unwrap_or_else(|e| e.into_inner()));
+    cast
+  }
+
+  fn shutdown(&mut self) {
+    if self.done {
+      return;
+    }
+    self.done = true;
+
+    let previous = ::set_term(self.screen);
+    if !::isendwin() {
+      ::endwin();
+    }
+    // The output thread drains what endwin wrote before it stops.
+    self.stop.store(true, Ordering::SeqCst);
+    for t in self.threads.drain(..) {
+      let _ = t.join();
+    }
+    ::delscreen(self.screen);
+    if !previous.is_null() && previous != self.screen {
+      ::set_term(previous);
+    }
+    unsafe {
+      libc::fclose(self.slave_out);
+      libc::fclose(self.slave_in);
+      libc::close(self.master);
+      if let Some(ref t) = self.saved_tty {
+        libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, t);
+      }
+    }
+  }
+}
+
+impl Drop for Recorder {
+  fn drop(&mut self) {
+    self.shutdown();
+  }
+}
+
+fn open_pty() -> io::Result<(c_int, c_int)> {
+  unsafe {
+    let master = libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY);
+    if master < 0 {
+      return Err(io::Error::last_os_error());
+    }
+    let name = if libc::grantpt(master) == 0 && libc::unlockpt(master) == 0 {
+      libc::ptsname(master)
+    } else {
+      std::ptr::null_mut()
+    };
+    let slave = if name.is_null() {
+      -1
+    } else {
+      libc::open(CStr::from_ptr(name).as_ptr(), libc::O_RDWR | libc::O_NOCTTY)
+    };
+    if slave < 0 {
+      let e = io::Error::last_os_error();
+      libc::close(master);
+      return Err(e);
+    }
+    Ok((master, slave))
+  }
+}
+
+/// `(lines, cols)` of the terminal on `fd`.
+fn winsize(fd: c_int) -> Option<(u16, u16)> {
+  let mut ws: libc::winsize = unsafe { std::mem::zeroed() };
+  if unsafe { libc::ioctl(fd, libc::TIOCGWINSZ, &mut ws) } == 0 && ws.ws_row > 0 {
+    Some((ws.ws_row, ws.ws_col))
+  } else {
+    None
+  }
+}
+
+fn set_winsize(fd: c_int, lines: u16, cols: u16) {
+  let ws = libc::winsize { ws_row: lines, ws_col: cols, ws_xpixel: 0, ws_ypixel: 0 };
+  unsafe {
+    libc::ioctl(fd, libc::TIOCSWINSZ, &ws);
+  }
+}
+
+fn poll_in(fd: c_int) -> bool {
+  let mut pfd = libc::pollfd { fd, events: libc::POLLIN, revents: 0 };
+  unsafe { libc::poll(&mut pfd, 1, POLL_MS) > 0 }
+}
+
+fn read_fd(fd: c_int, buf: &mut [u8]) -> Option<usize> {
+  let n = unsafe { libc::read(fd, buf.as_mut_ptr() as *mut c_void, buf.len()) };
+  if n > 0 { Some(n as usize) } else { None }
+}
+
+fn write_fd(fd: c_int, mut bytes: &[u8]) {
+  while !bytes.is_empty() {
+    let n = unsafe { libc::write(fd, bytes.as_ptr() as *const c_void, bytes.len()) };
+    if n <= 0 {
+      return;
+    }
+    bytes = &bytes[n as usize..];
+  }
+}
+
+/// Takes the text from the front of `pending`, leaving a UTF-8 sequence
+/// cut off by the end of a read for the next one.
+fn take_utf8(pending: &mut Vec<u8>) -> String {
+  let keep = match std::str::from_utf8(pending) {
+    Ok(_) => 0,
+    Err(e) if e.error_len().is_none() => pending.len() - e.valid_up_to(),
+    Err(_) => 0,
+  };
+  let tail = pending.split_off(pending.len() - keep);
+  let text = String::from_utf8_lossy(pending).into_owned();
+  *pending = tail;
+  text
+}
+
+fn pump_output(master: c_int, mut out: Box<dyn Write + Send>, log: Log, stop: Arc<AtomicBool>) {
+  let mut buf = [0u8; 4096];
+  let mut pending = Vec::new();
+  loop {
+    if !poll_in(master) {
+      if stop.load(Ordering::SeqCst) {
+        break;
+      }
+      continue;
+    }
+    let n = match read_fd(master, &mut buf) {
+      Some(n) => n,
+      None => break,
+    };
+    let _ = out.write_all(&buf[..n]);
+    let _ = out.flush();
+    pending.extend_from_slice(&buf[..n]);
+    let text = take_utf8(&mut pending);
+    if !text.is_empty() {
+      log.push(EventKind::Output, text);
+    }
+  }
+  if !pending.is_empty() {
+    log.push(EventKind::Output, String::from_utf8_lossy(&pending).into_owned());
+  }
+}
+
+fn pump_tty_input(fd: c_int, master: c_int, log: Log, stop: Arc<AtomicBool>) {
+  let mut buf = [0u8; 256];
+  let mut pending = Vec::new();
+  while !stop.load(Ordering::SeqCst) {
+    if !poll_in(fd) {
+      continue;
+    }
+    let n = match read_fd(fd, &mut buf) {
+      Some(n) => n,
+      None => break,
+    };
+    write_fd(master, &buf[..n]);
+    pending.extend_from_slice(&buf[..n]);
+    let text = take_utf8(&mut pending);
+    if !text.is_empty() {
+      log.push(EventKind::Input, text);
+    }
+  }
+}
+
+fn pump_cast_input(events: Vec<Event>, speed: f64, master: c_int, log: Log, stop: Arc<AtomicBool>) {
+  for e in events {
+    let due = match due_at(log.start, e.time, speed) {
+      Some(due) => due,
+      None => continue,
+    };
+    loop {
+      if stop.load(Ordering::SeqCst) {
+        return;
+      }
+      let now = Instant::now();
+      if now >= due {
+        break;
+      }
+      thread::sleep(std::cmp::min(due - now, Duration::from_millis(POLL_MS as u64)));
+    }
+    match e.kind {
+      EventKind::Input => write_fd(master, e.data.as_bytes()),
+      EventKind::Resize => {
+        let mut dims = e.data.split('x').map(|d| d.trim().parse::<u16>());
+        if let (Some(Ok(cols)), Some(Ok(lines))) = (dims.next(), dims.next()) {
+          set_winsize(master, lines, cols);
+        }
+      }
+      _ => continue,
+    }
+    log.push(e.kind, e.data);
+  }
+}
+
+fn invalid(msg: &str) -> io::Error {
+  io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn json_string(s: &str) -> String {
+  let mut out = String::with_capacity(s.len() + 2);
+  out.push('"');
+  for c in s.chars() {
+    match c {
+      '"' => out.push_str("\\\""),
+      '\\' => out.push_str("\\\\"),
+      '\n' => out.push_str("\\n"),
+      '\r' => out.push_str("\\r"),
+      '\t' => out.push_str("\\t"),
+      c if (c as u32) < 0x20 || c == '\u{7f}' => out.push_str(&format!("\\u{:04x}", c as u32)),
+      c => out.push(c),
+    }
+  }
+  out.push('"');
+  out
+}
+
+/// How deep arrays and objects may nest; cast files need two levels, and
+/// the parser recurses, so a hostile line must not exhaust the stack.
+const MAX_DEPTH: usize = 64;
+
+/// Just enough JSON for cast files.
+#[derive(Debug, Clone, PartialEq)]
+enum Json {
+  Null,
+  Bool(bool),
+  Num(f64),
+  Str(String),
+  Arr(Vec<Json>),
+  Obj(Vec<(String, Json)>),
+}
+
+impl Json {
+  fn parse(text: &str) -> io::Result<Json> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut pos = 0;
+    let value = Json::value(&chars, &mut pos, 0)?;
+    skip_space(&chars, &mut pos);
+    if pos != chars.len() {
+      return Err(invalid("trailing characters after JSON value"));
+    }
+    Ok(value)
+  }
+
+  fn get(&self, key: &str) -> Option<&Json> {
+    match *self {
+      Json::Obj(ref fields) => fields.iter().find(|f| f.0 == key).map(|f| &f.1),
+      _ => None,
+    }
+  }
+
+  fn as_f64(&self) -> Option<f64> {
+    match *self {
+      Json::Num(n) => Some(n),
+      _ => None,
+    }
+  }
+
+  fn as_str(&self) -> Option<&str> {
+    match *self {
+      Json::Str(ref s) => Some(s),
+      _ => None,
+    }
+  }
+
+  fn value(c: &[char], pos: &mut usize, depth: usize) -> io::Result<Json> {
+    skip_space(c, pos);
+    if depth == MAX_DEPTH && matches!(c.get(*pos), Some('[') | Some('{')) {
+      return Err(invalid("JSON nested too deeply"));
+    }
+    match c.get(*pos) {
+      Some('{') => {
+        *pos += 1;
+        let mut fields = Vec::new();
+        loop {
+          skip_space(c, pos);
+          if c.get(*pos) == Some(&'}') && fields.is_empty() {
+            *pos += 1;
+            return Ok(Json::Obj(fields));
+          }
+          let key = match Json::value(c, pos, depth + 1)? {
+            Json::Str(s) => s,
+            _ => return Err(invalid("object key is not a string")),
+          };
+          skip_space(c, pos);
+          expect(c, pos, ':')?;
+          fields.push((key, Json::value(c, pos, depth + 1)?));
+          skip_space(c, pos);
+          match c.get(*pos) {
+            Some(',') => *pos += 1,
+            Some('}') => {
+              *pos += 1;
+              return Ok(Json::Obj(fields));
+            }
+            _ => return Err(invalid("expected ',' or '}'")),
+          }
+        }
+      }
+      Some('[') => {
+        *pos += 1;
+        let mut items = Vec::new();
+        loop {
+          skip_space(c, pos);
+          if c.get(*pos) == Some(&']') && items.is_empty() {
+            *pos += 1;
+            return Ok(Json::Arr(items));
+          }
+          items.push(Json::value(c, pos, depth + 1)?);
+          skip_space(c, pos);
+          match c.get(*pos) {
+            Some(',') => *pos += 1,
+            Some(']') => {
+              *pos += 1;
+              return Ok(Json::Arr(items));
+            }
+            _ => return Err(invalid("expected ',' or ']'")),
+          }
+        }
+      }
+      Some('"') => {
+        *pos += 1;
+        let mut s = String::new();
+        loop {
+          match c.get(*pos) {
+            None => return Err(invalid("unterminated string")),
+            Some('"') => {
+              *pos += 1;
+              return Ok(Json::Str(s));
+            }
+            Some('\\') => {
+              let esc = c.get(*pos + 1).cloned();
+              *pos += 2;
+              match esc {
+                Some('n') => s.push('\n'),
+                Some('r') => s.push('\r'),
+                Some('t') => s.push('\t'),
+                Some('b') => s.push('\u{8}'),
+                Some('f') => s.push('\u{c}'),
+                Some('u') => s.push(unicode_escape(c, pos)?),
+                Some(e) => s.push(e),
+                None => return Err(invalid("unterminated string")),
+              }
+            }
+            Some(&ch) => {
+              s.push(ch);
+              *pos += 1;
+            }
+          }
+        }
+      }
+      Some('t') if c[*pos..].starts_with(&['t', 'r', 'u', 'e']) => {
+        *pos += 4;
+        Ok(Json::Bool(true))
+      }
+      Some('f') if c[*pos..].starts_with(&['f', 'a', 'l', 's', 'e']) => {
+        *pos += 5;
+        Ok(Json::Bool(false))
+      }
+      Some('n') if c[*pos..].starts_with(&['n', 'u', 'l', 'l']) => {
+        *pos += 4;
+        Ok(Json::Null)
+      }
+      Some(_) => {
+        let start = *pos;
+        while c.get(*pos).is_some_and(|ch| ch.is_ascii_digit() || "+-.eE".contains(*ch)) {
+          *pos += 1;
+        }
+        c[start..*pos].iter().collect::<String>().parse().map(Json::Num)
+          .map_err(|_| invalid("bad JSON value"))
+      }
+      None => Err(invalid("unexpected end of JSON")),
+    }
+  }
+}
+
+fn skip_space(c: &[char], pos: &mut usize) {
+  while c.get(*pos).is_some_and(|ch| ch.is_whitespace()) {
+    *pos += 1;
+  }
+}
+
+fn expect(c: &[char], pos: &mut usize, ch: char) -> io::Result<()> {
+  if c.get(*pos) == Some(&ch) {
+    *pos += 1;
+    Ok(())
+  } else {
+    Err(invalid(&format!("expected '{}'", ch)))
+  }
+}
+
+/// The `XXXX` after `\u` at `pos`, joining surrogate pairs.
+fn unicode_escape(c: &[char], pos: &mut usize) -> io::Result<char> {
+  let hex = |pos: usize| -> io::Result<u32> {
+    let digits: String = c.get(pos..pos + 4).ok_or_else(|| invalid("short \\u escape"))?.iter().collect();
+    u32::from_str_radix(&digits, 16).map_err(|_| invalid("bad \\u escape"))
+  };
+  let high = hex(*pos)?;
+  *pos += 4;
+  if (0xD800..0xDC00).contains(&high) && c.get(*pos) == Some(&'\\') && c.get(*pos + 1) == Some(&'u') {
+    let low = hex(*pos + 2)?;
+    if (0xDC00..0xE000).contains(&low) {
+      *pos += 6;
+      return Ok(std::char::from_u32(0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)).unwrap_or('\u{fffd}'));
+    }
+  }
+  Ok(std::char::from_u32(high).unwrap_or('\u{fffd}'))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn event(time: f64, kind: EventKind, data: &str) -> Event {
+    Event { time, kind, data: data.to_string() }
+  }
+
+  fn round_trip(cast: &Cast) -> Cast {
+    let mut buf = Vec::new();
+    cast.write(&mut buf).unwrap();
+    Cast::read(&buf[..]).unwrap()
+  }
+
+  #[test]
+  fn cast_round_trip() {
+    let cast = Cast {
+      width: 100,
+      height: 30,
+      timestamp: Some(1_700_000_000),
+      title: Some("\"quoted\" \\ title\u{1F600}".to_string()),
+      term: Some("xterm-256color".to_string()),
+      events: vec![
+        event(0.0, EventKind::Output, "\x1b[H\x1b[2Jhello\r\n\t\u{7f}"),
+        event(0.25, EventKind::Input, "q"),
+        event(1.5, EventKind::Resize, "100x30"),
+        event(2.125, EventKind::Marker, "日本"),
+      ],
+    };
+    assert_eq!(round_trip(&cast), cast);
+    assert_eq!(round_trip(&Cast::default()), Cast::default());
+    assert_eq!(cast.output(), "\x1b[H\x1b[2Jhello\r\n\t\u{7f}");
+  }
+
+  #[test]
+  fn read_skips_unknown_events_and_blank_lines() {
+    let text = "{\"version\": 2, \"width\": 8, \"height\": 2, \"extra\": [true, false, null, {}]}\n\n\
+                [0.5, \"x\", \"?\"]\n[1e0, \"o\", \"\\ud83d\\ude00\\u00e9\"]\n";
+    let cast = Cast::read(text.as_bytes()).unwrap();
+    assert_eq!((cast.width, cast.height, cast.title), (8, 2, None));
+    assert_eq!(cast.events, vec![event(1.0, EventKind::Output, "\u{1F600}\u{e9}")]);
+  }
+
+  #[test]
+  fn non_finite_times() {
+    let header = "{\"version\": 2, \"width\": 8, \"height\": 2}\n";
+    for bad in &["[1e999, \"o\", \"x\"]", "[-1e999, \"o\", \"x\"]", "[NaN, \"o\", \"x\"]", "[inf, \"o\", \"x\"]"] {
+      let text = format!("{}{}\n", header, bad);
+      assert_eq!(Cast::read(text.as_bytes()).unwrap_err().kind(), io::ErrorKind::InvalidData, "{}", bad);
+    }
+    for &time in &[::std::f64::NAN, ::std::f64::INFINITY] {
+      let cast = Cast { events: vec![event(time, EventKind::Output, "x")], ..Cast::default() };
+      assert_eq!(cast.write(Vec::new()).unwrap_err().kind(), io::ErrorKind::InvalidInput);
+    }
+  }
+
+  #[test]
+  fn malformed_json() {
+    for bad in &["", "{", "[1, 2", "\"open", "{\"a\" 1}", "{1: 2}", "[1] x", "[\"\\u12\"]", "tru"] {
+      assert!(Json::parse(bad).is_err(), "{}", bad);
+    }
+  }
+
+  #[test]
+  fn deep_nesting() {
+    let nested = |depth: usize| format!("{}{}", "[".repeat(depth), "]".repeat(depth));
+    assert!(Json::parse(&nested(MAX_DEPTH)).is_ok());
+    assert!(Json::parse(&format!("{}1{}", "[".repeat(MAX_DEPTH), "]".repeat(MAX_DEPTH))).is_ok());
+    assert!(Json::parse(&nested(MAX_DEPTH + 1)).is_err());
+    assert!(Json::parse(&nested(1_000_000)).is_err());
+    let objects = format!("{}1{}", "{\"a\":".repeat(100_000), "}".repeat(100_000));
+    assert!(Json::parse(&objects).is_err());
+  }
+}
@@ -0,0 +1,62 @@
+/*
+    Screen rectangles, in curses order: `y` before `x`, lines before cols.
+*/
+
+use std::cmp::{max, min};
+
+use constants::{LINES, COLS};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Rect {
+  pub y: i32,
+  pub x: i32,
+  pub lines: i32,
+  pub cols: i32,
+}
+
+impl Rect {
+  pub fn new(y: i32, x: i32, lines: i32, cols: i32) -> Rect {
+    Rect { y, x, lines, cols }
+  }
+
+  /// The whole terminal, as currently sized.
+  pub fn screen() -> Rect {
+    Rect::new(0, 0, LINES(), COLS())
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.lines <= 0 || self.cols <= 0
+  }
+
+  /// One past the last line.
+  pub fn bottom(&self) -> i32 {
+    self.y + self.lines
+  }
+
+  /// One past the last column.
+  pub fn right(&self) -> i32 {
+    self.x + self.cols
+  }
+
+  pub fn contains(&self, y: i32, x: i32) -> bool {
+    y >= self.y && y < self.bottom() && x >= self.x && x < self.right()
+  }
+
+  /// The overlapping area; empty (with zero size) if there is none.
+  pub fn intersect(&self, other: &Rect) -> Rect {
+    let y = max(self.y, other.y);
+    let x = max(self.x, other.x);
+    let bottom = min(self.bottom(), other.bottom());
+    let right = min(self.right(), other.right());
+    Rect::new(y, x, max(0, bottom - y), max(0, right - x))
+  }
+
+  /// Shrinks the rectangle by `vertical` lines on top and bottom and
+  /// `horizontal` columns on either side.
+  pub fn inset(&self, vertical: i32, horizontal: i32) -> Rect {
+    Rect::new(self.y + vertical,
+              self.x + horizontal,
+              max(0, self.lines - 2 * vertical),
+              max(0, self.cols - 2 * horizontal))
+  }
+}
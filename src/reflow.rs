@@ -0,0 +1,282 @@
+/*
+    Paragraph wrapping for windows and pads.
+
+    `Reflow::lines` breaks text into screen lines and returns where each
+    one starts and ends, so a viewer can keep the breaks, rewrap on
+    `KEY_RESIZE` and map between logical lines (paragraphs, separated by
+    newlines) and screen rows. `Reflow::render` then draws a range of those
+    lines into a window or a pad's window.
+
+    Widths are display widths (see `width`); tabs count as one space.
+*/
+
+use std::cmp::max;
+
+use curses::Window;
+use rect::Rect;
+use styled::Align;
+use constants::{OK, ERR};
+use width::{graphemes, grapheme_width, str_width};
+
+/// How to wrap: line width, indentation and justification.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Reflow {
+  width: usize,
+  indent: usize,
+  hanging: usize,
+  justify: bool,
+  break_words: bool,
+}
+
+/// One screen line of wrapped text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Line {
+  /// Index of the paragraph (logical line) this belongs to.
+  pub paragraph: usize,
+  /// Byte range in the text, without the whitespace at the break.
+  pub start: usize,
+  pub end: usize,
+  /// Columns of indentation before the text.
+  pub indent: usize,
+  /// Whether this is the last line of its paragraph. Such lines are never
+  /// justified.
+  pub last: bool,
+}
+
+fn is_blank(g: &str) -> bool {
+  g == " " || g == "\t"
+}
+
+fn skip_blanks(s: &str, pos: usize) -> usize {
+  s.len() - s[pos..].trim_start_matches([' ', '\t']).len()
+}
+
+impl Reflow {
+  /// Word wrap at `width` columns, long words broken at the edge.
+  pub fn new(width: usize) -> Reflow {
+    Reflow { width, indent: 0, hanging: 0, justify: false, break_words: true }
+  }
+
+  pub fn width(&self) -> usize {
+    self.width
+  }
+
+  /// Same settings at a new width, e.g. after a resize.
+  pub fn with_width(mut self, width: usize) -> Reflow {
+    self.width = width;
+    self
+  }
+
+  /// Indentation of the first line of each paragraph.
+  pub fn indent(mut self, cols: usize) -> Reflow {
+    self.indent = cols;
+    self
+  }
+
+  /// Indentation of the lines after the first.
+  pub fn hanging(mut self, cols: usize) -> Reflow {
+    self.hanging = cols;
+    self
+  }
+
+  /// Stretch the gaps between words so lines end flush right.
+  pub fn justify(mut self, on: bool) -> Reflow {
+    self.justify = on;
+    self
+  }
+
+  /// Whether words longer than a line are broken (the default) or left
+  /// to run past the edge, where they are clipped.
+  pub fn break_words(mut self, on: bool) -> Reflow {
+    self.break_words = on;
+    self
+  }
+
+  /// Where the line starting at `start` ends, and where the next starts.
+  fn next_line(&self, para: &str, start: usize, avail: usize) -> (usize, usize) {
+    let mut width = 0;
+    let mut pos = start;
+    let mut gap = None;
+    let mut brk = None;
+    let mut overflow = false;
+
+    for g in graphemes(&para[start..]) {
+      if is_blank(g) {
+        if overflow {
+          return (pos, skip_blanks(para, pos));
+        }
+        if gap.is_none() && pos > start {
+          gap = Some(pos);
+        }
+        width += 1;
+      } else {
+        if let Some(gap_start) = gap.take() {
+          brk = Some((gap_start, pos));
+        }
+        let gw = grapheme_width(g);
+        if width + gw > avail && !overflow {
+          if let Some(b) = brk {
+            return b;
+          }
+          if self.break_words {
+            let split = if pos > start { pos } else { pos + g.len() };
+            return (split, split);
+          }
+          overflow = true;
+        }
+        width += gw;
+      }
+      pos += g.len();
+    }
+    (gap.unwrap_or(pos), para.len())
+  }
+
+  /// Breaks `text` into lines. Every paragraph, even an empty one, gives
+  /// at least one line.
+  pub fn lines(&self, text: &str) -> Vec<Line> {
+    let mut out = Vec::new();
+    let mut offset = 0;
+    for (paragraph, raw) in text.split('\n').enumerate() {
+      let para = raw.strip_suffix('\r').unwrap_or(raw);
+      let mut start = 0;
+      let mut first = true;
+      loop {
+        let indent = if first { self.indent } else { self.hanging };
+        let avail = max(1, self.width.saturating_sub(indent));
+        let (end, next) = self.next_line(para, start, avail);
+        out.push(Line { paragraph, start: offset + start, end: offset + end, indent, last: next >= para.len() });
+        if next >= para.len() {
+          break;
+        }
+        start = next;
+        first = false;
+      }
+      offset += raw.len() + 1;
+    }
+    out
+  }
+
+  /// The text of `line` as it should appear on screen, indentation and
+  /// justification included.
+  pub fn format(&self, text: &str, line: &Line) -> String {
+    let body = &text[line.start..line.end];
+    let mut out: String = (0..line.indent).map(|_| ' ').collect();
+    let avail = self.width.saturating_sub(line.indent);
+    let words: Vec<&str> = body.split([' ', '\t']).filter(|w| !w.is_empty()).collect();
+
+    if !self.justify || line.last || words.len() < 2 || str_width(body) > avail {
+      out.extend(body.chars().map(|c| if c == '\t' { ' ' } else { c }));
+      return out;
+    }
+    let gaps = words.len() - 1;
+    let used: usize = words.iter().map(|w| str_width(w)).sum();
+    let spaces = avail.saturating_sub(used);
+    for (i, word) in words.iter().enumerate() {
+      if i > 0 {
+        // Leftmost gaps take the remainder.
+        let n = spaces / gaps + (i <= spaces % gaps) as usize;
+        out.extend((0..max(1, n)).map(|_| ' '));
+      }
+      out.push_str(word);
+    }
+    out
+  }
+
+  /// Draws `lines[first..]` into `area` of `w`, one per row, padded or
+  /// clipped to the area's width so that text from before a reflow does
+  /// not show through. Rows of the area past the last line are left alone.
+  /// Works the same for a pad's window, in pad coordinates.
+  pub fn render(&self, w: &Window, text: &str, lines: &[Line], first: usize, area: Rect) -> i32 {
+    let mut ret = OK;
+    let (lines_w, cols_w) = w.size();
+    let rows = max(0, area.lines) as usize;
+    for (row, line) in lines.iter().skip(first).take(rows).enumerate() {
+      let s = self.format(text, line);
+      let y = area.y + row as i32;
+      // Filling the bottom-right cell leaves the cursor nowhere to go, so
+      // with scrolling off curses reports ERR, although the row was drawn.
+      let corner = y == lines_w - 1 && area.x + area.cols >= cols_w;
+      match w.put_str_padded(y, area.x, &s, area.cols, Align::Left) {
+        Ok(OK) => {}
+        Ok(_) if corner => {}
+        _ => ret = ERR,
+      }
+    }
+    ret
+  }
+}
+
+/// The first row of `paragraph` in `lines`, or `lines.len()` if it is
+/// past the end.
+pub fn row_of(lines: &[Line], paragraph: usize) -> usize {
+  lines.partition_point(|l| l.paragraph < paragraph)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use constants::A_CHARTEXT;
+  use testing::with_screen;
+
+  fn wrap(r: &Reflow, text: &str) -> Vec<String> {
+    r.lines(text).iter().map(|l| r.format(text, l)).collect()
+  }
+
+  #[test]
+  fn wraps_at_spaces() {
+    assert_eq!(wrap(&Reflow::new(10), "the quick brown fox jumps"), vec!["the quick", "brown fox", "jumps"]);
+    assert_eq!(wrap(&Reflow::new(10), "a\tb"), vec!["a b"]);
+    assert_eq!(wrap(&Reflow::new(4), "日本語 ab"), vec!["日本", "語", "ab"]);
+  }
+
+  #[test]
+  fn long_words() {
+    assert_eq!(wrap(&Reflow::new(4), "abcdefghij"), vec!["abcd", "efgh", "ij"]);
+    assert_eq!(wrap(&Reflow::new(4).break_words(false), "ab abcdefgh cd"), vec!["ab", "abcdefgh", "cd"]);
+    assert_eq!(wrap(&Reflow::new(0), "ab"), vec!["a", "b"]);
+  }
+
+  #[test]
+  fn indentation() {
+    let r = Reflow::new(10).indent(2).hanging(4);
+    assert_eq!(wrap(&r, "aaa bbb ccc ddd"), vec!["  aaa bbb", "    ccc", "    ddd"]);
+  }
+
+  #[test]
+  fn justification() {
+    let r = Reflow::new(11).justify(true);
+    assert_eq!(wrap(&r, "a bb c dd eeeeeeee"), vec!["a  bb  c dd", "eeeeeeee"]);
+    assert_eq!(wrap(&r, "verylongword x"), vec!["verylongwor", "d x"]);
+  }
+
+  #[test]
+  fn paragraphs() {
+    let text = "one\n\ntwo\r\n";
+    let lines = Reflow::new(10).lines(text);
+    let summary: Vec<(usize, usize, usize, bool)> = lines.iter().map(|l| (l.paragraph, l.start, l.end, l.last)).collect();
+    assert_eq!(summary, vec![(0, 0, 3, true), (1, 4, 4, true), (2, 5, 8, true), (3, 10, 10, true)]);
+    assert_eq!(row_of(&lines, 2), 2);
+    assert_eq!(row_of(&lines, 9), 4);
+
+    let lines = Reflow::new(3).lines("ab cd\nef");
+    assert_eq!(lines.iter().map(|l| (l.paragraph, l.last)).collect::<Vec<_>>(), vec![(0, false), (0, true), (1, true)]);
+    assert_eq!(row_of(&lines, 1), 2);
+  }
+
+  #[test]
+  fn render_fills_the_window_exactly() {
+    with_screen(|curses| {
+      let w = curses.new_window(2, 5, 0, 0).unwrap();
+      let text = "abcde fghij";
+      let r = Reflow::new(5);
+      let lines = r.lines(text);
+      assert_eq!(r.render(&w, text, &lines, 0, Rect::new(0, 0, 2, 5)), OK);
+      let rows: Vec<String> = (0..2)
+        .map(|y| (0..5).map(|x| (::mvwinch(w.raw(), y, x) & A_CHARTEXT) as u8 as char).collect())
+        .collect();
+      assert_eq!(rows, vec!["abcde", "fghij"]);
+      assert_eq!(r.render(&w, text, &lines, 0, Rect::new(0, 0, 3, 6)), OK);
+      assert_eq!(r.render(&w, text, &lines, 0, Rect::new(-1, 0, 2, 5)), ERR);
+    });
+  }
+}
@@ -0,0 +1,246 @@
+/*
+    Pacing screen updates to what the terminal can take.
+
+    Redrawing on every event floods a slow link: output queues up behind
+    the connection and the screen lags further and further behind the
+    input. A `RenderScheduler` sits between "something changed" and
+    "draw": `request` marks the screen dirty (any number of requests
+    between two frames make one frame), and `ready` says whether to draw
+    now. It says no while
+
+      - the last frame was less than `1 / fps` ago,
+      - input is waiting on the input fd, the same check ncurses makes for
+        `typeahead` during `doupdate`, except that here the drawing work is
+        skipped as well; a frame is forced after `max_defer` so that a
+        steady stream of keys cannot starve the screen,
+      - more than `max_queued` bytes of earlier frames are still in the
+        tty's output queue (`TIOCOUTQ`).
+
+    The rate at which that queue drains is measured between frames and
+    used to estimate when it will be short enough again, so `timeout_ms`
+    can be handed straight to `timeout` for the input loop to wake up in
+    time for the next frame.
+*/
+
+use std::cmp::max;
+use std::time::{Duration, Instant};
+
+use libc::{self, c_int};
+
+/// How much of a new drain rate measurement goes into the estimate.
+const SMOOTHING: f64 = 0.5;
+
+/// Counters for tuning; none of them affect scheduling.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SchedulerStats {
+  /// Calls to `request`.
+  pub requested: u64,
+  /// Frames drawn.
+  pub rendered: u64,
+  /// Requests that landed on an already dirty screen.
+  pub coalesced: u64,
+  /// `ready` calls that held back a due frame because input was pending.
+  pub input_deferrals: u64,
+  /// `ready` calls that held back a due frame because of queued output.
+  pub output_deferrals: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct RenderScheduler {
+  interval: Duration,
+  input_fd: Option<c_int>,
+  output_fd: Option<c_int>,
+  max_queued: usize,
+  max_defer: Duration,
+  dirty_since: Option<Instant>,
+  last_render: Option<Instant>,
+  /// The output queue length at the last sample, and when it was taken.
+  queue_sample: Option<(Instant, usize)>,
+  /// Bytes per second leaving the output queue.
+  drain_rate: Option<f64>,
+  stats: SchedulerStats,
+}
+
+impl RenderScheduler {
+  /// At most `fps` frames a second, watching stdin for input and stdout's
+  /// output queue.
+  pub fn new(fps: u32) -> RenderScheduler {
+    RenderScheduler {
+      interval: Duration::from_secs(1) / max(1, fps),
+      input_fd: Some(libc::STDIN_FILENO),
+      output_fd: Some(libc::STDOUT_FILENO),
+      max_queued: 4096,
+      max_defer: Duration::from_millis(250),
+      dirty_since: None,
+      last_render: None,
+      queue_sample: None,
+      drain_rate: None,
+      stats: SchedulerStats::default(),
+    }
+  }
+
+  /// The fd checked for pending input; `None` never defers for input.
+  /// Pass the same fd to `typeahead` if it is not stdin.
+  pub fn input_fd(mut self, fd: Option<c_int>) -> RenderScheduler {
+    self.input_fd = fd;
+    self
+  }
+
+  /// The tty curses writes to, as given to `newterm`; `None` does not
+  /// watch the output queue.
+  pub fn output_fd(mut self, fd: Option<c_int>) -> RenderScheduler {
+    self.output_fd = fd;
+    self
+  }
+
+  /// Bytes allowed to sit in the output queue before frames are held.
+  pub fn max_queued(mut self, bytes: usize) -> RenderScheduler {
+    self.max_queued = bytes;
+    self
+  }
+
+  /// The longest a requested frame waits for the input to stop.
+  pub fn max_defer(mut self, d: Duration) -> RenderScheduler {
+    self.max_defer = d;
+    self
+  }
+
+  /// Marks the screen as needing a redraw.
+  pub fn request(&mut self) {
+    self.stats.requested += 1;
+    if self.dirty_since.is_some() {
+      self.stats.coalesced += 1;
+    } else {
+      self.dirty_since = Some(Instant::now());
+    }
+  }
+
+  pub fn is_dirty(&self) -> bool {
+    self.dirty_since.is_some()
+  }
+
+  /// Whether to draw now. Call `rendered` after drawing.
+  pub fn ready(&mut self) -> bool {
+    let now = Instant::now();
+    let since = match self.dirty_since {
+      Some(t) => t,
+      None => return false,
+    };
+    if self.frame_wait(now) > Duration::from_secs(0) {
+      return false;
+    }
+    if self.output_wait(now) > Duration::from_secs(0) {
+      self.stats.output_deferrals += 1;
+      return false;
+    }
+    if now.duration_since(since) < self.max_defer && self.input_pending() {
+      self.stats.input_deferrals += 1;
+      return false;
+    }
+    true
+  }
+
+  /// Records a frame drawn and flushed with `doupdate`.
+  pub fn rendered(&mut self) {
+    let now = Instant::now();
+    self.stats.rendered += 1;
+    self.dirty_since = None;
+    self.last_render = Some(now);
+    // The queue grew by this frame; measure the drain from here on.
+    self.queue_sample = self.queued().map(|q| (now, q));
+  }
+
+  /// Runs `draw` if `ready`, and returns whether it did.
+  pub fn render<F: FnOnce()>(&mut self, draw: F) -> bool {
+    if !self.ready() {
+      return false;
+    }
+    draw();
+    self.rendered();
+    true
+  }
+
+  /// How long until a requested frame may be drawn, or `None` with
+  /// nothing requested.
+  pub fn wait(&mut self) -> Option<Duration> {
+    self.dirty_since?;
+    let now = Instant::now();
+    Some(max(self.frame_wait(now), self.output_wait(now)))
+  }
+
+  /// `wait` in milliseconds for `timeout`/`wtimeout`: -1 (block) with
+  /// nothing requested, rounded up otherwise.
+  pub fn timeout_ms(&mut self) -> i32 {
+    match self.wait() {
+      None => -1,
+      Some(d) => {
+        let ms = d.as_secs().saturating_mul(1000) + (d.subsec_nanos() as u64).div_ceil(1_000_000);
+        ms.min(i32::MAX as u64) as i32
+      }
+    }
+  }
+
+  /// Bytes per second the output queue was last seen draining at.
+  pub fn drain_rate(&self) -> Option<f64> {
+    self.drain_rate
+  }
+
+  pub fn stats(&self) -> SchedulerStats {
+    self.stats
+  }
+
+  fn frame_wait(&self, now: Instant) -> Duration {
+    match self.last_render {
+      Some(t) => (t + self.interval).saturating_duration_since(now),
+      None => Duration::from_secs(0),
+    }
+  }
+
+  /// Time for the output queue to drain down to `max_queued`, judging by
+  /// the measured rate. With no measurement yet, one frame interval.
+  fn output_wait(&mut self, now: Instant) -> Duration {
+    let queued = match self.queued() {
+      Some(q) => q,
+      None => return Duration::from_secs(0),
+    };
+    if let Some((then, before)) = self.queue_sample {
+      let secs = now.duration_since(then).as_secs_f64();
+      if queued < before && secs > 0.0 {
+        let rate = (before - queued) as f64 / secs;
+        self.drain_rate = Some(match self.drain_rate {
+          Some(r) => r + SMOOTHING * (rate - r),
+          None => rate,
+        });
+      }
+    }
+    self.queue_sample = Some((now, queued));
+
+    if queued <= self.max_queued {
+      return Duration::from_secs(0);
+    }
+    match self.drain_rate {
+      Some(rate) if rate > 0.0 => Duration::from_secs_f64((queued - self.max_queued) as f64 / rate),
+      _ => self.interval,
+    }
+  }
+
+  /// Bytes written to the output tty but not yet sent.
+  fn queued(&self) -> Option<usize> {
+    let fd = self.output_fd?;
+    let mut n: c_int = 0;
+    if unsafe { libc::ioctl(fd, libc::TIOCOUTQ, &mut n) } == 0 {
+      Some(max(0, n) as usize)
+    } else {
+      None
+    }
+  }
+
+  fn input_pending(&self) -> bool {
+    let fd = match self.input_fd {
+      Some(fd) => fd,
+      None => return false,
+    };
+    let mut pfd = libc::pollfd { fd, events: libc::POLLIN, revents: 0 };
+    unsafe { libc::poll(&mut pfd, 1, 0) > 0 && pfd.revents & libc::POLLIN != 0 }
+  }
+}
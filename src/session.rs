@@ -0,0 +1,398 @@
+/*
+    Curses sessions.
+
+    `Curses::init()` (or `Curses::builder()`) starts curses mode and returns
+    a `Session`, which derefs to the `Curses` token. Dropping the session
+    calls `endwin`, and a panic hook makes sure the terminal is restored
+    before the panic message is printed.
+
+    Sessions also cooperate with job control: after the process is stopped
+    with Ctrl+Z (SIGTSTP) and continued with `fg` (SIGCONT), ncurses'
+    own SIGTSTP handler repaints the screen, or, where that handler is not
+    installed, the next `check_resumed` does; and `run_external` hands the
+    terminal to an editor or pager and takes it back afterwards.
+*/
+
+use std::io;
+use std::ops::Deref;
+use std::panic;
+use std::os::unix::process::CommandExt;
+use std::process::{Command, ExitStatus};
+use std::sync::Once;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use curses::Curses;
+use constants::{ERR, LcCategory, curscr};
+use CURSOR_VISIBILITY;
+
+static SESSION_ACTIVE: AtomicBool = AtomicBool::new(false);
+static PANIC_HOOK: Once = Once::new();
+static RESUMED: AtomicBool = AtomicBool::new(false);
+static SIGCONT_HANDLER: Once = Once::new();
+
+/// Configures the terminal modes applied when the session starts.
+#[derive(Debug, Clone)]
+pub struct SessionBuilder {
+  locale: bool,
+  cbreak: bool,
+  raw: bool,
+  echo: bool,
+  keypad: bool,
+  colors: bool,
+  default_colors: bool,
+  cursor: Option<CURSOR_VISIBILITY>,
+  panic_hook: bool,
+  job_control: bool,
+}
+
+impl Default for SessionBuilder {
+  fn default() -> SessionBuilder {
+    SessionBuilder {
+      locale: true,
+      cbreak: true,
+      raw: false,
+      echo: false,
+      keypad: true,
+      colors: true,
+      default_colors: false,
+      cursor: None,
+      panic_hook: true,
+      job_control: true,
+    }
+  }
+}
+
+impl SessionBuilder {
+  pub fn new() -> SessionBuilder {
+    SessionBuilder::default()
+  }
+
+  /// Calls `setlocale(LC_ALL, "")` before `initscr`. Needed for UTF-8 output.
+  pub fn locale(mut self, on: bool) -> SessionBuilder {
+    self.locale = on;
+    self
+  }
+
+  pub fn cbreak(mut self, on: bool) -> SessionBuilder {
+    self.cbreak = on;
+    self
+  }
+
+  /// Takes precedence over `cbreak`.
+  pub fn raw(mut self, on: bool) -> SessionBuilder {
+    self.raw = on;
+    self
+  }
+
+  pub fn echo(mut self, on: bool) -> SessionBuilder {
+    self.echo = on;
+    self
+  }
+
+  pub fn keypad(mut self, on: bool) -> SessionBuilder {
+    self.keypad = on;
+    self
+  }
+
+  /// Calls `start_color` if the terminal supports colours.
+  pub fn colors(mut self, on: bool) -> SessionBuilder {
+    self.colors = on;
+    self
+  }
+
+  /// Calls `use_default_colors`, making colour `-1` the terminal's own.
+  /// Ignored by curses libraries without it.
+  pub fn default_colors(mut self, on: bool) -> SessionBuilder {
+    self.default_colors = on;
+    self
+  }
+
+  pub fn cursor(mut self, visibility: CURSOR_VISIBILITY) -> SessionBuilder {
+    self.cursor = Some(visibility);
+    self
+  }
+
+  /// Installs a panic hook that calls `endwin` first. On by default.
+  pub fn panic_hook(mut self, on: bool) -> SessionBuilder {
+    self.panic_hook = on;
+    self
+  }
+
+  /// Watches for SIGCONT so `check_resumed` can repaint after `fg`. On by default.
+  pub fn job_control(mut self, on: bool) -> SessionBuilder {
+    self.job_control = on;
+    self
+  }
+
+  /// Takes the curses lock and enters curses mode.
+  pub fn init(self) -> Session {
+    let curses = Curses::lock();
+
+    if self.locale {
+      let _ = ::setlocale(LcCategory::all, "");
+    }
+    if self.panic_hook {
+      install_panic_hook();
+    }
+    if self.job_control {
+      install_sigcont_handler();
+    }
+
+    ::initscr();
+    SESSION_ACTIVE.store(true, Ordering::SeqCst);
+
+    if self.raw {
+      ::raw();
+    } else if self.cbreak {
+      ::cbreak();
+    }
+    if self.echo {
+      ::echo();
+    } else {
+      ::noecho();
+    }
+    ::keypad(::stdscr(), self.keypad);
+    if self.colors && ::has_colors() {
+      ::start_color();
+      #[cfg(ncurses_default_colors)]
+      {
+        if self.default_colors {
+          ::use_default_colors();
+        }
+      }
+    }
+    if let Some(visibility) = self.cursor {
+      ::curs_set(visibility);
+    }
+
+    Session { curses, suspended: false }
+  }
+}
+
+fn install_panic_hook() {
+  PANIC_HOOK.call_once(|| {
+    let previous = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+      restore_terminal();
+      previous(info);
+    }));
+  });
+}
+
+/// The SIGCONT handler found when ours was installed, called after ours.
+static PREVIOUS_SIGCONT: AtomicUsize = AtomicUsize::new(0);
+static PREVIOUS_SIGCONT_SIGINFO: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn on_sigcont(sig: libc::c_int, info: *mut libc::siginfo_t, context: *mut libc::c_void) {
+  RESUMED.store(true, Ordering::SeqCst);
+  let previous = PREVIOUS_SIGCONT.load(Ordering::SeqCst);
+  if previous == libc::SIG_DFL || previous == libc::SIG_IGN {
+    return;
+  }
+  unsafe {
+    if PREVIOUS_SIGCONT_SIGINFO.load(Ordering::SeqCst) {
+      let f: extern "C" fn(libc::c_int, *mut libc::siginfo_t, *mut libc::c_void) = std::mem::transmute(previous);
+      f(sig, info, context);
+    } else {
+      let f: extern "C" fn(libc::c_int) = std::mem::transmute(previous);
+      f(sig);
+    }
+  }
+}
+
+/*
+ * ncurses installs its own SIGTSTP handler (unless the application had one
+ * already), which leaves curses mode before the process stops and repaints
+ * the screen once it is continued. Where SIGTSTP is left at its default,
+ * nothing repaints; so SIGCONT is recorded here, chaining to any handler
+ * that was there before, and acted upon outside of the signal handler by
+ * `check_resumed`.
+ */
+fn install_sigcont_handler() {
+  SIGCONT_HANDLER.call_once(|| unsafe {
+    let mut action: libc::sigaction = std::mem::zeroed();
+    let mut previous: libc::sigaction = std::mem::zeroed();
+    action.sa_sigaction = on_sigcont as extern "C" fn(libc::c_int, *mut libc::siginfo_t, *mut libc::c_void)
+      as libc::sighandler_t;
+    action.sa_flags = libc::SA_RESTART | libc::SA_SIGINFO;
+    libc::sigemptyset(&mut action.sa_mask);
+    if libc::sigaction(libc::SIGCONT, std::ptr::null(), &mut previous) == 0 {
+      PREVIOUS_SIGCONT_SIGINFO.store(previous.sa_flags & libc::SA_SIGINFO != 0, Ordering::SeqCst);
+      PREVIOUS_SIGCONT.store(previous.sa_sigaction, Ordering::SeqCst);
+    }
+    libc::sigaction(libc::SIGCONT, &action, std::ptr::null_mut());
+  });
+}
+
+/// Whether a SIGTSTP handler is installed; ncurses' own repaints the
+/// screen after the process is continued.
+fn tstp_handler_installed() -> bool {
+  unsafe {
+    let mut current: libc::sigaction = std::mem::zeroed();
+    libc::sigaction(libc::SIGTSTP, std::ptr::null(), &mut current) == 0
+      && current.sa_sigaction != libc::SIG_DFL
+      && current.sa_sigaction != libc::SIG_IGN
+  }
+}
+
+/// Sets `sig` to `SIG_DFL` or `SIG_IGN` and returns the action it had, flags
+/// and mask included, for `sigaction` to put back. Async-signal-safe.
+unsafe fn set_handler(sig: libc::c_int, handler: libc::sighandler_t) -> libc::sigaction {
+  let mut action: libc::sigaction = std::mem::zeroed();
+  let mut previous: libc::sigaction = std::mem::zeroed();
+  action.sa_sigaction = handler;
+  libc::sigemptyset(&mut action.sa_mask);
+  libc::sigaction(sig, &action, &mut previous);
+  previous
+}
+
+/// Leaves curses mode if a session is active. Safe to call more than once.
+fn restore_terminal() {
+  if SESSION_ACTIVE.swap(false, Ordering::SeqCst) && !::isendwin() {
+    ::endwin();
+  }
+}
+
+impl Curses {
+  /// Starts curses with the default `SessionBuilder` settings.
+  pub fn init() -> Session {
+    SessionBuilder::new().init()
+  }
+
+  pub fn builder() -> SessionBuilder {
+    SessionBuilder::new()
+  }
+}
+
+/// An active curses session. Restores the terminal when dropped.
+#[derive(Debug)]
+pub struct Session {
+  curses: Curses,
+  suspended: bool,
+}
+
+impl Session {
+  pub fn curses(&self) -> &Curses {
+    &self.curses
+  }
+
+  pub fn is_suspended(&self) -> bool {
+    self.suspended
+  }
+
+  /// Saves the program modes and temporarily leaves curses mode.
+  pub fn suspend(&mut self) -> i32 {
+    if self.suspended {
+      return ERR;
+    }
+    self.suspended = true;
+    ::def_prog_mode();
+    ::endwin()
+  }
+
+  /// Restores the modes saved by `suspend` and repaints the screen.
+  pub fn resume(&mut self) -> i32 {
+    if !self.suspended {
+      return ERR;
+    }
+    self.suspended = false;
+    ::reset_prog_mode();
+    ::refresh()
+  }
+
+  /// Repaints the whole screen if the process was continued after being
+  /// stopped, unless a SIGTSTP handler (normally ncurses' own) has done so
+  /// already. Returns whether it did; call it after `getch` returns.
+  pub fn check_resumed(&self) -> bool {
+    if !RESUMED.swap(false, Ordering::SeqCst) || self.suspended || tstp_handler_installed() {
+      return false;
+    }
+    ::reset_prog_mode();
+    ::clearok(curscr(), true);
+    ::wrefresh(curscr());
+    true
+  }
+
+  /// Stops the process as Ctrl+Z would and repaints once it is continued.
+  /// Useful in raw mode, where Ctrl+Z arrives as an ordinary key.
+  pub fn stop(&mut self) -> i32 {
+    if self.suspend() == ERR {
+      return ERR;
+    }
+    unsafe {
+      libc::raise(libc::SIGTSTP);
+    }
+    RESUMED.store(false, Ordering::SeqCst);
+    self.resume_with_redraw()
+  }
+
+  /// Leaves curses mode, runs `cmd` to completion with the terminal in
+  /// shell mode and then restores the screen.
+  ///
+  /// Like `system(3)`, SIGINT and SIGQUIT are ignored while waiting so that
+  /// interrupting the child does not take the application down with it.
+  ///
+  /// Each call adds a `pre_exec` hook to `cmd` that puts those signals back
+  /// to their defaults in the child. Running the same `Command` again adds
+  /// another one; they do no harm, but pass a new `Command` for each run.
+  pub fn run_external(&mut self, cmd: &mut Command) -> io::Result<ExitStatus> {
+    if self.suspend() == ERR {
+      return Err(io::Error::other("curses session is already suspended"));
+    }
+    let status = unsafe {
+      // The child must not inherit the ignored dispositions.
+      cmd.pre_exec(|| {
+        set_handler(libc::SIGINT, libc::SIG_DFL);
+        set_handler(libc::SIGQUIT, libc::SIG_DFL);
+        Ok(())
+      });
+      let old_int = set_handler(libc::SIGINT, libc::SIG_IGN);
+      let old_quit = set_handler(libc::SIGQUIT, libc::SIG_IGN);
+      let status = cmd.status();
+      libc::sigaction(libc::SIGINT, &old_int, std::ptr::null_mut());
+      libc::sigaction(libc::SIGQUIT, &old_quit, std::ptr::null_mut());
+      status
+    };
+    self.resume_with_redraw();
+    status
+  }
+
+  /// Like `suspend`, but hands the closure the terminal in shell mode.
+  pub fn with_shell_mode<F, R>(&mut self, f: F) -> R
+    where F: FnOnce() -> R
+  {
+    // A session the caller suspended stays suspended.
+    let suspended_here = self.suspend() != ERR;
+    let ret = f();
+    if suspended_here {
+      self.resume_with_redraw();
+    }
+    ret
+  }
+
+  /// `resume`, except that the screen is cleared and repainted from
+  /// scratch, since whatever ran meanwhile has scribbled over it.
+  fn resume_with_redraw(&mut self) -> i32 {
+    if !self.suspended {
+      return ERR;
+    }
+    self.suspended = false;
+    ::reset_prog_mode();
+    ::clearok(curscr(), true);
+    ::refresh()
+  }
+}
+
+impl Deref for Session {
+  type Target = Curses;
+
+  fn deref(&self) -> &Curses {
+    &self.curses
+  }
+}
+
+impl Drop for Session {
+  fn drop(&mut self) {
+    restore_terminal();
+  }
+}
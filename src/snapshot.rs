@@ -0,0 +1,377 @@
+/*
+    Snapshot tests for what a window shows.
+
+    `assert_window_snapshot!(win, "name")` reads the window back cell by
+    cell and compares it with `tests/snapshots/name.snap` under the calling
+    crate. A snapshot is plain text, meant to be read in review and in
+    diffs:
+
+        size 2x8
+        --- text
+        |Name    |
+        |├──────┤|
+        --- attrs
+        |aaaa....|
+        |bbbbbbbb|
+        --- legend
+        a bold pair 1
+        b acs
+
+    The text layer has the characters, line graphics drawn as their Unicode
+    counterparts; the attrs layer has one letter per cell for its
+    combination of attributes and colour pair, `.` being none, explained in
+    the legend. A `wide` build reads cells with `mvwin_wch` and
+    `getcchar`, so wide characters read back whole, and the cell after a
+    double width one shows nothing in the text layer; other builds read
+    `chtype`s, where a character is its low byte only.
+
+    When the window differs, the assertion fails with the two side by side,
+    and leaves `name.snap.new` next to the snapshot and `name.win`, a
+    `putwin` dump that `getwin` loads back, for a closer look. With
+    `UPDATE_SNAPSHOTS=1` in the environment the snapshot is rewritten
+    instead; a missing snapshot is always written.
+*/
+
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use curses::Window;
+use ll::{self, WINDOW, chtype, attr_t};
+use constants::{A_ALTCHARSET, A_ATTRIBUTES, A_BLINK, A_BOLD, A_CHARTEXT, A_COLOR, A_DIM, A_INVIS,
+                A_PROTECT, A_REVERSE, A_STANDOUT, A_UNDERLINE};
+
+/// Attribute names in the legend, in this order.
+const ATTRIBUTES: &[(attr_t, &str)] = &[
+  (A_BOLD, "bold"),
+  (A_DIM, "dim"),
+  (A_STANDOUT, "standout"),
+  (A_UNDERLINE, "underline"),
+  (A_REVERSE, "reverse"),
+  (A_BLINK, "blink"),
+  (A_INVIS, "invis"),
+  (A_PROTECT, "protect"),
+  (A_ALTCHARSET, "acs"),
+];
+
+/// Letters for attribute combinations, in order of first appearance.
+const KEYS: &str = "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
+
+/// Anything a snapshot can be taken of.
+pub trait RawWindow {
+  fn raw_window(&self) -> WINDOW;
+}
+
+impl RawWindow for WINDOW {
+  fn raw_window(&self) -> WINDOW {
+    *self
+  }
+}
+
+impl<'c> RawWindow for Window<'c> {
+  fn raw_window(&self) -> WINDOW {
+    self.raw()
+  }
+}
+
+impl<T: RawWindow + ?Sized> RawWindow for &T {
+  fn raw_window(&self) -> WINDOW {
+    (**self).raw_window()
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+  pub text: Vec<String>,
+  pub attrs: Vec<String>,
+  /// The letters used in `attrs` and what they stand for.
+  pub legend: Vec<(char, String)>,
+}
+
+impl Snapshot {
+  /// Reads back the contents of `w`. The cursor is left where it was.
+  ///
+  /// Panics if the window uses more combinations of attributes and colour
+  /// pairs than there are letters for them in `KEYS`.
+  pub fn capture<W: RawWindow + ?Sized>(w: &W) -> Snapshot {
+    let w = w.raw_window();
+    let (lines, cols) = (::getmaxy(w), ::getmaxx(w));
+    let (cy, cx) = (::getcury(w), ::getcurx(w));
+    let mut snap = Snapshot { text: Vec::new(), attrs: Vec::new(), legend: Vec::new() };
+    let mut combos: Vec<(attr_t, i32)> = Vec::new();
+
+    for y in 0..lines {
+      let mut text = String::with_capacity(cols as usize);
+      let mut attrs = String::with_capacity(cols as usize);
+      for (shown, a, pair) in read_row(w, y, cols) {
+        text.push_str(&shown);
+        let a = a & A_ATTRIBUTES & !A_COLOR;
+        if a == 0 && pair == 0 {
+          attrs.push('.');
+          continue;
+        }
+        let i = match combos.iter().position(|&c| c == (a, pair)) {
+          Some(i) => i,
+          None => {
+            combos.push((a, pair));
+            combos.len() - 1
+          }
+        };
+        attrs.push(key(i));
+      }
+      snap.text.push(text);
+      snap.attrs.push(attrs);
+    }
+    ::wmove(w, cy, cx);
+
+    let known = ATTRIBUTES.iter().fold(0, |acc, &(bit, _)| acc | bit);
+    for (i, &(a, pair)) in combos.iter().enumerate() {
+      let mut names: Vec<String> = ATTRIBUTES.iter()
+        .filter(|&&(bit, _)| a & bit != 0)
+        .map(|&(_, n)| n.to_string())
+        .collect();
+      if a & !known != 0 {
+        names.push(format!("{:#x}", a & !known));
+      }
+      if pair != 0 {
+        names.push(format!("pair {}", pair));
+      }
+      snap.legend.push((key(i), names.join(" ")));
+    }
+    snap
+  }
+
+  /// `(lines, cols)`
+  pub fn size(&self) -> (usize, usize) {
+    (self.attrs.len(), self.attrs.first().map_or(0, |l| l.chars().count()))
+  }
+}
+
+impl fmt::Display for Snapshot {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let (lines, cols) = self.size();
+    writeln!(f, "size {}x{}", lines, cols)?;
+    writeln!(f, "--- text")?;
+    for l in &self.text {
+      writeln!(f, "|{}|", l)?;
+    }
+    writeln!(f, "--- attrs")?;
+    for l in &self.attrs {
+      writeln!(f, "|{}|", l)?;
+    }
+    writeln!(f, "--- legend")?;
+    for &(key, ref meaning) in &self.legend {
+      writeln!(f, "{} {}", key, meaning)?;
+    }
+    Ok(())
+  }
+}
+
+/// The letter for the `i`th attribute combination.
+fn key(i: usize) -> char {
+  match KEYS.chars().nth(i) {
+    Some(k) => k,
+    None => panic!("a snapshot tells apart at most {} attribute combinations", KEYS.len()),
+  }
+}
+
+/// What each cell of line `y` shows, with its attributes and colour pair.
+/// The cell after a double width character shows nothing.
+#[cfg(not(feature = "wide"))]
+fn read_row(w: WINDOW, y: i32, cols: i32) -> Vec<(String, attr_t, i32)> {
+  let mut buf: Vec<chtype> = vec![0; cols.max(0) as usize + 1];
+  unsafe { ll::mvwinchnstr(w, y, 0, buf.as_mut_ptr() as ll::chtype_p, cols) };
+  buf[..cols.max(0) as usize].iter()
+    .map(|&ch| (cell_char(ch).to_string(), ch & A_ATTRIBUTES, ::PAIR_NUMBER((ch & A_COLOR) as i32)))
+    .collect()
+}
+
+#[cfg(feature = "wide")]
+fn read_row(w: WINDOW, y: i32, cols: i32) -> Vec<(String, attr_t, i32)> {
+  let mut row = Vec::with_capacity(cols.max(0) as usize);
+  let mut covered = 0;
+  for x in 0..cols {
+    let mut cell: ll::cchar_t = unsafe { ::std::mem::zeroed() };
+    let mut wch: [::libc::wchar_t; 6] = [0; 6];
+    let (mut a, mut pair): (attr_t, i16) = (0, 0);
+    unsafe {
+      ll::mvwin_wch(w, y, x, &mut cell);
+      ll::getcchar(&cell, wch.as_mut_ptr(), &mut a, &mut pair, ::std::ptr::null_mut());
+    }
+    if covered > 0 {
+      covered -= 1;
+      row.push((String::new(), a, pair as i32));
+      continue;
+    }
+    let chars: Vec<char> = wch.iter()
+      .take_while(|&&c| c != 0)
+      .map(|&c| ::std::char::from_u32(c as u32).unwrap_or('?'))
+      .collect();
+    let shown = match chars.first() {
+      None => " ".to_string(),
+      Some(&c) if c.is_ascii() => cell_char(c as chtype | (a & A_ALTCHARSET)).to_string(),
+      Some(&c) if c.is_control() => "?".to_string(),
+      Some(_) => chars.iter().collect(),
+    };
+    covered = ::width::str_width(&shown).max(1) - 1;
+    row.push((shown, a, pair as i32));
+  }
+  row
+}
+
+/// The character shown for a cell; line graphics as box drawing.
+fn cell_char(ch: chtype) -> char {
+  let c = (ch & A_CHARTEXT) as u8;
+  if ch & A_ALTCHARSET != 0 {
+    let acs = match c {
+      b'q' => '─', b'x' => '│', b'l' => '┌', b'k' => '┐', b'm' => '└', b'j' => '┘',
+      b't' => '├', b'u' => '┤', b'w' => '┬', b'v' => '┴', b'n' => '┼',
+      b'a' => '▒', b'~' => '·', b'`' => '◆', b'f' => '°', b'g' => '±', b'0' => '█',
+      _ => '\0',
+    };
+    if acs != '\0' {
+      return acs;
+    }
+  }
+  match c {
+    0 => ' ',
+    0x20..=0x7e => c as char,
+    _ => '?',
+  }
+}
+
+/// Where `assert_window_snapshot!` keeps the snapshot called `name`.
+pub fn snapshot_path(manifest_dir: &str, name: &str) -> PathBuf {
+  Path::new(manifest_dir).join("tests").join("snapshots").join(format!("{}.snap", name))
+}
+
+/// The body of `assert_window_snapshot!`.
+pub fn assert_snapshot<W: RawWindow + ?Sized>(w: &W, name: &str, manifest_dir: &str) {
+  let path = snapshot_path(manifest_dir, name);
+  let actual = Snapshot::capture(w).to_string();
+  let update = env::var("UPDATE_SNAPSHOTS").map(|v| v == "1").unwrap_or(false);
+
+  let expected = match fs::read_to_string(&path) {
+    Ok(s) if !update => s,
+    _ => {
+      if let Some(dir) = path.parent() {
+        let _ = fs::create_dir_all(dir);
+      }
+      fs::write(&path, &actual).unwrap_or_else(|e| panic!("cannot write {}: {}", path.display(), e));
+      eprintln!("wrote window snapshot {}", path.display());
+      return;
+    }
+  };
+  if expected.replace("\r\n", "\n") == actual {
+    return;
+  }
+
+  let new = path.with_extension("snap.new");
+  let _ = fs::write(&new, &actual);
+  let dump = path.with_extension("win");
+  put_window(w.raw_window(), &dump);
+  panic!("window snapshot {:?} differs from {}\n{}\nactual written to {}; \
+          rerun with UPDATE_SNAPSHOTS=1 to accept it",
+         name, path.display(), side_by_side(&expected, &actual), new.display());
+}
+
+/// Dumps `w` with `putwin`, for `getwin`.
+fn put_window(w: WINDOW, path: &Path) {
+  let name = match ::std::ffi::CString::new(path.to_string_lossy().into_owned()) {
+    Ok(n) => n,
+    Err(_) => return,
+  };
+  unsafe {
+    let f = ::libc::fopen(name.as_ptr(), b"wb\0".as_ptr() as *const ::libc::c_char);
+    if !f.is_null() {
+      ::putwin(w, f);
+      ::libc::fclose(f);
+    }
+  }
+}
+
+/// `expected` and `actual` in two columns, differing lines marked `!`.
+pub fn side_by_side(expected: &str, actual: &str) -> String {
+  let left: Vec<&str> = expected.lines().collect();
+  let right: Vec<&str> = actual.lines().collect();
+  let width = left.iter().map(|l| l.chars().count()).max().unwrap_or(0).max("expected".len());
+  let mut out = format!("  {:<width$}   actual\n", "expected", width = width);
+  for i in 0..left.len().max(right.len()) {
+    let (l, r) = (left.get(i).cloned().unwrap_or(""), right.get(i).cloned().unwrap_or(""));
+    let mark = if l == r { ' ' } else { '!' };
+    let pad = width - l.chars().count();
+    out.push_str(&format!("{} {}{} {} {}\n", mark, l, " ".repeat(pad), if l == r { "|" } else { "≠" }, r));
+  }
+  out
+}
+
+/// Compares a window with the snapshot `tests/snapshots/<name>.snap` of the
+/// calling crate; see the `snapshot` module.
+#[macro_export]
+macro_rules! assert_window_snapshot {
+  ($win:expr, $name:expr) => {
+    $crate::snapshot::assert_snapshot(&$win, $name, env!("CARGO_MANIFEST_DIR"))
+  };
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use canvas::{LineCanvas, LineStyle};
+  use glyphs::{BoxStyle, Glyphs, GlyphSet};
+  use rect::Rect;
+  use testing::with_screen;
+
+  fn boxed(set: GlyphSet) -> Snapshot {
+    let mut snap = None;
+    with_screen(|curses| {
+      let w = curses.new_window(3, 6, 0, 0).unwrap();
+      let mut canvas = LineCanvas::for_window(&w);
+      canvas.rect(Rect::new(0, 0, 3, 6), LineStyle::Light);
+      canvas.hline(1, 0, 6, LineStyle::Light);
+      canvas.draw_with(&w, &Glyphs::new(set, BoxStyle::Light));
+      w.mv(1, 2);
+      snap = Some(Snapshot::capture(&w));
+      assert_eq!(w.cursor(), (1, 2));
+    });
+    snap.unwrap()
+  }
+
+  #[test]
+  fn line_canvas_box_round_trips() {
+    let snap = boxed(GlyphSet::Acs);
+    assert_eq!(snap.size(), (3, 6));
+    assert_eq!(snap.text, vec!["┌────┐", "├────┤", "└────┘"]);
+    assert_eq!(snap.attrs, vec!["aaaaaa"; 3]);
+    assert_eq!(snap.legend, vec![('a', "acs".to_string())]);
+  }
+
+  #[cfg(feature = "wide")]
+  #[test]
+  fn unicode_box_and_wide_characters_round_trip() {
+    let snap = boxed(GlyphSet::Unicode);
+    assert_eq!(snap.text, vec!["┌────┐", "├────┤", "└────┘"]);
+    assert_eq!(snap.attrs, vec!["......"; 3]);
+
+    with_screen(|curses| {
+      let w = curses.new_window(1, 6, 0, 0).unwrap();
+      w.mvaddstr(0, 0, "日本ab").unwrap();
+      let snap = Snapshot::capture(&w);
+      assert_eq!(snap.size(), (1, 6));
+      assert_eq!(snap.text, vec!["日本ab"]);
+    });
+  }
+
+  #[test]
+  #[should_panic(expected = "at most 62 attribute combinations")]
+  fn running_out_of_keys_panics() {
+    with_screen(|curses| {
+      let w = curses.new_window(1, 63, 0, 0).unwrap();
+      for x in 0..63 {
+        ::mvwaddch(w.raw(), 0, x, 'x' as chtype | ((x + 1) as chtype) << 16);
+      }
+      Snapshot::capture(&w);
+    });
+  }
+}
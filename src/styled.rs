@@ -0,0 +1,504 @@
+/*
+    Text with mixed attributes and colours.
+
+    A `StyledText` is a list of spans, each with its own `Style`. It can be
+    built by hand or parsed from a small markup language:
+
+        [bold red]error:[/] file [underline]foo.txt[/] not found
+        [yellow on blue]warning[/], [[literal brackets]
+
+    Tags are whitespace separated words: attribute names (`bold`, `dim`,
+    `underline`, `reverse`, `blink`, `standout`, `invis`), colour names
+    (`black` .. `white`, `default`) or numbers for the foreground, and
+    `on <colour>` for the background. `[/]` (or `[/anything]`) closes the
+    innermost open tag; `[[` is a literal `[`.
+
+    Colour pairs are allocated on demand, from `COLOR_PAIRS() - 1`
+    downwards so as not to collide with pairs the application numbers
+    itself from 1.
+*/
+
+use std::cmp::{max, min};
+use std::error::Error;
+use std::fmt;
+use std::sync::Mutex;
+
+use curses::Window;
+use rect::Rect;
+use ll::attr_t;
+use constants::*;
+use width::{graphemes, grapheme_width, str_width};
+pub use width::Align;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Color {
+  /// The terminal's own colour; needs `use_default_colors`.
+  Default,
+  Black,
+  Red,
+  Green,
+  Yellow,
+  Blue,
+  Magenta,
+  Cyan,
+  White,
+  /// Any colour number below `COLORS()`.
+  Indexed(i16),
+}
+
+impl Color {
+  pub fn number(self) -> i16 {
+    match self {
+      Color::Default => -1,
+      Color::Black => COLOR_BLACK,
+      Color::Red => COLOR_RED,
+      Color::Green => COLOR_GREEN,
+      Color::Yellow => COLOR_YELLOW,
+      Color::Blue => COLOR_BLUE,
+      Color::Magenta => COLOR_MAGENTA,
+      Color::Cyan => COLOR_CYAN,
+      Color::White => COLOR_WHITE,
+      Color::Indexed(n) => n,
+    }
+  }
+
+  /// Parses a colour name or number, as used in markup.
+  pub fn from_name(name: &str) -> Option<Color> {
+    Some(match name {
+      "default" => Color::Default,
+      "black" => Color::Black,
+      "red" => Color::Red,
+      "green" => Color::Green,
+      "yellow" => Color::Yellow,
+      "blue" => Color::Blue,
+      "magenta" => Color::Magenta,
+      "cyan" => Color::Cyan,
+      "white" => Color::White,
+      _ => return name.parse::<i16>().ok().filter(|n| *n >= 0).map(Color::Indexed),
+    })
+  }
+}
+
+static PAIRS: Mutex<Vec<(i16, i16)>> = Mutex::new(Vec::new());
+
+/// A colour pair for `fg` on `bg`, initialising a new one the first time
+/// a combination is seen. Returns 0 (the default pair) without colours or
+/// once all pairs are used up.
+///
+/// Pairs are remembered across `endwin`; call `reset_color_pairs` after
+/// starting a new screen.
+pub fn color_pair(fg: i16, bg: i16) -> i16 {
+  if !::has_colors() {
+    return 0;
+  }
+  let mut pairs = PAIRS.lock().unwrap_or_else(|e| e.into_inner());
+  let top = min(COLOR_PAIRS(), i16::MAX as i32 + 1) - 1;
+  if let Some(i) = pairs.iter().position(|&p| p == (fg, bg)) {
+    return (top - i as i32) as i16;
+  }
+  let pair = top - pairs.len() as i32;
+  if pair < 1 || ::init_pair(pair as i16, fg, bg) == ERR {
+    return 0;
+  }
+  pairs.push((fg, bg));
+  pair as i16
+}
+
+/// Forgets every pair handed out by `color_pair`.
+pub fn reset_color_pairs() {
+  PAIRS.lock().unwrap_or_else(|e| e.into_inner()).clear();
+}
+
+/// Attributes plus optional colours. A colour left as `None` is inherited
+/// from the enclosing markup tag, or in the end from the window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Style {
+  pub attrs: attr_t,
+  pub fg: Option<Color>,
+  pub bg: Option<Color>,
+}
+
+impl Style {
+  pub fn new() -> Style {
+    Style::default()
+  }
+
+  pub fn attr(mut self, a: attr_t) -> Style {
+    self.attrs |= a;
+    self
+  }
+
+  pub fn bold(self) -> Style {
+    self.attr(A_BOLD)
+  }
+
+  pub fn dim(self) -> Style {
+    self.attr(A_DIM)
+  }
+
+  pub fn underline(self) -> Style {
+    self.attr(A_UNDERLINE)
+  }
+
+  pub fn reverse(self) -> Style {
+    self.attr(A_REVERSE)
+  }
+
+  pub fn fg(mut self, c: Color) -> Style {
+    self.fg = Some(c);
+    self
+  }
+
+  pub fn bg(mut self, c: Color) -> Style {
+    self.bg = Some(c);
+    self
+  }
+
+  /// `other` drawn over `self`: attributes add up, colours replace.
+  pub fn patch(self, other: Style) -> Style {
+    Style {
+      attrs: self.attrs | other.attrs,
+      fg: other.fg.or(self.fg),
+      bg: other.bg.or(self.bg),
+    }
+  }
+
+  /// The attributes and colour pair to draw with, over a window whose
+  /// current attributes are `base_attrs` and pair `base_pair`.
+  pub fn resolve(&self, base_attrs: attr_t, base_pair: i16) -> (attr_t, i16) {
+    let attrs = (base_attrs | self.attrs) & !A_COLOR;
+    if self.fg.is_none() && self.bg.is_none() {
+      return (attrs, base_pair);
+    }
+    let (mut f, mut b) = (0, 0);
+    ::pair_content(base_pair, &mut f, &mut b);
+    let f = self.fg.map_or(f, Color::number);
+    let b = self.bg.map_or(b, Color::number);
+    (attrs, color_pair(f, b))
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Span {
+  pub text: String,
+  pub style: Style,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MarkupError {
+  /// Byte offset of the offending tag.
+  pub offset: usize,
+  pub message: String,
+}
+
+impl fmt::Display for MarkupError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{} at byte {}", self.message, self.offset)
+  }
+}
+
+impl Error for MarkupError {}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct StyledText {
+  spans: Vec<Span>,
+}
+
+impl StyledText {
+  pub fn new() -> StyledText {
+    StyledText::default()
+  }
+
+  pub fn plain(text: &str) -> StyledText {
+    StyledText::new().push(text, Style::new())
+  }
+
+  /// Appends a span; consecutive spans with the same style are merged.
+  pub fn push(mut self, text: &str, style: Style) -> StyledText {
+    self.push_span(text, style);
+    self
+  }
+
+  pub fn push_span(&mut self, text: &str, style: Style) {
+    if text.is_empty() {
+      return;
+    }
+    if let Some(last) = self.spans.last_mut() {
+      if last.style == style {
+        last.text.push_str(text);
+        return;
+      }
+    }
+    self.spans.push(Span { text: text.to_string(), style });
+  }
+
+  pub fn spans(&self) -> &[Span] {
+    &self.spans
+  }
+
+  /// The text without any styling.
+  pub fn text(&self) -> String {
+    self.spans.iter().map(|s| &s.text[..]).collect()
+  }
+
+  /// Display width of the widest line.
+  pub fn width(&self) -> usize {
+    self.text().split('\n').map(str_width).max().unwrap_or(0)
+  }
+
+  /// Parses markup such as `[bold red]text[/]`.
+  pub fn parse(markup: &str) -> Result<StyledText, MarkupError> {
+    let mut out = StyledText::new();
+    let mut stack = vec![Style::new()];
+    let mut text = String::new();
+    let mut rest = markup;
+
+    while let Some(open) = rest.find('[') {
+      text.push_str(&rest[..open]);
+      let offset = markup.len() - rest.len() + open;
+      rest = &rest[open + 1..];
+      if rest.starts_with('[') {
+        text.push('[');
+        rest = &rest[1..];
+        continue;
+      }
+      let close = match rest.find(']') {
+        Some(c) => c,
+        None => return Err(MarkupError { offset, message: "unclosed tag".to_string() }),
+      };
+      let tag = &rest[..close];
+      rest = &rest[close + 1..];
+
+      out.push_span(&text, *stack.last().unwrap());
+      text.clear();
+
+      if tag.starts_with('/') {
+        if stack.len() == 1 {
+          return Err(MarkupError { offset, message: format!("[{}] closes nothing", tag) });
+        }
+        stack.pop();
+      } else {
+        let style = parse_tag(tag).map_err(|message| MarkupError { offset, message })?;
+        let top = *stack.last().unwrap();
+        stack.push(top.patch(style));
+      }
+    }
+    text.push_str(rest);
+    out.push_span(&text, *stack.last().unwrap());
+    Ok(out)
+  }
+
+  /// Lines of `(grapheme cluster, span index)`, split at newlines and, if
+  /// `wrap` is set, wrapped at spaces to fit `cols`.
+  fn layout(&self, cols: usize, wrap: bool) -> Vec<Vec<(&str, usize)>> {
+    let mut lines = vec![Vec::new()];
+    for (i, span) in self.spans.iter().enumerate() {
+      for c in graphemes(&span.text) {
+        if c == "\n" || c == "\r\n" {
+          lines.push(Vec::new());
+        } else {
+          lines.last_mut().unwrap().push((c, i));
+        }
+      }
+    }
+    if !wrap {
+      return lines;
+    }
+    let mut out = Vec::new();
+    for line in lines {
+      wrap_line(&line, cols, &mut out);
+    }
+    out
+  }
+
+  /// Draws into `area` of `w`, clipped to the area and the window, and
+  /// returns how many lines of the area it used. The window's attributes
+  /// are the base for every span and are restored afterwards.
+  pub fn render(&self, w: &Window, area: Rect, align: Align, wrap: bool) -> usize {
+    let (wl, wc) = w.size();
+    let clip = area.intersect(&Rect::new(0, 0, wl, wc));
+    if clip.is_empty() || area.is_empty() {
+      return 0;
+    }
+    let cols = area.cols as usize;
+
+    let (mut base_attrs, mut base_pair) = (0, 0);
+    ::wattr_get(w.raw(), &mut base_attrs, &mut base_pair);
+    let resolved: Vec<(attr_t, i16)> = self.spans.iter().map(|s| s.style.resolve(base_attrs, base_pair)).collect();
+
+    let lines = self.layout(cols, wrap);
+    let used = min(lines.len(), area.lines as usize);
+    for (row, line) in lines.iter().take(used).enumerate() {
+      let y = area.y + row as i32;
+      if y < clip.y || y >= clip.bottom() {
+        continue;
+      }
+      // Drop whatever does not fit, then place what is left.
+      let mut fitted = 0;
+      let mut end = 0;
+      for &(c, _) in line {
+        let cw = grapheme_width(c);
+        if fitted + cw > cols {
+          break;
+        }
+        fitted += cw;
+        end += 1;
+      }
+      let mut x = area.x + match align {
+        Align::Left => 0,
+        Align::Center => (cols - fitted) / 2,
+        Align::Right => cols - fitted,
+      } as i32;
+
+      let mut run = String::new();
+      let mut run_x = x;
+      let mut run_span = None;
+      for &(c, span) in &line[..end] {
+        let cw = grapheme_width(c) as i32;
+        let visible = x >= clip.x && x + max(cw, 1) <= clip.right();
+        if run_span != Some(span) || !visible {
+          flush(w, y, run_x, &run, run_span.map(|s| resolved[s]));
+          run.clear();
+          run_span = Some(span);
+          run_x = x;
+        }
+        if visible {
+          run.push_str(c);
+        } else {
+          run_x = x + cw;
+        }
+        x += cw;
+      }
+      flush(w, y, run_x, &run, run_span.map(|s| resolved[s]));
+    }
+    w.attr_set(base_attrs, base_pair);
+    used
+  }
+
+  /// Draws a single line at `(y, x)`, clipped at the right edge of `w`.
+  pub fn print(&self, w: &Window, y: i32, x: i32) -> usize {
+    let (_, cols) = w.size();
+    self.render(w, Rect::new(y, x, 1, cols - x), Align::Left, false)
+  }
+}
+
+fn flush(w: &Window, y: i32, x: i32, run: &str, style: Option<(attr_t, i16)>) {
+  if let (false, Some((attrs, pair))) = (run.is_empty(), style) {
+    w.attr_set(attrs, pair);
+    // Control characters have no width and would move the cursor.
+    let text: String = run.chars().filter(|c| !c.is_control()).collect();
+    w.mvaddstr(y, x, &text).ok();
+  }
+}
+
+/// Greedy word wrap of one line into lines no wider than `cols`. Words
+/// longer than a line are broken wherever they hit the edge.
+fn wrap_line<'a>(line: &[(&'a str, usize)], cols: usize, out: &mut Vec<Vec<(&'a str, usize)>>) {
+  if line.is_empty() {
+    out.push(Vec::new());
+    return;
+  }
+  let mut start = 0;
+  while start < line.len() {
+    let mut width = 0;
+    let mut space = None;
+    let mut end = line.len();
+    for (i, &(c, _)) in line.iter().enumerate().skip(start) {
+      if c == " " {
+        space = Some(i);
+      }
+      width += grapheme_width(c);
+      if width > cols {
+        end = match space {
+          Some(s) if s > start => s,
+          _ => max(i, start + 1),
+        };
+        break;
+      }
+    }
+    out.push(line[start..end].to_vec());
+    start = end;
+    if start < line.len() {
+      while start < line.len() && line[start].0 == " " {
+        start += 1;
+      }
+    }
+  }
+}
+
+fn parse_tag(tag: &str) -> Result<Style, String> {
+  let mut style = Style::new();
+  let mut words = tag.split_whitespace();
+  if tag.trim().is_empty() {
+    return Err("empty tag".to_string());
+  }
+  while let Some(word) = words.next() {
+    style = match word {
+      "bold" => style.attr(A_BOLD),
+      "dim" => style.attr(A_DIM),
+      "underline" => style.attr(A_UNDERLINE),
+      "reverse" => style.attr(A_REVERSE),
+      "blink" => style.attr(A_BLINK),
+      "standout" => style.attr(A_STANDOUT),
+      "invis" => style.attr(A_INVIS),
+      "on" => {
+        let name = words.next().ok_or_else(|| "`on` without a colour".to_string())?;
+        style.bg(Color::from_name(name).ok_or_else(|| format!("unknown colour `{}`", name))?)
+      }
+      _ => style.fg(Color::from_name(word).ok_or_else(|| format!("unknown style `{}`", word))?),
+    };
+  }
+  Ok(style)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn spans(markup: &str) -> Vec<(String, Style)> {
+    StyledText::parse(markup).unwrap().spans().iter().map(|s| (s.text.clone(), s.style)).collect()
+  }
+
+  #[test]
+  fn parse_nested_tags() {
+    let bold = Style::new().bold();
+    assert_eq!(spans("a[bold]b[red on blue]c[underline]d[/]e[/]f[/]g"), vec![
+      ("a".to_string(), Style::new()),
+      ("b".to_string(), bold),
+      ("c".to_string(), bold.fg(Color::Red).bg(Color::Blue)),
+      ("d".to_string(), bold.underline().fg(Color::Red).bg(Color::Blue)),
+      ("e".to_string(), bold.fg(Color::Red).bg(Color::Blue)),
+      ("f".to_string(), bold),
+      ("g".to_string(), Style::new()),
+    ]);
+    assert_eq!(spans("[red]a[green]b[/anything]c"), vec![
+      ("a".to_string(), Style::new().fg(Color::Red)),
+      ("b".to_string(), Style::new().fg(Color::Green)),
+      ("c".to_string(), Style::new().fg(Color::Red)),
+    ]);
+  }
+
+  #[test]
+  fn parse_literals_and_merges() {
+    assert_eq!(spans("[[x] [bold][/]y"), vec![("[x] y".to_string(), Style::new())]);
+    assert_eq!(spans("[7 on default]n"), vec![("n".to_string(), Style::new().fg(Color::Indexed(7)).bg(Color::Default))]);
+    assert_eq!(StyledText::parse("").unwrap(), StyledText::new());
+  }
+
+  #[test]
+  fn parse_leaves_unterminated_tags_open() {
+    assert_eq!(spans("[bold]open to the end"), vec![("open to the end".to_string(), Style::new().bold())]);
+  }
+
+  #[test]
+  fn parse_errors() {
+    let err = |markup: &str| StyledText::parse(markup).unwrap_err();
+    assert_eq!(err("ok [bold"), MarkupError { offset: 3, message: "unclosed tag".to_string() });
+    assert_eq!(err("x[/]"), MarkupError { offset: 1, message: "[/] closes nothing".to_string() });
+    assert_eq!(err("[bold][/][/]").offset, 9);
+    assert_eq!(err("[]").message, "empty tag");
+    assert_eq!(err("[bold on]").message, "`on` without a colour");
+    assert_eq!(err("[on mauve]").message, "unknown colour `mauve`");
+    assert_eq!(err("[-1]").message, "unknown style `-1`");
+  }
+}
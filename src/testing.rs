@@ -0,0 +1,28 @@
+/*
+    A screen for unit tests that draw.
+
+    `with_screen` runs a test with the curses token held, on a screen that
+    `newterm` opens once per test binary, as an `xterm` writing to
+    `/dev/null`, in a UTF-8 locale. Tests create their own windows on it.
+*/
+
+use std::sync::Once;
+
+use curses::Curses;
+use constants::LcCategory;
+
+static SCREEN: Once = Once::new();
+
+pub fn with_screen<F: FnOnce(&Curses)>(f: F) {
+  let curses = Curses::lock();
+  SCREEN.call_once(|| unsafe {
+    ::setlocale(LcCategory::all, "C.UTF-8").ok();
+    let null = b"/dev/null\0".as_ptr() as *const ::libc::c_char;
+    let out = ::libc::fopen(null, b"w\0".as_ptr() as *const ::libc::c_char);
+    let input = ::libc::fopen(null, b"r\0".as_ptr() as *const ::libc::c_char);
+    let screen = ::newterm(Some("xterm"), out as ::ll::FILE_p, input as ::ll::FILE_p).unwrap();
+    assert!(!screen.is_null(), "newterm failed; is the xterm terminfo entry installed?");
+  });
+  ::werase(::stdscr());
+  f(&curses);
+}
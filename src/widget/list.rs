@@ -0,0 +1,414 @@
+/*
+    A scrolling list of items.
+
+    Items come from a `ListSource`, which is only asked for the rows that
+    are on screen (and, while searching, for the rows being compared, at
+    most `SEARCH_ROWS` of them unless the source has its own `find`), so
+    a source can compute or load them lazily and hold millions of them.
+    Selections are kept as a set of indices rather than per item.
+*/
+
+use std::borrow::Cow;
+use std::cmp::{max, min};
+use std::collections::BTreeSet;
+
+use curses::Window;
+use rect::Rect;
+use constants::*;
+use styled::{Align, Style};
+use width::str_width;
+use widget::{Request, Response};
+
+/// How many rows a type-ahead search compares, from the cursor on, when
+/// the source does not search itself.
+pub const SEARCH_ROWS: usize = 1024;
+
+/// Where a `List` gets its items from.
+pub trait ListSource {
+  fn len(&self) -> usize;
+
+  /// The text shown for item `index`.
+  fn label(&self, index: usize) -> Cow<'_, str>;
+
+  /// The first item from `from` on (or back, if `!forward`), wrapping
+  /// around, whose label starts with `pattern` ignoring case. Only looks
+  /// at `SEARCH_ROWS` items; a source with an index of its own, or cheap
+  /// labels, can search them all.
+  fn find(&self, pattern: &str, from: usize, forward: bool) -> Option<usize> {
+    scan(self, pattern, from, forward, SEARCH_ROWS)
+  }
+
+  /// The style of item `index`, drawn over the window's attributes.
+  fn style(&self, _index: usize) -> Style {
+    Style::new()
+  }
+
+  fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+}
+
+impl<T: AsRef<str>> ListSource for Vec<T> {
+  fn len(&self) -> usize {
+    <[T]>::len(self)
+  }
+
+  fn label(&self, index: usize) -> Cow<'_, str> {
+    Cow::Borrowed(self[index].as_ref())
+  }
+
+  fn find(&self, pattern: &str, from: usize, forward: bool) -> Option<usize> {
+    scan(self, pattern, from, forward, usize::MAX)
+  }
+}
+
+impl<T: AsRef<str>> ListSource for &[T] {
+  fn len(&self) -> usize {
+    <[T]>::len(self)
+  }
+
+  fn label(&self, index: usize) -> Cow<'_, str> {
+    Cow::Borrowed(self[index].as_ref())
+  }
+
+  fn find(&self, pattern: &str, from: usize, forward: bool) -> Option<usize> {
+    scan(self, pattern, from, forward, usize::MAX)
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SelectionMode {
+  /// Only the cursor; `ToggleItem` is denied.
+  Single,
+  /// Any number of items, toggled with `ToggleItem`.
+  Multi,
+}
+
+#[derive(Debug)]
+pub struct List<S> {
+  source: S,
+  mode: SelectionMode,
+  selected: BTreeSet<usize>,
+  cursor: usize,
+  top: usize,
+  page: usize,
+  cyclic: bool,
+  pattern: String,
+  mark: String,
+  cursor_style: Style,
+  selected_style: Style,
+  /// Where the list was last drawn, in screen coordinates, for mouse
+  /// events.
+  screen_area: Rect,
+}
+
+fn starts_with_ignore_case(label: &str, pattern: &str) -> bool {
+  let mut l = label.chars().flat_map(char::to_lowercase);
+  pattern.chars().flat_map(char::to_lowercase).all(|p| l.next() == Some(p))
+}
+
+/// `ListSource::find` comparing at most `rows` labels.
+fn scan<S: ListSource + ?Sized>(source: &S, pattern: &str, from: usize, forward: bool, rows: usize) -> Option<usize> {
+  let len = source.len();
+  (0..min(len, rows)).map(|i| if forward { (from + i) % len } else { (from + len - i) % len })
+    .find(|&i| starts_with_ignore_case(&source.label(i), pattern))
+}
+
+impl<S: ListSource> List<S> {
+  pub fn new(source: S) -> List<S> {
+    List {
+      source,
+      mode: SelectionMode::Single,
+      selected: BTreeSet::new(),
+      cursor: 0,
+      top: 0,
+      page: 1,
+      cyclic: true,
+      pattern: String::new(),
+      mark: String::from("*"),
+      cursor_style: Style::new().reverse(),
+      selected_style: Style::new().bold(),
+      screen_area: Rect::default(),
+    }
+  }
+
+  pub fn source(&self) -> &S {
+    &self.source
+  }
+
+  /// The source, for changing its items. The cursor and selection are
+  /// clamped to the new length on the next draw.
+  pub fn source_mut(&mut self) -> &mut S {
+    &mut self.source
+  }
+
+  pub fn selection_mode(mut self, mode: SelectionMode) -> List<S> {
+    self.mode = mode;
+    self
+  }
+
+  /// Whether `NextItem`/`PrevItem` wrap around, as without `O_NONCYCLIC`.
+  pub fn cyclic(mut self, on: bool) -> List<S> {
+    self.cyclic = on;
+    self
+  }
+
+  /// The marker shown before selected items in multi mode, like
+  /// `set_menu_mark`.
+  pub fn mark(mut self, mark: &str) -> List<S> {
+    self.mark = mark.to_string();
+    self
+  }
+
+  pub fn cursor_style(mut self, style: Style) -> List<S> {
+    self.cursor_style = style;
+    self
+  }
+
+  pub fn selected_style(mut self, style: Style) -> List<S> {
+    self.selected_style = style;
+    self
+  }
+
+  pub fn cursor(&self) -> usize {
+    self.cursor
+  }
+
+  pub fn set_cursor(&mut self, index: usize) {
+    self.cursor = min(index, self.source.len().saturating_sub(1));
+    self.scroll_to_cursor();
+  }
+
+  /// The first item on screen.
+  pub fn top(&self) -> usize {
+    self.top
+  }
+
+  pub fn pattern(&self) -> &str {
+    &self.pattern
+  }
+
+  /// Selected items in ascending order; in single mode, the cursor.
+  pub fn selected(&self) -> Vec<usize> {
+    match self.mode {
+      SelectionMode::Single if self.source.is_empty() => Vec::new(),
+      SelectionMode::Single => vec![self.cursor],
+      SelectionMode::Multi => self.selected.iter().cloned().collect(),
+    }
+  }
+
+  pub fn is_selected(&self, index: usize) -> bool {
+    match self.mode {
+      SelectionMode::Single => index == self.cursor,
+      SelectionMode::Multi => self.selected.contains(&index),
+    }
+  }
+
+  /// Selects or deselects an item; ignored in single mode.
+  pub fn set_selected(&mut self, index: usize, on: bool) {
+    if self.mode == SelectionMode::Multi && index < self.source.len() {
+      if on {
+        self.selected.insert(index);
+      } else {
+        self.selected.remove(&index);
+      }
+    }
+  }
+
+  fn scroll_to_cursor(&mut self) {
+    if self.cursor < self.top {
+      self.top = self.cursor;
+    } else if self.cursor >= self.top + self.page {
+      self.top = self.cursor + 1 - self.page;
+    }
+  }
+
+  fn move_to(&mut self, index: usize) -> Response {
+    self.pattern.clear();
+    self.cursor = index;
+    self.scroll_to_cursor();
+    Response::Handled
+  }
+
+  fn search(&mut self, pattern: String, from: usize, forward: bool) -> Response {
+    match self.source.find(&pattern, from, forward).filter(|&i| i < self.source.len()) {
+      Some(i) => {
+        self.cursor = i;
+        self.scroll_to_cursor();
+        self.pattern = pattern;
+        Response::Handled
+      }
+      None => Response::NoMatch,
+    }
+  }
+
+  /// Carries out `req`, like `menu_driver`.
+  pub fn driver(&mut self, req: Request) -> Response {
+    let len = self.source.len();
+    if len == 0 {
+      return Response::Denied;
+    }
+    let last = len - 1;
+    let c = self.cursor;
+    match req {
+      Request::UpItem if c > 0 => self.move_to(c - 1),
+      Request::DownItem if c < last => self.move_to(c + 1),
+      Request::PrevItem if c > 0 => self.move_to(c - 1),
+      Request::PrevItem if self.cyclic => self.move_to(last),
+      Request::NextItem if c < last => self.move_to(c + 1),
+      Request::NextItem if self.cyclic => self.move_to(0),
+      Request::FirstItem => self.move_to(0),
+      Request::LastItem => self.move_to(last),
+      Request::ScrollUpLine if self.top > 0 => {
+        self.top -= 1;
+        self.move_to(min(c, self.top + self.page - 1))
+      }
+      Request::ScrollDownLine if self.top + self.page < len => {
+        self.top += 1;
+        self.move_to(max(c, self.top))
+      }
+      Request::ScrollUpPage if self.top > 0 => {
+        self.top = self.top.saturating_sub(self.page);
+        self.move_to(c.saturating_sub(self.page))
+      }
+      Request::ScrollDownPage if self.top + self.page < len => {
+        self.top = min(self.top + self.page, len - min(len, self.page));
+        self.move_to(min(c + self.page, last))
+      }
+      Request::ToggleItem if self.mode == SelectionMode::Multi => {
+        let on = !self.selected.contains(&c);
+        self.set_selected(c, on);
+        Response::Handled
+      }
+      Request::ClearPattern => {
+        self.pattern.clear();
+        Response::Handled
+      }
+      Request::BackPattern if !self.pattern.is_empty() => {
+        self.pattern.pop();
+        Response::Handled
+      }
+      Request::NextMatch if !self.pattern.is_empty() => {
+        let p = self.pattern.clone();
+        self.search(p, (c + 1) % len, true)
+      }
+      Request::PrevMatch if !self.pattern.is_empty() => {
+        let p = self.pattern.clone();
+        self.search(p, (c + len - 1) % len, false)
+      }
+      Request::Char(ch) => {
+        let mut p = self.pattern.clone();
+        p.push(ch);
+        self.search(p, c, true)
+      }
+      _ => Response::Denied,
+    }
+  }
+
+  /// Handles a key from `getch`: the bindings of `Request::from_key`,
+  /// Enter to activate, and with `KEY_MOUSE`, clicks, double clicks and
+  /// the wheel. Mouse events need `mousemask` set by the application.
+  /// While a search is in progress, Space goes into the pattern.
+  pub fn handle_key(&mut self, key: i32) -> Response {
+    match key {
+      KEY_ENTER | 0x0A | 0x0D if !self.source.is_empty() => Response::Activated(self.cursor),
+      0x20 if !self.pattern.is_empty() => self.driver(Request::Char(' ')),
+      #[cfg(ncurses_mouse)]
+      KEY_MOUSE => {
+        let mut ev = ::MEVENT { id: 0, x: 0, y: 0, z: 0, bstate: 0 };
+        if ::getmouse(&mut ev) == OK {
+          self.handle_mouse(&ev)
+        } else {
+          Response::Unknown
+        }
+      }
+      _ => match Request::from_key(key) {
+        Some(req) => self.driver(req),
+        None => Response::Unknown,
+      },
+    }
+  }
+
+  #[cfg(ncurses_mouse)]
+  pub fn handle_mouse(&mut self, ev: &::MEVENT) -> Response {
+    let b = ev.bstate as i32;
+    if b & BUTTON4_PRESSED != 0 {
+      for _ in 0..3 {
+        self.driver(Request::ScrollUpLine);
+      }
+      return Response::Handled;
+    }
+    if b & BUTTON5_PRESSED != 0 {
+      for _ in 0..3 {
+        self.driver(Request::ScrollDownLine);
+      }
+      return Response::Handled;
+    }
+    if !self.screen_area.contains(ev.y, ev.x) {
+      return Response::Unknown;
+    }
+    let index = self.top + (ev.y - self.screen_area.y) as usize;
+    if index >= self.source.len() {
+      return Response::Denied;
+    }
+    if b & BUTTON1_DOUBLE_CLICKED != 0 {
+      self.move_to(index);
+      Response::Activated(index)
+    } else if b & (BUTTON1_CLICKED | BUTTON1_PRESSED) != 0 {
+      self.move_to(index);
+      if self.mode == SelectionMode::Multi {
+        self.driver(Request::ToggleItem);
+      }
+      Response::Handled
+    } else {
+      Response::Unknown
+    }
+  }
+
+  /// Draws the visible items into `area` of `w`. Rows past the last item
+  /// are cleared.
+  pub fn draw(&mut self, w: &Window, area: Rect) -> i32 {
+    let len = self.source.len();
+    self.page = max(1, area.lines) as usize;
+    self.cursor = min(self.cursor, len.saturating_sub(1));
+    self.selected = self.selected.range(..len).cloned().collect();
+    self.top = min(self.top, len.saturating_sub(self.page));
+    self.scroll_to_cursor();
+
+    let (oy, ox) = w.origin();
+    self.screen_area = Rect::new(oy + area.y, ox + area.x, area.lines, area.cols);
+
+    let (mut base_attrs, mut base_pair) = (0, 0);
+    ::wattr_get(w.raw(), &mut base_attrs, &mut base_pair);
+    let multi = self.mode == SelectionMode::Multi;
+    let mark_cols = if multi { str_width(&self.mark) } else { 0 };
+    let blank: String = (0..mark_cols).map(|_| ' ').collect();
+
+    let mut ret = OK;
+    for row in 0..self.page {
+      let y = area.y + row as i32;
+      let index = self.top + row;
+      if index >= len {
+        w.attr_set(base_attrs, base_pair);
+        w.put_str_padded(y, area.x, "", area.cols, Align::Left).ok();
+        continue;
+      }
+      let mut style = self.source.style(index);
+      if multi && self.selected.contains(&index) {
+        style = style.patch(self.selected_style);
+      }
+      if index == self.cursor {
+        style = style.patch(self.cursor_style);
+      }
+      let (attrs, pair) = style.resolve(base_attrs, base_pair);
+      w.attr_set(attrs, pair);
+      let mark = if multi && self.selected.contains(&index) { &self.mark } else { &blank };
+      let text = format!("{}{}", mark, self.source.label(index));
+      if w.put_str_padded(y, area.x, &text, area.cols, Align::Left) != Ok(OK) {
+        ret = ERR;
+      }
+    }
+    w.attr_set(base_attrs, base_pair);
+    ret
+  }
+}
@@ -0,0 +1,173 @@
+/*
+    Widgets drawn with plain windows rather than libmenu/libform.
+
+    Widgets do not own a window: they are drawn into an area of one and
+    are fed keys (or `Request`s) by the application. Requests use the same
+    vocabulary as `menu_driver`, and convert to and from the `REQ_*` codes,
+    so code written against libmenu ports over directly.
+*/
+
+use constants::*;
+use menu::constants::*;
+#[cfg(feature = "wide")]
+use WchResult;
+
+pub mod list;
+pub mod table;
+pub mod progress;
+
+pub use self::list::{List, ListSource, SelectionMode};
+pub use self::table::{Table, TableSource, Column, SortOrder};
+pub use self::progress::{ProgressBar, Spinner, Gauge, Throttle};
+
+/// A widget driver request, named after the `REQ_*` menu requests.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Request {
+  LeftItem,
+  RightItem,
+  UpItem,
+  DownItem,
+  ScrollUpLine,
+  ScrollDownLine,
+  ScrollDownPage,
+  ScrollUpPage,
+  FirstItem,
+  LastItem,
+  NextItem,
+  PrevItem,
+  ToggleItem,
+  ClearPattern,
+  BackPattern,
+  NextMatch,
+  PrevMatch,
+  /// A character typed into the search pattern.
+  Char(char),
+}
+
+impl Request {
+  /// The request for a `REQ_*` code, or for a printable ASCII character.
+  /// Other bytes from `getch` may be part of a UTF-8 sequence and are left
+  /// out; see `from_wch` for characters beyond ASCII.
+  pub fn from_code(code: i32) -> Option<Request> {
+    Some(match code {
+      REQ_LEFT_ITEM => Request::LeftItem,
+      REQ_RIGHT_ITEM => Request::RightItem,
+      REQ_UP_ITEM => Request::UpItem,
+      REQ_DOWN_ITEM => Request::DownItem,
+      REQ_SCR_ULINE => Request::ScrollUpLine,
+      REQ_SCR_DLINE => Request::ScrollDownLine,
+      REQ_SCR_DPAGE => Request::ScrollDownPage,
+      REQ_SCR_UPAGE => Request::ScrollUpPage,
+      REQ_FIRST_ITEM => Request::FirstItem,
+      REQ_LAST_ITEM => Request::LastItem,
+      REQ_NEXT_ITEM => Request::NextItem,
+      REQ_PREV_ITEM => Request::PrevItem,
+      REQ_TOGGLE_ITEM => Request::ToggleItem,
+      REQ_CLEAR_PATTERN => Request::ClearPattern,
+      REQ_BACK_PATTERN => Request::BackPattern,
+      REQ_NEXT_MATCH => Request::NextMatch,
+      REQ_PREV_MATCH => Request::PrevMatch,
+      c @ 0x20..=0x7E => Request::Char(c as u8 as char),
+      _ => return None,
+    })
+  }
+
+  /// The `REQ_*` code, or the character itself for `Char`.
+  pub fn code(&self) -> i32 {
+    match *self {
+      Request::LeftItem => REQ_LEFT_ITEM,
+      Request::RightItem => REQ_RIGHT_ITEM,
+      Request::UpItem => REQ_UP_ITEM,
+      Request::DownItem => REQ_DOWN_ITEM,
+      Request::ScrollUpLine => REQ_SCR_ULINE,
+      Request::ScrollDownLine => REQ_SCR_DLINE,
+      Request::ScrollDownPage => REQ_SCR_DPAGE,
+      Request::ScrollUpPage => REQ_SCR_UPAGE,
+      Request::FirstItem => REQ_FIRST_ITEM,
+      Request::LastItem => REQ_LAST_ITEM,
+      Request::NextItem => REQ_NEXT_ITEM,
+      Request::PrevItem => REQ_PREV_ITEM,
+      Request::ToggleItem => REQ_TOGGLE_ITEM,
+      Request::ClearPattern => REQ_CLEAR_PATTERN,
+      Request::BackPattern => REQ_BACK_PATTERN,
+      Request::NextMatch => REQ_NEXT_MATCH,
+      Request::PrevMatch => REQ_PREV_MATCH,
+      Request::Char(c) => c as i32,
+    }
+  }
+
+  /// The usual key bindings: arrows, paging keys, Home/End, space to
+  /// toggle, backspace to edit the pattern, and characters to search.
+  pub fn from_key(key: i32) -> Option<Request> {
+    Some(match key {
+      KEY_LEFT => Request::LeftItem,
+      KEY_RIGHT => Request::RightItem,
+      KEY_UP => Request::UpItem,
+      KEY_DOWN => Request::DownItem,
+      KEY_NPAGE => Request::ScrollDownPage,
+      KEY_PPAGE => Request::ScrollUpPage,
+      KEY_HOME => Request::FirstItem,
+      KEY_END => Request::LastItem,
+      0x20 => Request::ToggleItem,
+      KEY_BACKSPACE | 0x08 | 0x7F => Request::BackPattern,
+      _ => return Request::from_code(key),
+    })
+  }
+
+  /// `from_key` for what `get_wch` returns, taking any printable character
+  /// as a `Char`.
+  #[cfg(feature = "wide")]
+  pub fn from_wch(wch: WchResult) -> Option<Request> {
+    match wch {
+      WchResult::KeyCode(key) => Request::from_key(key),
+      WchResult::Char(c) => match ::std::char::from_u32(c) {
+        Some(c) if c.is_ascii() => Request::from_key(c as i32),
+        Some(c) if !c.is_control() => Some(Request::Char(c)),
+        _ => None,
+      },
+    }
+  }
+}
+
+/// What a driver did with a request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Response {
+  /// The request was carried out.
+  Handled,
+  /// The request made no sense here, e.g. moving up from the first item.
+  Denied,
+  /// A search found nothing; the pattern is left as it was.
+  NoMatch,
+  /// The key or request is not one the widget handles.
+  Unknown,
+  /// The item was chosen, with Enter or a double click.
+  Activated(usize),
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn from_code_takes_printable_ascii_only() {
+    assert_eq!(Request::from_code('a' as i32), Some(Request::Char('a')));
+    assert_eq!(Request::from_code('~' as i32), Some(Request::Char('~')));
+    assert_eq!(Request::from_code(REQ_NEXT_MATCH), Some(Request::NextMatch));
+    for &code in &[0x1F, 0x7F, 0x80, 0xC3, 0xA9, 0xFF, KEY_MIN - 1, KEY_DOWN] {
+      assert_eq!(Request::from_code(code), None, "{:#x}", code);
+    }
+    assert_eq!(Request::from_key(0x20), Some(Request::ToggleItem));
+    assert_eq!(Request::from_key(0xC3), None);
+  }
+
+  #[cfg(feature = "wide")]
+  #[test]
+  fn from_wch_takes_any_printable_character() {
+    assert_eq!(Request::from_wch(WchResult::Char(0xE9)), Some(Request::Char('\u{e9}')));
+    assert_eq!(Request::from_wch(WchResult::Char(0x65E5)), Some(Request::Char('\u{65e5}')));
+    assert_eq!(Request::from_wch(WchResult::Char(0x20)), Some(Request::ToggleItem));
+    assert_eq!(Request::from_wch(WchResult::Char(0x7F)), Some(Request::BackPattern));
+    assert_eq!(Request::from_wch(WchResult::Char(0x85)), None);
+    assert_eq!(Request::from_wch(WchResult::KeyCode(KEY_DOWN)), Some(Request::DownItem));
+  }
+}
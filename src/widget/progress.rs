@@ -0,0 +1,258 @@
+/*
+    Progress bars, spinners and gauges.
+
+    These draw into a single line of any window and leave refreshing to the
+    caller, so a status line can be updated with a `wnoutrefresh` of its own
+    window rather than a full-screen refresh. `Throttle` limits how often
+    that happens when progress is reported faster than anyone can read it.
+
+    With the `wide` feature and Unicode glyphs, bars use the eighth blocks
+    (U+2589..U+258F) for a resolution of 1/8 of a cell; with the alternate
+    character set a half-filled cell is `ACS_CKBOARD` and full ones
+    `ACS_BLOCK`; in plain ASCII only whole cells are shown.
+*/
+
+use std::cmp::{max, min};
+use std::time::{Duration, Instant};
+
+use curses::Window;
+use constants::*;
+use glyphs::{Glyph, Glyphs, GlyphSet, Symbol};
+use styled::{Align, Style};
+use width::pad;
+
+fn clamp_fraction(f: f64) -> f64 {
+  if f.is_nan() { 0.0 } else { f.clamp(0.0, 1.0) }
+}
+
+/// A determinate progress bar.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ProgressBar {
+  glyphs: Glyphs,
+  style: Style,
+}
+
+impl Default for ProgressBar {
+  fn default() -> ProgressBar {
+    ProgressBar::new()
+  }
+}
+
+impl ProgressBar {
+  pub fn new() -> ProgressBar {
+    ProgressBar { glyphs: Glyphs::current(), style: Style::new() }
+  }
+
+  pub fn glyphs(mut self, glyphs: Glyphs) -> ProgressBar {
+    self.glyphs = glyphs;
+    self
+  }
+
+  /// Style of the filled part.
+  pub fn style(mut self, style: Style) -> ProgressBar {
+    self.style = style;
+    self
+  }
+
+  /// Steps each cell is divided into.
+  fn steps(&self) -> usize {
+    match self.glyphs.set() {
+      GlyphSet::Unicode if cfg!(feature = "wide") => 8,
+      GlyphSet::Unicode | GlyphSet::Acs => 2,
+      GlyphSet::Ascii => 1,
+    }
+  }
+
+  /// The glyph for a cell filled `eighths`/8, 0 < eighths < 8.
+  fn partial(&self, eighths: usize) -> Glyph {
+    match self.steps() {
+      8 => Glyph::Char(::std::char::from_u32(0x2590 - eighths as u32).unwrap_or(' ')),
+      _ => self.glyphs.get(Symbol::CkBoard),
+    }
+  }
+
+  /// Draws `fraction` (0.0 to 1.0) of a bar `cols` wide at `(y, x)`. The
+  /// empty part is cleared.
+  pub fn draw(&self, w: &Window, y: i32, x: i32, cols: i32, fraction: f64) -> i32 {
+    let cols = max(0, cols);
+    let steps = self.steps();
+    let filled = (clamp_fraction(fraction) * (cols as usize * steps) as f64).round() as usize;
+    let (full, part) = (filled / steps, filled % steps);
+
+    let (mut base_attrs, mut base_pair) = (0, 0);
+    ::wattr_get(w.raw(), &mut base_attrs, &mut base_pair);
+    let (attrs, pair) = self.style.resolve(base_attrs, base_pair);
+    w.attr_set(attrs, pair);
+
+    let block = self.glyphs.get(Symbol::Block);
+    let fill_end = full + (part > 0) as usize;
+    let mut ret = OK;
+    for i in 0..cols {
+      let cell = i as usize;
+      let g = if cell < full {
+        block
+      } else if cell == full && part > 0 {
+        self.partial(part * 8 / steps)
+      } else {
+        Glyph::Char(' ')
+      };
+      if cell == fill_end {
+        w.attr_set(base_attrs, base_pair);
+      }
+      if g.mvput(w, y, x + i) == ERR {
+        ret = ERR;
+      }
+    }
+    w.attr_set(base_attrs, base_pair);
+    ret
+  }
+}
+
+/// An indeterminate progress indicator, one cell wide.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Spinner {
+  frames: Vec<char>,
+  index: usize,
+}
+
+impl Default for Spinner {
+  fn default() -> Spinner {
+    Spinner::new()
+  }
+}
+
+impl Spinner {
+  /// Braille dots with Unicode glyphs, `|/-\` otherwise.
+  pub fn new() -> Spinner {
+    let frames = if Glyphs::current().set() == GlyphSet::Unicode {
+      "\u{280B}\u{2819}\u{2839}\u{2838}\u{283C}\u{2834}\u{2826}\u{2827}\u{2807}\u{280F}"
+    } else {
+      "|/-\\"
+    };
+    Spinner::with_frames(frames)
+  }
+
+  /// A spinner cycling through the characters of `frames`.
+  pub fn with_frames(frames: &str) -> Spinner {
+    let mut frames: Vec<char> = frames.chars().collect();
+    if frames.is_empty() {
+      frames.push(' ');
+    }
+    Spinner { frames, index: 0 }
+  }
+
+  /// Advances to the next frame.
+  pub fn tick(&mut self) {
+    self.index = (self.index + 1) % self.frames.len();
+  }
+
+  pub fn frame(&self) -> char {
+    self.frames[self.index]
+  }
+
+  pub fn draw(&self, w: &Window, y: i32, x: i32) -> i32 {
+    Glyph::Char(self.frame()).mvput(w, y, x)
+  }
+}
+
+/// A bar with a label centred over it; the filled part is drawn in
+/// reverse video, label included, so it reads on any terminal.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Gauge {
+  label: Option<String>,
+  style: Style,
+}
+
+impl Default for Gauge {
+  fn default() -> Gauge {
+    Gauge::new()
+  }
+}
+
+impl Gauge {
+  pub fn new() -> Gauge {
+    Gauge { label: None, style: Style::new().reverse() }
+  }
+
+  /// The text shown; defaults to the percentage.
+  pub fn label(mut self, label: &str) -> Gauge {
+    self.label = Some(label.to_string());
+    self
+  }
+
+  /// Style of the filled part.
+  pub fn style(mut self, style: Style) -> Gauge {
+    self.style = style;
+    self
+  }
+
+  pub fn draw(&self, w: &Window, y: i32, x: i32, cols: i32, fraction: f64) -> i32 {
+    let fraction = clamp_fraction(fraction);
+    let cols = max(0, cols);
+    let label = match self.label {
+      Some(ref l) => l.clone(),
+      None => format!("{:.0}%", fraction * 100.0),
+    };
+    let text = pad(&label, cols as usize, Align::Center);
+    let filled = min(cols, (fraction * cols as f64).round() as i32);
+
+    let (mut base_attrs, mut base_pair) = (0, 0);
+    ::wattr_get(w.raw(), &mut base_attrs, &mut base_pair);
+    let (attrs, pair) = self.style.resolve(base_attrs, base_pair);
+
+    // Split the label where the fill ends, on a character boundary.
+    let mut split = text.len();
+    let mut width = 0;
+    for (i, c) in text.char_indices() {
+      if width >= filled as usize {
+        split = i;
+        break;
+      }
+      width += ::width::char_width(c);
+    }
+    let (head, tail) = text.split_at(split);
+    w.attr_set(attrs, pair);
+    let mut ret = w.put_str_clipped(y, x, head, cols).unwrap_or(ERR);
+    w.attr_set(base_attrs, base_pair);
+    if !tail.is_empty() {
+      let tx = x + ::width::str_width(head) as i32;
+      ret = w.put_str_clipped(y, tx, tail, x + cols - tx).unwrap_or(ERR);
+    }
+    ret
+  }
+}
+
+/// Lets an update through at most once per interval.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Throttle {
+  interval: Duration,
+  last: Option<Instant>,
+}
+
+impl Throttle {
+  pub fn new(interval: Duration) -> Throttle {
+    Throttle { interval, last: None }
+  }
+
+  /// At most `fps` updates a second.
+  pub fn per_second(fps: u32) -> Throttle {
+    Throttle::new(Duration::from_secs(1) / max(1, fps))
+  }
+
+  /// Whether to update now. The first call always says yes.
+  pub fn ready(&mut self) -> bool {
+    let now = Instant::now();
+    match self.last {
+      Some(t) if now.duration_since(t) < self.interval => false,
+      _ => {
+        self.last = Some(now);
+        true
+      }
+    }
+  }
+
+  /// Makes the next `ready` say yes, e.g. to show the final state.
+  pub fn reset(&mut self) {
+    self.last = None;
+  }
+}
@@ -0,0 +1,437 @@
+/*
+    A table with a header row, sized columns and a row cursor.
+
+    Column widths come from `layout::Constraint`s, solved over the width
+    the table is drawn in. Like `List`, rows are fetched from the source
+    only as they are drawn. Horizontal scrolling is by whole columns: the
+    columns scrolled off to the left are skipped and the rest are laid out
+    over the full width.
+
+    Sorting is up to the source: the table keeps track of the sort column
+    and order, shows an arrow next to its title and calls
+    `TableSource::sort` when it changes.
+*/
+
+use std::borrow::Cow;
+use std::cmp::{max, min, Ordering};
+
+use curses::Window;
+use rect::Rect;
+use constants::*;
+use layout::{Layout, Constraint};
+use canvas::{Arms, LineStyle};
+use glyphs::{Glyphs, GlyphSet, Symbol};
+use styled::{Align, Style};
+use width::{ellipsize, pad};
+use widget::{Request, Response};
+
+/// Where a `Table` gets its rows from.
+pub trait TableSource {
+  fn rows(&self) -> usize;
+
+  /// The text of one cell.
+  fn cell(&self, row: usize, column: usize) -> Cow<'_, str>;
+
+  /// The style of a whole row, drawn over the window's attributes.
+  fn row_style(&self, _row: usize) -> Style {
+    Style::new()
+  }
+
+  /// Reorders the rows; called when the sort column or order changes.
+  /// The default leaves the rows as they are.
+  fn sort(&mut self, _column: usize, _order: SortOrder) {}
+}
+
+/// Compares as numbers when both cells are numbers, as text otherwise.
+fn compare_cells(a: &str, b: &str) -> Ordering {
+  match (a.trim().parse::<f64>(), b.trim().parse::<f64>()) {
+    (Ok(x), Ok(y)) => x.partial_cmp(&y).unwrap_or(Ordering::Equal),
+    _ => a.cmp(b),
+  }
+}
+
+impl<T: AsRef<str>> TableSource for Vec<Vec<T>> {
+  fn rows(&self) -> usize {
+    self.len()
+  }
+
+  fn cell(&self, row: usize, column: usize) -> Cow<'_, str> {
+    Cow::Borrowed(self[row].get(column).map_or("", |c| c.as_ref()))
+  }
+
+  fn sort(&mut self, column: usize, order: SortOrder) {
+    self.sort_by(|a, b| {
+      let a = a.get(column).map_or("", |c| c.as_ref());
+      let b = b.get(column).map_or("", |c| c.as_ref());
+      match order {
+        SortOrder::Ascending => compare_cells(a, b),
+        SortOrder::Descending => compare_cells(b, a),
+      }
+    });
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortOrder {
+  Ascending,
+  Descending,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Column {
+  title: String,
+  constraint: Constraint,
+  align: Align,
+}
+
+impl Column {
+  pub fn new(title: &str, constraint: Constraint) -> Column {
+    Column { title: title.to_string(), constraint, align: Align::Left }
+  }
+
+  /// Alignment of the cells, and of the title.
+  pub fn align(mut self, align: Align) -> Column {
+    self.align = align;
+    self
+  }
+}
+
+#[derive(Debug)]
+pub struct Table<S> {
+  source: S,
+  columns: Vec<Column>,
+  cursor: usize,
+  top: usize,
+  page: usize,
+  left: usize,
+  sort: Option<(usize, SortOrder)>,
+  separators: bool,
+  glyphs: Glyphs,
+  ellipsis: String,
+  header_style: Style,
+  cursor_style: Style,
+  /// Screen area of the header and `(column, x, width)` of each visible
+  /// column in it, from the last draw, for mouse events.
+  screen_header: Rect,
+  screen_columns: Vec<(usize, i32, i32)>,
+}
+
+impl<S: TableSource> Table<S> {
+  pub fn new(source: S, columns: Vec<Column>) -> Table<S> {
+    let glyphs = Glyphs::current();
+    let ellipsis = if glyphs.set() == GlyphSet::Unicode { "\u{2026}" } else { "~" };
+    Table {
+      source,
+      columns,
+      cursor: 0,
+      top: 0,
+      page: 1,
+      left: 0,
+      sort: None,
+      separators: true,
+      glyphs,
+      ellipsis: ellipsis.to_string(),
+      header_style: Style::new().bold(),
+      cursor_style: Style::new().reverse(),
+      screen_header: Rect::default(),
+      screen_columns: Vec::new(),
+    }
+  }
+
+  pub fn source(&self) -> &S {
+    &self.source
+  }
+
+  /// The source, for changing its rows. The cursor is clamped to the new
+  /// row count on the next draw.
+  pub fn source_mut(&mut self) -> &mut S {
+    &mut self.source
+  }
+
+  pub fn columns(&self) -> &[Column] {
+    &self.columns
+  }
+
+  /// Lines between columns and under the header.
+  pub fn separators(mut self, on: bool) -> Table<S> {
+    self.separators = on;
+    self
+  }
+
+  pub fn glyphs(mut self, glyphs: Glyphs) -> Table<S> {
+    self.glyphs = glyphs;
+    self
+  }
+
+  /// What cut-off cells end in.
+  pub fn ellipsis(mut self, ellipsis: &str) -> Table<S> {
+    self.ellipsis = ellipsis.to_string();
+    self
+  }
+
+  pub fn header_style(mut self, style: Style) -> Table<S> {
+    self.header_style = style;
+    self
+  }
+
+  pub fn cursor_style(mut self, style: Style) -> Table<S> {
+    self.cursor_style = style;
+    self
+  }
+
+  pub fn cursor(&self) -> usize {
+    self.cursor
+  }
+
+  pub fn set_cursor(&mut self, row: usize) {
+    self.cursor = min(row, self.source.rows().saturating_sub(1));
+    self.scroll_to_cursor();
+  }
+
+  /// The first column shown.
+  pub fn first_column(&self) -> usize {
+    self.left
+  }
+
+  pub fn sort(&self) -> Option<(usize, SortOrder)> {
+    self.sort
+  }
+
+  /// Sorts by `column`, or clears sorting with `None`.
+  pub fn set_sort(&mut self, sort: Option<(usize, SortOrder)>) {
+    self.sort = sort;
+    if let Some((column, order)) = sort {
+      self.source.sort(column, order);
+    }
+  }
+
+  /// Sorts by `column`, ascending first, then flipping the order on each
+  /// call for the same column.
+  pub fn toggle_sort(&mut self, column: usize) {
+    let order = match self.sort {
+      Some((c, SortOrder::Ascending)) if c == column => SortOrder::Descending,
+      _ => SortOrder::Ascending,
+    };
+    self.set_sort(Some((column, order)));
+  }
+
+  fn scroll_to_cursor(&mut self) {
+    if self.cursor < self.top {
+      self.top = self.cursor;
+    } else if self.cursor >= self.top + self.page {
+      self.top = self.cursor + 1 - self.page;
+    }
+  }
+
+  fn move_to(&mut self, row: usize) -> Response {
+    self.cursor = row;
+    self.scroll_to_cursor();
+    Response::Handled
+  }
+
+  /// Carries out `req`. `LeftItem`/`RightItem` scroll by a column.
+  pub fn driver(&mut self, req: Request) -> Response {
+    let rows = self.source.rows();
+    let last = rows.saturating_sub(1);
+    let c = self.cursor;
+    match req {
+      Request::LeftItem if self.left > 0 => {
+        self.left -= 1;
+        Response::Handled
+      }
+      Request::RightItem if self.left + 1 < self.columns.len() => {
+        self.left += 1;
+        Response::Handled
+      }
+      _ if rows == 0 => Response::Denied,
+      Request::UpItem | Request::PrevItem if c > 0 => self.move_to(c - 1),
+      Request::DownItem | Request::NextItem if c < last => self.move_to(c + 1),
+      Request::FirstItem => self.move_to(0),
+      Request::LastItem => self.move_to(last),
+      Request::ScrollUpLine if self.top > 0 => {
+        self.top -= 1;
+        self.move_to(min(c, self.top + self.page - 1))
+      }
+      Request::ScrollDownLine if self.top + self.page < rows => {
+        self.top += 1;
+        self.move_to(max(c, self.top))
+      }
+      Request::ScrollUpPage if c > 0 => self.move_to(c.saturating_sub(self.page)),
+      Request::ScrollDownPage if c < last => self.move_to(min(c + self.page, last)),
+      _ => Response::Denied,
+    }
+  }
+
+  /// Handles a key from `getch`, as `List::handle_key` does. Clicking a
+  /// column title sorts by that column.
+  pub fn handle_key(&mut self, key: i32) -> Response {
+    match key {
+      KEY_ENTER | 0x0A | 0x0D if self.source.rows() > 0 => Response::Activated(self.cursor),
+      #[cfg(ncurses_mouse)]
+      KEY_MOUSE => {
+        let mut ev = ::MEVENT { id: 0, x: 0, y: 0, z: 0, bstate: 0 };
+        if ::getmouse(&mut ev) == OK {
+          self.handle_mouse(&ev)
+        } else {
+          Response::Unknown
+        }
+      }
+      _ => match Request::from_key(key) {
+        Some(Request::Char(_)) | Some(Request::ToggleItem) => Response::Unknown,
+        Some(req) => self.driver(req),
+        None => Response::Unknown,
+      },
+    }
+  }
+
+  #[cfg(ncurses_mouse)]
+  pub fn handle_mouse(&mut self, ev: &::MEVENT) -> Response {
+    let b = ev.bstate as i32;
+    if b & BUTTON4_PRESSED != 0 {
+      for _ in 0..3 {
+        self.driver(Request::ScrollUpLine);
+      }
+      return Response::Handled;
+    }
+    if b & BUTTON5_PRESSED != 0 {
+      for _ in 0..3 {
+        self.driver(Request::ScrollDownLine);
+      }
+      return Response::Handled;
+    }
+    if b & (BUTTON1_CLICKED | BUTTON1_PRESSED | BUTTON1_DOUBLE_CLICKED) == 0 {
+      return Response::Unknown;
+    }
+    let h = self.screen_header;
+    if ev.y == h.y && ev.x >= h.x && ev.x < h.right() {
+      let hit = self.screen_columns.iter().find(|&&(_, x, w)| ev.x >= x && ev.x < x + w);
+      if let Some(&(column, _, _)) = hit {
+        self.toggle_sort(column);
+        return Response::Handled;
+      }
+      return Response::Unknown;
+    }
+    let first_row = h.y + self.header_lines();
+    if ev.y < first_row || ev.y >= first_row + self.page as i32 || ev.x < h.x || ev.x >= h.right() {
+      return Response::Unknown;
+    }
+    let row = self.top + (ev.y - first_row) as usize;
+    if row >= self.source.rows() {
+      return Response::Denied;
+    }
+    self.move_to(row);
+    if b & BUTTON1_DOUBLE_CLICKED != 0 {
+      Response::Activated(row)
+    } else {
+      Response::Handled
+    }
+  }
+
+  fn header_lines(&self) -> i32 {
+    if self.separators { 2 } else { 1 }
+  }
+
+  fn cell_text(&self, text: &str, width: i32, align: Align) -> String {
+    let width = max(0, width) as usize;
+    pad(&ellipsize(text, width, &self.ellipsis), width, align)
+  }
+
+  /// Draws the header and the visible rows into `area` of `w`. Rows past
+  /// the last one are cleared.
+  pub fn draw(&mut self, w: &Window, area: Rect) -> i32 {
+    let rows = self.source.rows();
+    let header = self.header_lines();
+    self.page = max(1, area.lines - header) as usize;
+    self.left = min(self.left, self.columns.len().saturating_sub(1));
+    self.cursor = min(self.cursor, rows.saturating_sub(1));
+    self.top = min(self.top, rows.saturating_sub(self.page));
+    self.scroll_to_cursor();
+
+    let visible = &self.columns[min(self.left, self.columns.len())..];
+    let spacing = if self.separators { 1 } else { 0 };
+    let layout = Layout::columns(visible.iter().map(|c| c.constraint).collect()).spacing(spacing);
+    let rects: Vec<Rect> = layout.split(Rect::new(0, area.x, 1, area.cols));
+
+    let (oy, ox) = w.origin();
+    self.screen_header = Rect::new(oy + area.y, ox + area.x, 1, area.cols);
+    self.screen_columns = rects.iter().enumerate().map(|(i, r)| (self.left + i, ox + r.x, r.cols)).collect();
+
+    let (mut base_attrs, mut base_pair) = (0, 0);
+    ::wattr_get(w.raw(), &mut base_attrs, &mut base_pair);
+    let vline = self.glyphs.get(Symbol::VLine);
+    let mut ret = OK;
+
+    // Header, with the sort arrow in place of the title's last column.
+    let (attrs, pair) = self.header_style.resolve(base_attrs, base_pair);
+    w.attr_set(attrs, pair);
+    w.put_str_padded(area.y, area.x, "", area.cols, Align::Left).ok();
+    for (i, (col, r)) in visible.iter().zip(&rects).enumerate() {
+      let sorted = self.sort.filter(|&(c, _)| c == self.left + i).map(|(_, order)| order);
+      let arrow = match sorted {
+        Some(SortOrder::Ascending) => Some(self.glyphs.get(Symbol::UArrow)),
+        Some(SortOrder::Descending) => Some(self.glyphs.get(Symbol::DArrow)),
+        None => None,
+      };
+      let room = if arrow.is_some() { r.cols - 1 } else { r.cols };
+      if room > 0 {
+        w.put_str_clipped(area.y, r.x, &self.cell_text(&col.title, room, col.align), room).ok();
+      }
+      if let (Some(a), true) = (arrow, r.cols > 0) {
+        a.mvput(w, area.y, r.x + r.cols - 1);
+      }
+    }
+
+    // Line under the header, crossing the column separators.
+    if self.separators && area.lines > 1 {
+      w.attr_set(base_attrs, base_pair);
+      let s = Some(LineStyle::Light);
+      let hline = self.glyphs.get(Symbol::HLine);
+      let cross = self.glyphs.junction(&Arms { up: s, down: s, left: s, right: s });
+      for x in area.x..area.right() {
+        let at_sep = rects.iter().take(rects.len().saturating_sub(1)).any(|r| r.right() == x);
+        if at_sep { cross } else { hline }.mvput(w, area.y + 1, x);
+      }
+    }
+    if self.separators {
+      let (attrs, pair) = self.header_style.resolve(base_attrs, base_pair);
+      w.attr_set(attrs, pair);
+      for r in rects.iter().take(rects.len().saturating_sub(1)) {
+        vline.mvput(w, area.y, r.right());
+      }
+    }
+
+    for line in 0..self.page as i32 {
+      let y = area.y + header + line;
+      if y >= area.bottom() {
+        break;
+      }
+      let row = self.top + line as usize;
+      if row >= rows {
+        w.attr_set(base_attrs, base_pair);
+        w.put_str_padded(y, area.x, "", area.cols, Align::Left).ok();
+        continue;
+      }
+      let mut style = self.source.row_style(row);
+      if row == self.cursor {
+        style = style.patch(self.cursor_style);
+      }
+      let (attrs, pair) = style.resolve(base_attrs, base_pair);
+      w.attr_set(attrs, pair);
+      if w.put_str_padded(y, area.x, "", area.cols, Align::Left).is_err() {
+        ret = ERR;
+      }
+      for (i, (col, r)) in visible.iter().zip(&rects).enumerate() {
+        if r.cols <= 0 {
+          continue;
+        }
+        let text = self.cell_text(&self.source.cell(row, self.left + i), r.cols, col.align);
+        w.put_str_clipped(y, r.x, &text, r.cols).ok();
+        if self.separators && i + 1 < rects.len() {
+          vline.mvput(w, y, r.right());
+        }
+      }
+    }
+    w.attr_set(base_attrs, base_pair);
+    ret
+  }
+}
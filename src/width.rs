@@ -0,0 +1,397 @@
+/*
+    Display width of text, in terminal columns.
+
+    `addnstr` and friends count bytes, but CJK ideographs and most emoji
+    take two columns and combining marks none. The tables below follow
+    Unicode's East Asian Width property, as `wcwidth` does, but are built
+    in so that results do not depend on the C library or the locale.
+
+    Text is measured and cut in grapheme clusters (a base character with
+    its combining marks, an emoji ZWJ sequence, a flag) so that a cut never
+    leaves an accent or half an emoji behind.
+*/
+
+use std::borrow::Cow;
+use std::ffi::NulError;
+
+use curses::Window;
+use constants::TABSIZE;
+
+/// Where a line shorter than its area goes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Align {
+  Left,
+  Center,
+  Right,
+}
+
+/// Ranges of characters that take no column of their own.
+static ZERO_WIDTH: &[(u32, u32)] = &[
+  (0x0300, 0x036F), (0x0483, 0x0489), (0x0591, 0x05BD), (0x05BF, 0x05BF),
+  (0x05C1, 0x05C2), (0x05C4, 0x05C5), (0x05C7, 0x05C7), (0x0610, 0x061A),
+  (0x064B, 0x065F), (0x0670, 0x0670), (0x06D6, 0x06DC), (0x06DF, 0x06E4),
+  (0x06E7, 0x06E8), (0x06EA, 0x06ED), (0x0711, 0x0711), (0x0730, 0x074A),
+  (0x07A6, 0x07B0), (0x07EB, 0x07F3), (0x0816, 0x0819), (0x081B, 0x0823),
+  (0x0825, 0x0827), (0x0829, 0x082D), (0x0859, 0x085B), (0x08D3, 0x08E1),
+  (0x08E3, 0x0902), (0x093A, 0x093A), (0x093C, 0x093C), (0x0941, 0x0948),
+  (0x094D, 0x094D), (0x0951, 0x0957), (0x0962, 0x0963), (0x0981, 0x0981),
+  (0x09BC, 0x09BC), (0x09C1, 0x09C4), (0x09CD, 0x09CD), (0x09E2, 0x09E3),
+  (0x0A01, 0x0A02), (0x0A3C, 0x0A3C), (0x0A41, 0x0A51), (0x0A70, 0x0A71),
+  (0x0A81, 0x0A82), (0x0ABC, 0x0ABC), (0x0AC1, 0x0AC8), (0x0ACD, 0x0ACD),
+  (0x0B01, 0x0B01), (0x0B3C, 0x0B3C), (0x0B3F, 0x0B3F), (0x0B41, 0x0B44),
+  (0x0B4D, 0x0B4D), (0x0BC0, 0x0BC0), (0x0BCD, 0x0BCD), (0x0C3E, 0x0C40),
+  (0x0C46, 0x0C56), (0x0CBC, 0x0CBC), (0x0CCC, 0x0CCD), (0x0D41, 0x0D44),
+  (0x0D4D, 0x0D4D), (0x0DCA, 0x0DCA), (0x0DD2, 0x0DD6), (0x0E31, 0x0E31),
+  (0x0E34, 0x0E3A), (0x0E47, 0x0E4E), (0x0EB1, 0x0EB1), (0x0EB4, 0x0EBC),
+  (0x0EC8, 0x0ECD), (0x0F18, 0x0F19), (0x0F35, 0x0F39), (0x0F71, 0x0F84),
+  (0x0F86, 0x0F87), (0x0F8D, 0x0FBC), (0x102D, 0x1030), (0x1032, 0x103A),
+  (0x1160, 0x11FF), (0x135D, 0x135F), (0x1712, 0x1714), (0x1732, 0x1734),
+  (0x17B4, 0x17B5), (0x17B7, 0x17BD), (0x17C6, 0x17D3), (0x180B, 0x180E),
+  (0x1AB0, 0x1AFF), (0x1B00, 0x1B03), (0x1B34, 0x1B3A), (0x1DC0, 0x1DFF),
+  (0x200B, 0x200F), (0x202A, 0x202E), (0x2060, 0x2064), (0x20D0, 0x20FF),
+  (0x2CEF, 0x2CF1), (0x2DE0, 0x2DFF), (0x302A, 0x302D), (0x3099, 0x309A),
+  (0xA66F, 0xA67D), (0xA69E, 0xA69F), (0xA6F0, 0xA6F1), (0xA8E0, 0xA8F1),
+  (0xFE00, 0xFE0F), (0xFE20, 0xFE2F), (0xFEFF, 0xFEFF), (0x1D167, 0x1D169),
+  (0x1D17B, 0x1D182), (0x1D185, 0x1D18B), (0x1D1AA, 0x1D1AD), (0xE0000, 0xE007F),
+  (0xE0100, 0xE01EF),
+];
+
+/// Ranges of characters that take two columns.
+static WIDE: &[(u32, u32)] = &[
+  (0x1100, 0x115F), (0x231A, 0x231B), (0x2329, 0x232A), (0x23E9, 0x23EC),
+  (0x23F0, 0x23F0), (0x23F3, 0x23F3), (0x25FD, 0x25FE), (0x2614, 0x2615),
+  (0x2648, 0x2653), (0x267F, 0x267F), (0x2693, 0x2693), (0x26A1, 0x26A1),
+  (0x26AA, 0x26AB), (0x26BD, 0x26BE), (0x26C4, 0x26C5), (0x26CE, 0x26CE),
+  (0x26D4, 0x26D4), (0x26EA, 0x26EA), (0x26F2, 0x26F3), (0x26F5, 0x26F5),
+  (0x26FA, 0x26FA), (0x26FD, 0x26FD), (0x2705, 0x2705), (0x270A, 0x270B),
+  (0x2728, 0x2728), (0x274C, 0x274C), (0x274E, 0x274E), (0x2753, 0x2755),
+  (0x2757, 0x2757), (0x2795, 0x2797), (0x27B0, 0x27B0), (0x27BF, 0x27BF),
+  (0x2B1B, 0x2B1C), (0x2B50, 0x2B50), (0x2B55, 0x2B55), (0x2E80, 0x303E),
+  (0x3041, 0x3247), (0x3250, 0x4DBF), (0x4E00, 0xA4CF), (0xA960, 0xA97F),
+  (0xAC00, 0xD7A3), (0xF900, 0xFAFF), (0xFE10, 0xFE19), (0xFE30, 0xFE6F),
+  (0xFF00, 0xFF60), (0xFFE0, 0xFFE6), (0x16FE0, 0x16FE4), (0x17000, 0x18CFF),
+  (0x1B000, 0x1B2FF), (0x1F004, 0x1F004), (0x1F0CF, 0x1F0CF), (0x1F18E, 0x1F18E),
+  (0x1F191, 0x1F19A), (0x1F200, 0x1F251), (0x1F300, 0x1F320), (0x1F32D, 0x1F335),
+  (0x1F337, 0x1F37C), (0x1F37E, 0x1F393), (0x1F3A0, 0x1F3CA), (0x1F3CF, 0x1F3D3),
+  (0x1F3E0, 0x1F3F0), (0x1F3F4, 0x1F3F4), (0x1F3F8, 0x1F43E), (0x1F440, 0x1F440),
+  (0x1F442, 0x1F4FC), (0x1F4FF, 0x1F53D), (0x1F54B, 0x1F54E), (0x1F550, 0x1F567),
+  (0x1F57A, 0x1F57A), (0x1F595, 0x1F596), (0x1F5A4, 0x1F5A4), (0x1F5FB, 0x1F64F),
+  (0x1F680, 0x1F6C5), (0x1F6CC, 0x1F6CC), (0x1F6D0, 0x1F6D2), (0x1F6D5, 0x1F6D7),
+  (0x1F6EB, 0x1F6EC), (0x1F6F4, 0x1F6FC), (0x1F7E0, 0x1F7EB), (0x1F90C, 0x1F93A),
+  (0x1F93C, 0x1F945), (0x1F947, 0x1F9FF), (0x1FA70, 0x1FAFF), (0x20000, 0x2FFFD),
+  (0x30000, 0x3FFFD),
+];
+
+fn in_table(table: &[(u32, u32)], c: u32) -> bool {
+  table.binary_search_by(|&(lo, hi)| {
+    if hi < c {
+      ::std::cmp::Ordering::Less
+    } else if lo > c {
+      ::std::cmp::Ordering::Greater
+    } else {
+      ::std::cmp::Ordering::Equal
+    }
+  }).is_ok()
+}
+
+/// Columns taken by `c`: 0, 1 or 2. Control characters count as 0, tabs
+/// included, since their width depends on the column; `expand_tabs`
+/// first text that may hold any.
+pub fn char_width(c: char) -> usize {
+  let cp = c as u32;
+  if cp < 0x20 || (0x7F..0xA0).contains(&cp) {
+    return 0;
+  }
+  if cp < 0x300 {
+    return 1;
+  }
+  if in_table(ZERO_WIDTH, cp) {
+    0
+  } else if in_table(WIDE, cp) {
+    2
+  } else {
+    1
+  }
+}
+
+/// Zero-width characters that are not marks: these stand on their own
+/// rather than joining the preceding cluster.
+fn is_format(cp: u32) -> bool {
+  matches!(cp, 0x200B | 0x200E | 0x200F | 0x202A..=0x202E | 0x2060..=0x2064 | 0xFEFF)
+}
+
+/// Whether `c` continues the cluster before it.
+fn is_extend(c: char) -> bool {
+  let cp = c as u32;
+  (0x1F3FB..=0x1F3FF).contains(&cp) || (cp >= 0x300 && in_table(ZERO_WIDTH, cp) && !is_format(cp))
+}
+
+fn is_regional_indicator(c: char) -> bool {
+  (0x1F1E6..=0x1F1FF).contains(&(c as u32))
+}
+
+/// Iterator over the grapheme clusters of a string.
+#[derive(Debug, Clone)]
+pub struct Graphemes<'a> {
+  rest: &'a str,
+}
+
+pub fn graphemes(s: &str) -> Graphemes<'_> {
+  Graphemes { rest: s }
+}
+
+impl<'a> Iterator for Graphemes<'a> {
+  type Item = &'a str;
+
+  fn next(&mut self) -> Option<&'a str> {
+    let mut chars = self.rest.char_indices();
+    let (_, first) = chars.next()?;
+    let mut end = first.len_utf8();
+    let mut prev = first;
+    let mut flags = is_regional_indicator(first) as u32;
+
+    if first == '\r' && self.rest[end..].starts_with('\n') {
+      end += 1;
+    } else if !first.is_control() {
+      for (i, c) in chars {
+        let joins = is_extend(c)
+          || (prev == '\u{200D}' && !c.is_control())
+          || (flags == 1 && is_regional_indicator(c));
+        if !joins {
+          break;
+        }
+        if is_regional_indicator(c) {
+          flags += 1;
+        }
+        prev = c;
+        end = i + c.len_utf8();
+      }
+    }
+
+    let (cluster, rest) = self.rest.split_at(end);
+    self.rest = rest;
+    Some(cluster)
+  }
+}
+
+/// Columns taken by a single grapheme cluster.
+pub fn grapheme_width(g: &str) -> usize {
+  let mut chars = g.chars();
+  let first = match chars.next() {
+    Some(c) => c,
+    None => return 0,
+  };
+  let w = char_width(first);
+  if w == 1 && chars.any(|c| c == '\u{FE0F}' || is_regional_indicator(c)) {
+    // Emoji presentation, or a flag.
+    2
+  } else {
+    w
+  }
+}
+
+/// Columns taken by `s`.
+pub fn str_width(s: &str) -> usize {
+  graphemes(s).map(grapheme_width).sum()
+}
+
+/// The longest prefix of `s` that fits in `cols` columns, and its width.
+pub fn truncate(s: &str, cols: usize) -> (&str, usize) {
+  let mut used = 0;
+  let mut end = 0;
+  for g in graphemes(s) {
+    let w = grapheme_width(g);
+    if used + w > cols {
+      break;
+    }
+    used += w;
+    end += g.len();
+  }
+  (&s[..end], used)
+}
+
+/// `s` cut to `cols` columns, ending in `ellipsis` if anything was cut.
+/// If not even the ellipsis fits, as much of it as fits is returned.
+pub fn ellipsize<'a>(s: &'a str, cols: usize, ellipsis: &str) -> Cow<'a, str> {
+  if str_width(s) <= cols {
+    return Cow::Borrowed(s);
+  }
+  let ew = str_width(ellipsis);
+  if ew > cols {
+    return Cow::Owned(truncate(ellipsis, cols).0.to_string());
+  }
+  let (head, _) = truncate(s, cols - ew);
+  Cow::Owned(format!("{}{}", head, ellipsis))
+}
+
+/// `s` with each tab replaced by the spaces up to the next tab stop, as
+/// curses expands it when `s` is written from column `col`, with stops
+/// every `TABSIZE` columns.
+pub fn expand_tabs(s: &str, col: usize) -> Cow<'_, str> {
+  if !s.contains('\t') {
+    return Cow::Borrowed(s);
+  }
+  let tabsize = match TABSIZE() {
+    t if t > 0 => t as usize,
+    _ => 8,
+  };
+  let mut out = String::with_capacity(s.len() + tabsize);
+  let mut at = col;
+  for g in graphemes(s) {
+    if g == "\t" {
+      let n = tabsize - at % tabsize;
+      out.extend((0..n).map(|_| ' '));
+      at += n;
+    } else {
+      out.push_str(g);
+      at += grapheme_width(g);
+    }
+  }
+  Cow::Owned(out)
+}
+
+/// `s` as it can be written from column `col` and take exactly the
+/// columns `str_width` says: tabs expanded, and other control characters,
+/// which curses would show as `^X` or act on, left out.
+pub fn printable(s: &str, col: usize) -> Cow<'_, str> {
+  match expand_tabs(s, col) {
+    Cow::Borrowed(t) if !t.chars().any(char::is_control) => Cow::Borrowed(t),
+    t => Cow::Owned(t.chars().filter(|c| !c.is_control()).collect()),
+  }
+}
+
+/// `s` padded with spaces to exactly `cols` columns, cut if it is wider.
+///
+/// A wide character that would straddle the edge is replaced by a space,
+/// so the result is always exactly `cols` columns. Control characters,
+/// tabs included, count as nothing, as in `char_width`; see `printable`.
+pub fn pad(s: &str, cols: usize, align: Align) -> String {
+  let (text, w) = truncate(s, cols);
+  let room = cols - w;
+  let left = match align {
+    Align::Left => 0,
+    Align::Center => room / 2,
+    Align::Right => room,
+  };
+  let mut out = String::with_capacity(text.len() + room);
+  out.extend((0..left).map(|_| ' '));
+  out.push_str(text);
+  out.extend((0..room - left).map(|_| ' '));
+  out
+}
+
+impl<'c> Window<'c> {
+  /// Writes as much of `s` at `(y, x)` as fits in `max_cols` columns and
+  /// before the right edge of the window, in whole grapheme clusters.
+  /// The text is made `printable` first.
+  pub fn put_str_clipped(&self, y: i32, x: i32, s: &str, max_cols: i32) -> Result<i32, NulError> {
+    let (_, cols) = self.size();
+    let room = ::std::cmp::min(max_cols, cols - x);
+    if room <= 0 || x < 0 {
+      return Ok(::constants::ERR);
+    }
+    let text = printable(s, x as usize);
+    let (text, _) = truncate(&text, room as usize);
+    self.mvaddstr(y, x, text)
+  }
+
+  /// Like `put_str_clipped`, but ends the text in `ellipsis` if it was cut.
+  pub fn put_str_ellipsized(&self, y: i32, x: i32, s: &str, max_cols: i32, ellipsis: &str) -> Result<i32, NulError> {
+    let (_, cols) = self.size();
+    let room = ::std::cmp::min(max_cols, cols - x);
+    if room <= 0 || x < 0 {
+      return Ok(::constants::ERR);
+    }
+    self.mvaddstr(y, x, &ellipsize(&printable(s, x as usize), room as usize, ellipsis))
+  }
+
+  /// Fills exactly `cols` columns at `(y, x)` with `s`, aligned and
+  /// padded with spaces, so that it overwrites whatever was there before.
+  pub fn put_str_padded(&self, y: i32, x: i32, s: &str, cols: i32, align: Align) -> Result<i32, NulError> {
+    let text = printable(s, ::std::cmp::max(0, x) as usize);
+    self.put_str_clipped(y, x, &pad(&text, ::std::cmp::max(0, cols) as usize, align), cols)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use constants::A_CHARTEXT;
+  use testing::with_screen;
+
+  /// Line `y` of `w` as text.
+  fn row(w: &Window, y: i32) -> String {
+    let (_, cols) = w.size();
+    (0..cols).map(|x| (::mvwinch(w.raw(), y, x) & A_CHARTEXT) as u8 as char).collect()
+  }
+
+  #[test]
+  fn graphemes_keep_clusters_whole() {
+    let clusters: Vec<&str> = graphemes("e\u{301}x\r\n\u{1F469}\u{200D}\u{1F4BB}\u{1F1EB}\u{1F1F7}\u{1F1E9}\t").collect();
+    assert_eq!(clusters, vec!["e\u{301}", "x", "\r\n", "\u{1F469}\u{200D}\u{1F4BB}",
+                              "\u{1F1EB}\u{1F1F7}", "\u{1F1E9}", "\t"]);
+    assert_eq!(graphemes("").count(), 0);
+    assert_eq!(graphemes("\u{200B}a").collect::<Vec<_>>(), vec!["\u{200B}", "a"]);
+  }
+
+  #[test]
+  fn widths() {
+    assert_eq!(str_width("abc"), 3);
+    assert_eq!(str_width("日本"), 4);
+    assert_eq!(str_width("e\u{301}"), 1);
+    assert_eq!(str_width("\u{2764}\u{FE0F}"), 2);
+    assert_eq!(str_width("\u{1F1EB}\u{1F1F7}"), 2);
+    assert_eq!(str_width("a\tb\x1b"), 2);
+  }
+
+  #[test]
+  fn truncate_never_splits_a_cluster() {
+    assert_eq!(truncate("abc", 5), ("abc", 3));
+    assert_eq!(truncate("abc", 2), ("ab", 2));
+    assert_eq!(truncate("a日本", 2), ("a", 1));
+    assert_eq!(truncate("ae\u{301}b", 2), ("ae\u{301}", 2));
+    assert_eq!(truncate("日本", 0), ("", 0));
+  }
+
+  #[test]
+  fn ellipsize_cuts_to_fit() {
+    assert_eq!(ellipsize("short", 5, "…"), "short");
+    assert_eq!(ellipsize("longer", 5, "…"), "long…");
+    assert_eq!(ellipsize("日本語", 5, "…"), "日本…");
+    assert_eq!(ellipsize("日本語", 4, "…"), "日…");
+    assert_eq!(ellipsize("longer", 2, "..."), "..");
+    assert!(matches!(ellipsize("fits", 4, "…"), Cow::Borrowed(_)));
+  }
+
+  #[test]
+  fn expand_tabs_to_the_next_stop() {
+    with_screen(|_| {
+      assert!(matches!(expand_tabs("no tabs", 3), Cow::Borrowed(_)));
+      assert_eq!(expand_tabs("a\tb", 0), "a       b");
+      assert_eq!(expand_tabs("a\tb", 5), "a  b");
+      assert_eq!(expand_tabs("日\t\t", 0), "日              ");
+      assert_eq!(printable("a\tb\x07", 6), "a b");
+    });
+  }
+
+  #[test]
+  fn control_characters_stay_within_max_cols() {
+    let text = "ab\x1b[1mcd\nef\u{85}gh\x7f";
+    with_screen(|curses| {
+      let w = curses.new_window(3, 20, 0, 0).unwrap();
+      for &(name, written) in &[("clipped", "ab[1mc"), ("ellipsized", "ab[1m~"), ("padded", "ab[1mc")] {
+        let expected = format!("..{}{}", written, ".".repeat(12));
+        w.bkgd('.' as ::ll::chtype);
+        w.erase();
+        match name {
+          "clipped" => w.put_str_clipped(1, 2, text, 6),
+          "ellipsized" => w.put_str_ellipsized(1, 2, text, 6, "~"),
+          _ => w.put_str_padded(1, 2, text, 6, Align::Left),
+        }.unwrap();
+        assert_eq!(w.cursor(), (1, 8), "{}", name);
+        assert_eq!(row(&w, 1), expected, "{}", name);
+        assert_eq!(row(&w, 2), ".".repeat(20), "{}", name);
+      }
+    });
+  }
+}
@@ -2,13 +2,17 @@
 
 #ifndef NCURSES_VERSION
 /*
- * NetBSD Curses only defines this as a macro
+ * NetBSD Curses and PDCurses only define these as macros
  */
 
 int (COLOR_PAIR)(int n) {
 	return COLOR_PAIR(n);
 }
 
+int (PAIR_NUMBER)(int n) {
+	return PAIR_NUMBER(n);
+}
+
 #endif
 
 /*
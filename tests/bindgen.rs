@@ -0,0 +1,272 @@
+/*
+    Checks the hand-written declarations in `ll.rs`, `menu/ll.rs` and
+    `panel/ll.rs` against the ones bindgen generates from the installed
+    headers. Run with `cargo test --features bindgen`; bindgen needs
+    libclang (`libclang-dev` on Debian and Ubuntu, or `LIBCLANG_PATH`
+    pointing at it), and the build fails with "Unable to find libclang"
+    without it. CI runs this in its own job, which installs it.
+
+    Both sides are read as source text. Types are reduced to what matters
+    for calling the function: integers to their signedness and width, and
+    every pointer, whatever it points to, to `ptr`. So `*mut i8` for a
+    `MENU *` passes, while a `short` declared as `c_int` does not.
+*/
+
+#![cfg(feature = "bindgen")]
+// `Option::is_none_or` needs Rust 1.82.
+#![allow(clippy::unnecessary_map_or)]
+
+extern crate ncurses;
+
+use std::collections::HashMap;
+use std::mem::size_of;
+use std::os::raw;
+
+const GENERATED: &str = include_str!(concat!(env!("OUT_DIR"), "/bindings.rs"));
+
+const HAND_WRITTEN: &[(&str, &str)] = &[
+  ("src/ll.rs", include_str!("../src/ll.rs")),
+  ("src/menu/ll.rs", include_str!("../src/menu/ll.rs")),
+  ("src/panel/ll.rs", include_str!("../src/panel/ll.rs")),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Signature {
+  params: Vec<String>,
+  ret: String,
+}
+
+impl Signature {
+  fn show(&self) -> String {
+    format!("({}) -> {}", self.params.join(", "), self.ret)
+  }
+}
+
+/// The functions and type aliases declared in a source file, with the
+/// types still as written.
+#[derive(Debug, Default)]
+struct Declarations {
+  functions: Vec<(String, Signature)>,
+  aliases: HashMap<String, String>,
+}
+
+fn strip_comments(src: &str) -> String {
+  let mut out = String::new();
+  let mut rest = src;
+  while let Some(start) = rest.find("/*") {
+    out.push_str(&rest[..start]);
+    rest = match rest[start..].find("*/") {
+      Some(end) => &rest[start + end + 2..],
+      None => "",
+    };
+  }
+  out.push_str(rest);
+  out.lines().map(|l| l.split("//").next().unwrap_or("")).collect::<Vec<_>>().join("\n")
+}
+
+/// Collapses whitespace and drops it next to punctuation, so that
+/// `pub fn f(arg1: *mut c_int)` and `pub fn f(_:*mut c_int)` read alike.
+fn normalize(src: &str) -> String {
+  const PUNCT: &str = "()[]{},:;<>*=&-!";
+  let collapsed = src.split_whitespace().collect::<Vec<_>>().join(" ");
+  let chars: Vec<char> = collapsed.chars().collect();
+  let mut out = String::with_capacity(chars.len());
+  for (i, &c) in chars.iter().enumerate() {
+    if c == ' ' {
+      let prev = out.chars().last();
+      let next = chars.get(i + 1).cloned();
+      if prev.map_or(true, |p| PUNCT.contains(p)) || next.map_or(true, |n| PUNCT.contains(n)) {
+        continue;
+      }
+    }
+    out.push(c);
+  }
+  out
+}
+
+/// Splits `s` at commas outside any brackets.
+fn split_top_level(s: &str) -> Vec<String> {
+  let mut parts = Vec::new();
+  let mut depth = 0i32;
+  let mut current = String::new();
+  for c in s.chars() {
+    match c {
+      '(' | '<' | '[' => depth += 1,
+      ')' | '>' | ']' => depth -= 1,
+      ',' if depth == 0 => {
+        parts.push(current.clone());
+        current.clear();
+        continue;
+      }
+      _ => {}
+    }
+    current.push(c);
+  }
+  if !current.is_empty() {
+    parts.push(current);
+  }
+  parts
+}
+
+fn parse(src: &str) -> Declarations {
+  let text = normalize(&strip_comments(src));
+  let mut decls = Declarations::default();
+
+  for item in text.split("pub type ").skip(1) {
+    if let (Some(eq), Some(end)) = (item.find('='), item.find(';')) {
+      if eq < end {
+        decls.aliases.insert(item[..eq].to_string(), item[eq + 1..end].to_string());
+      }
+    }
+  }
+
+  for item in text.split("pub fn ").skip(1) {
+    let open = match item.find('(') {
+      Some(i) => i,
+      None => continue,
+    };
+    let name = item[..open].to_string();
+    let mut depth = 0;
+    let mut close = None;
+    for (i, c) in item[open..].char_indices() {
+      match c {
+        '(' => depth += 1,
+        ')' => {
+          depth -= 1;
+          if depth == 0 {
+            close = Some(open + i);
+            break;
+          }
+        }
+        _ => {}
+      }
+    }
+    let close = match close {
+      Some(i) => i,
+      None => continue,
+    };
+    let rest = &item[close + 1..];
+    let end = rest.find([';', '{']).unwrap_or(rest.len());
+    if !rest[end..].starts_with(';') {
+      // A function with a body, not a declaration.
+      continue;
+    }
+    let ret = if rest.starts_with("->") { rest[2..end].to_string() } else { "()".to_string() };
+    let params = split_top_level(&item[open + 1..close]).into_iter().map(|p| {
+      if p == "..." {
+        return p;
+      }
+      // Drop the parameter name: `arg1:`, `_:`.
+      let name_len = p.find(|c: char| !(c.is_alphanumeric() || c == '_')).unwrap_or(0);
+      if p[name_len..].starts_with(':') && !p[name_len..].starts_with("::") {
+        p[name_len + 1..].to_string()
+      } else {
+        p
+      }
+    }).collect();
+    decls.functions.push((name, Signature { params, ret }));
+  }
+  decls
+}
+
+fn int(signed: bool, bytes: usize) -> String {
+  format!("{}{}", if signed { "i" } else { "u" }, bytes * 8)
+}
+
+fn primitive(name: &str) -> Option<String> {
+  Some(match name {
+    "i8" | "i16" | "i32" | "i64" | "u8" | "u16" | "u32" | "u64" | "f32" | "f64" | "()" | "..." => name.to_string(),
+    "c_char" => int(raw::c_char::MIN != 0, size_of::<raw::c_char>()),
+    "c_schar" => int(true, 1),
+    // ncurses' `bool` is an `unsigned char`; a C99 `_Bool` has the same
+    // size.
+    "c_uchar" | "bool" => int(false, 1),
+    "c_short" => int(true, size_of::<raw::c_short>()),
+    "c_ushort" => int(false, size_of::<raw::c_ushort>()),
+    "c_int" => int(true, size_of::<raw::c_int>()),
+    "c_uint" => int(false, size_of::<raw::c_uint>()),
+    "c_long" => int(true, size_of::<raw::c_long>()),
+    "c_ulong" => int(false, size_of::<raw::c_ulong>()),
+    "c_longlong" => int(true, size_of::<raw::c_longlong>()),
+    "c_ulonglong" => int(false, size_of::<raw::c_ulonglong>()),
+    "c_float" => "f32".to_string(),
+    "c_double" => "f64".to_string(),
+    _ => return None,
+  })
+}
+
+/// Reduces `ty` to a primitive or `ptr`, following `aliases`. Types that
+/// resolve to neither, such as structs passed by value, keep their name.
+fn canonical(ty: &str, aliases: &HashMap<String, String>) -> String {
+  let mut ty = ty.to_string();
+  for _ in 0..32 {
+    if ty.starts_with('*') || ty.starts_with("Option<") || ty.contains("fn(") {
+      return "ptr".to_string();
+    }
+    let name = ty.rsplit("::").next().unwrap_or(&ty).to_string();
+    if let Some(p) = primitive(&name) {
+      return p;
+    }
+    match aliases.get(&name) {
+      Some(t) => ty = t.clone(),
+      None => return name,
+    }
+  }
+  ty
+}
+
+fn canonical_signature(sig: &Signature, aliases: &HashMap<String, String>) -> Signature {
+  Signature {
+    params: sig.params.iter().map(|p| canonical(p, aliases)).collect(),
+    ret: canonical(&sig.ret, aliases),
+  }
+}
+
+#[test]
+fn hand_written_declarations_match_headers() {
+  let generated = parse(GENERATED);
+  let headers: HashMap<String, Signature> = generated.functions.iter()
+    .map(|(name, sig)| (name.clone(), canonical_signature(sig, &generated.aliases)))
+    .collect();
+
+  // `chtype` is declared once per `wide_chtype` setting; use the one the
+  // crate was built with.
+  let chtype = int(false, size_of::<ncurses::ll::chtype>());
+  let mut aliases = HashMap::new();
+  for &(_, src) in HAND_WRITTEN {
+    aliases.extend(parse(src).aliases);
+  }
+  aliases.insert("chtype".to_string(), chtype);
+
+  let mut mismatches = Vec::new();
+  let mut undeclared = Vec::new();
+  for &(file, src) in HAND_WRITTEN {
+    // A function declared more than once, under different `cfg`s, matches
+    // if any of its declarations does.
+    let mut variants: Vec<(String, Vec<Signature>)> = Vec::new();
+    for (name, sig) in parse(src).functions {
+      let sig = canonical_signature(&sig, &aliases);
+      match variants.iter_mut().find(|v| v.0 == name) {
+        Some(v) => v.1.push(sig),
+        None => variants.push((name, vec![sig])),
+      }
+    }
+    for (name, sigs) in variants {
+      match headers.get(&name) {
+        Some(expected) if !sigs.contains(expected) => mismatches.push(format!(
+          "{}: {}: declared {}, headers have {}",
+          file, name, sigs.iter().map(Signature::show).collect::<Vec<_>>().join(" or "), expected.show())),
+        Some(_) => {}
+        None => undeclared.push(format!("{}: {}", file, name)),
+      }
+    }
+  }
+
+  if !undeclared.is_empty() {
+    // Macros in the headers and the helpers in `wrap.c`; nothing to
+    // compare against.
+    eprintln!("not declared as functions by the headers:\n  {}", undeclared.join("\n  "));
+  }
+  assert!(mismatches.is_empty(), "{} declarations differ from the headers:\n  {}",
+          mismatches.len(), mismatches.join("\n  "));
+}